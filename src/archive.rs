@@ -0,0 +1,286 @@
+//! Download of a directory as one archive: GET of a directory under [crate::READ] or
+//! [crate::WRITE] with `?archive=zip`, `?archive=tar.gz` or `?archive=tar`. The archive is streamed
+//! while it's made (on a blocking thread), never stored.
+//!
+//! Like WebDAV listing, archives skip symlinks: Shares and sub-folders published on their own stay
+//! need-to-know.
+
+use crate::error::{self, Error};
+use crate::server::{accept_header, content_disposition, decode_tail, share_of};
+use core::str::FromStr;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use http::header::{HeaderValue, CONTENT_DISPOSITION, CONTENT_TYPE};
+use http::{Response, StatusCode};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tokio::fs as tokio_fs;
+use tokio::sync::mpsc;
+use tokio::task;
+use warp::hyper::body::Bytes;
+use warp::hyper::Body;
+use warp::path::Peek;
+use warp::{reject::Rejection, reply, Filter, Reply};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, DateTime, ZipWriter};
+
+/// How many bytes [ChannelWriter] collects before it sends them on.
+const CHUNK_BYTES: usize = 64 * 1024;
+
+/// How many chunks can wait for a slow client, before making the archive pauses.
+const CHUNKS_IN_FLIGHT: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    TarGz,
+    Tar,
+}
+
+impl ArchiveFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Zip => "zip",
+            Self::TarGz => "tar.gz",
+            Self::Tar => "tar",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Zip => "application/zip",
+            Self::TarGz => "application/gzip",
+            Self::Tar => "application/x-tar",
+        }
+    }
+}
+
+/// Parse `zip`, `tar.gz` (or `tgz`) or `tar`.
+impl FromStr for ArchiveFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "zip" => Ok(Self::Zip),
+            "tar.gz" | "tgz" => Ok(Self::TarGz),
+            "tar" => Ok(Self::Tar),
+            _ => Err(Error::BadRequest(format!("archive {s}"))),
+        }
+    }
+}
+
+/// Write an archive of `dir` (its files and directories, but not symlinks) to `out`. Its entries
+/// are under one top directory, `top`. Blocking.
+pub fn write_archive(
+    dir: &Path,
+    top: &str,
+    format: ArchiveFormat,
+    out: impl Write,
+) -> io::Result<()> {
+    match format {
+        ArchiveFormat::Zip => {
+            let mut zip = ZipWriter::new_stream(out);
+            walk(dir, Path::new(top), &mut |path, name, metadata| {
+                let name = name.to_string_lossy();
+                let options = SimpleFileOptions::default()
+                    .unix_permissions(metadata.permissions().mode() & 0o777)
+                    .last_modified_time(dos_time(metadata));
+                if metadata.is_dir() {
+                    zip.add_directory(name, options)?;
+                } else {
+                    let options = options
+                        .compression_method(CompressionMethod::Deflated)
+                        .large_file(metadata.len() >= u32::MAX as u64);
+                    zip.start_file(name, options)?;
+                    io::copy(&mut fs::File::open(path)?, &mut zip)?;
+                }
+                Ok(())
+            })?;
+            zip.finish()?;
+        }
+        ArchiveFormat::TarGz => {
+            let gz = write_tar(dir, top, GzEncoder::new(out, Compression::default()))?;
+            gz.finish()?;
+        }
+        ArchiveFormat::Tar => {
+            write_tar(dir, top, out)?;
+        }
+    }
+    Ok(())
+}
+
+/// Write a tar archive (see [write_archive]) to `out`, and return `out`.
+fn write_tar<W: Write>(dir: &Path, top: &str, out: W) -> io::Result<W> {
+    let mut tar = tar::Builder::new(out);
+    walk(dir, Path::new(top), &mut |path, name, metadata| {
+        if metadata.is_dir() {
+            tar.append_dir(name, path)
+        } else {
+            tar.append_file(name, &mut fs::File::open(path)?)
+        }
+    })?;
+    tar.into_inner()
+}
+
+/// Call `add` for `dir` itself (named `name`), and (recursively) for its files and directories,
+/// sorted by names. Skip symlinks (and anything else that isn't a file or a directory).
+fn walk(
+    dir: &Path,
+    name: &Path,
+    add: &mut dyn FnMut(&Path, &Path, &fs::Metadata) -> io::Result<()>,
+) -> io::Result<()> {
+    add(dir, name, &fs::metadata(dir)?)?;
+    let mut children = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    children.sort_by_key(|child| child.file_name());
+    for child in children {
+        let (path, child_name) = (child.path(), name.join(child.file_name()));
+        let metadata = fs::symlink_metadata(&path)?;
+        if metadata.is_dir() {
+            walk(&path, &child_name, add)?;
+        } else if metadata.is_file() {
+            add(&path, &child_name, &metadata)?;
+        }
+    }
+    Ok(())
+}
+
+/// Modification time (UTC) as ZIP (MS-DOS) has it, or the earliest it can have.
+fn dos_time(metadata: &fs::Metadata) -> DateTime {
+    let secs = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |since| since.as_secs());
+    let (days, secs_of_day) = (secs / 86_400, secs % 86_400);
+    // Civil date from days since 1970-01-01 (by Howard Hinnant's algorithm).
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    DateTime::from_date_and_time(
+        u16::try_from(year).unwrap_or(u16::MAX),
+        month as u8,
+        day as u8,
+        (secs_of_day / 3_600) as u8,
+        (secs_of_day % 3_600 / 60) as u8,
+        (secs_of_day % 60) as u8,
+    )
+    .unwrap_or_default()
+}
+
+/// Sends what is written to it through a channel, in chunks of [CHUNK_BYTES]. Writing fails once
+/// the receiver is gone (the client disconnected). Blocking.
+struct ChannelWriter {
+    sender: mpsc::Sender<io::Result<Bytes>>,
+    buffer: Vec<u8>,
+}
+
+impl ChannelWriter {
+    fn send(&mut self, chunk: io::Result<Bytes>) -> io::Result<()> {
+        self.sender
+            .blocking_send(chunk)
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))
+    }
+}
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        if self.buffer.len() >= CHUNK_BYTES {
+            self.flush()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.buffer.is_empty() {
+            let chunk = Bytes::from(std::mem::take(&mut self.buffer));
+            self.send(Ok(chunk))?;
+        }
+        Ok(())
+    }
+}
+
+/// A streamed response with an archive of `dir`.
+fn stream(dir: PathBuf, top: String, format: ArchiveFormat) -> Result<reply::Response, Error> {
+    let file_name = format!("{top}.{}", format.extension());
+    let disposition = HeaderValue::from_str(&content_disposition(&file_name))
+        .map_err(|e| Error::BadRequest(e.to_string()))?;
+
+    let (sender, mut receiver) = mpsc::channel(CHUNKS_IN_FLIGHT);
+    task::spawn_blocking(move || {
+        let mut writer = ChannelWriter {
+            sender,
+            buffer: Vec::with_capacity(CHUNK_BYTES),
+        };
+        // The status is sent already, so an error can only cut the body short.
+        let written = write_archive(&dir, &top, format, &mut writer);
+        if let Err(e) = written.and_then(|()| writer.flush()) {
+            let _ = writer.send(Err(e));
+        }
+    });
+    let body = futures_util::stream::poll_fn(move |cx| receiver.poll_recv(cx));
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(CONTENT_TYPE, format.content_type())
+        .header(CONTENT_DISPOSITION, disposition)
+        .body(Body::wrap_stream(body))
+        .map_err(|e| Error::Io(io::Error::other(e)))
+}
+
+/// Reply to GET of a directory with `?archive=...` with an archive of it. Reject anything else
+/// (with [warp::reject::not_found]), so that the request can go on through [Filter::or]. Its errors
+/// are replies (like in [crate::server::trash_delete]). Put it in front of the handlers of
+/// [crate::READ] and [crate::WRITE], after the URL prefix segment (and after
+/// [crate::server::method_policy]).
+pub fn archive(
+    symlinks_dir: impl Into<PathBuf>,
+) -> impl Filter<Extract = (reply::Response,), Error = Rejection> + Clone {
+    let symlinks_dir = symlinks_dir.into();
+    warp::get()
+        .and(warp::path::peek())
+        .and(warp::query::<HashMap<String, String>>())
+        .and(accept_header())
+        .and_then(
+            move |tail: Peek, query: HashMap<String, String>, accept: Option<String>| {
+                let symlinks_dir = symlinks_dir.clone();
+                async move {
+                    let Some(format) = query.get("archive") else {
+                        return Err(warp::reject::not_found());
+                    };
+                    let relative = decode_tail(&tail).map_err(|_| warp::reject::not_found())?;
+                    // Not the list of shares itself: Only (within) a share.
+                    if share_of(&symlinks_dir, &relative).await.is_none() {
+                        return Err(warp::reject::not_found());
+                    }
+                    let dir = symlinks_dir.join(&relative);
+                    if !tokio_fs::metadata(&dir).await.is_ok_and(|m| m.is_dir()) {
+                        return Err(warp::reject::not_found());
+                    }
+                    let top = relative
+                        .file_name()
+                        .map(|name| name.to_string_lossy().to_string())
+                        .unwrap_or_default();
+
+                    let response = format
+                        .parse::<ArchiveFormat>()
+                        .and_then(|format| stream(dir, top, format));
+                    Ok(response.unwrap_or_else(|e| {
+                        error::reply(&warp::reject::custom(e), accept.as_deref()).into_response()
+                    }))
+                }
+            },
+        )
+}
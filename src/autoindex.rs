@@ -0,0 +1,84 @@
+//! Directory listing for [crate::READ]. The DAV handler serves `index.html` of a directory (if
+//! any), but with [dav_server::DavConfig::indexfile] set it never generates a listing (it replies
+//! 404 instead). So this lists directories that have no `index.html`, and leaves the rest to the
+//! DAV handler.
+//!
+//! Like WebDAV listing, it doesn't show symlinks: Shares and sub-folders published on their own
+//! stay need-to-know.
+
+use crate::error::Error;
+use crate::manager::{self, Listed};
+use crate::server::{decode_tail, share_of};
+use askama::Template;
+use http::Method;
+use std::path::PathBuf;
+use tokio::fs as tokio_fs;
+use tokio::task;
+use warp::path::Peek;
+use warp::{reject::Rejection, reply, Filter, Reply};
+
+/// Name of the file that the DAV handler serves instead of a listing.
+pub const INDEX_FILE: &str = "index.html";
+
+#[derive(Template)]
+#[template(path = "read_index.html")]
+pub struct IndexTemplate<'a> {
+    /// The directory's path after the URL prefix segment (starting with the share's name).
+    pub path: &'a str,
+    pub entries: &'a [Listed],
+}
+
+impl IndexTemplate<'_> {
+    fn date(&self, listed: &Listed) -> String {
+        listed
+            .modified
+            .map(httpdate::fmt_http_date)
+            .unwrap_or_default()
+    }
+}
+
+/// List a directory on GET or HEAD, if its path ends with a slash and it has no [INDEX_FILE].
+/// Reject anything else (with [warp::reject::not_found]), so that the request can go on to the DAV
+/// handler through [Filter::or] (which also redirects directory paths without a trailing slash).
+/// Put it in front of the DAV handler of [crate::READ], after the URL prefix segment.
+pub fn autoindex(
+    symlinks_dir: impl Into<PathBuf>,
+) -> impl Filter<Extract = (reply::Response,), Error = Rejection> + Clone {
+    let symlinks_dir = symlinks_dir.into();
+    warp::method()
+        .and(warp::path::peek())
+        .and_then(move |method: Method, tail: Peek| {
+            let symlinks_dir = symlinks_dir.clone();
+            async move {
+                if !(method == Method::GET || method == Method::HEAD)
+                    || !tail.as_str().ends_with('/')
+                {
+                    return Err(warp::reject::not_found());
+                }
+                let relative = decode_tail(&tail).map_err(|_| warp::reject::not_found())?;
+                // Not the list of shares itself: Only (within) a share.
+                if share_of(&symlinks_dir, &relative).await.is_none() {
+                    return Err(warp::reject::not_found());
+                }
+                let dir = symlinks_dir.join(&relative);
+                let is_dir = tokio_fs::metadata(&dir).await.is_ok_and(|m| m.is_dir());
+                if !is_dir || tokio_fs::metadata(dir.join(INDEX_FILE)).await.is_ok() {
+                    return Err(warp::reject::not_found());
+                }
+
+                let entries = task::spawn_blocking(move || manager::list(&dir))
+                    .await
+                    .map_err(Error::from)?
+                    .map_err(Error::from)?;
+                let path = relative.display().to_string();
+                let path = path.trim_end_matches('/');
+                let html = IndexTemplate {
+                    path,
+                    entries: &entries,
+                }
+                .render()
+                .map_err(Error::from)?;
+                Ok(reply::html(html).into_response())
+            }
+        })
+}
@@ -0,0 +1,493 @@
+//! Tamper-evident log of write operations: `writes.jsonl` under [crate::LOGS], next to the logs of
+//! [crate::logs]. It has the [AccessRecord] of every request that may change anything (see
+//! [is_write]). Each line has the SHA-256 of the line before it ([ChainRecord::prev]), so changing
+//! or removing any line breaks the chain after it. Every [CHECKPOINT_EVERY]th record is a
+//! checkpoint, with an HMAC-SHA256 (keyed with the data key) of the chain so far. And `writes.head`
+//! has an HMAC of the last line, so that the log can't be cut short (nor rebuilt) without the key.
+//! [verify] checks all of that. But removing both files altogether looks like a log that was never
+//! written to. This log is never rotated.
+//!
+//! If the log doesn't resume cleanly (for example, its last line is partial after a crash, or the
+//! head doesn't match it), [WriteChain] sets both files aside as `writes.<time>.jsonl` and
+//! `writes.<time>.head`, and starts a new segment: A log whose first record
+//! ([ChainEntry::Segment]) is signed, and names the set-aside log and its hash.
+//!
+//! Everything here is blocking. Async handlers call it through [tokio::task::spawn_blocking].
+
+use crate::logs::{now_ms, AccessRecord};
+use crate::ENV_DATA_KEY;
+use core::fmt;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+pub const WRITES_LOG: &str = "writes.jsonl";
+/// The signed (HMAC) hash of the last line of [WRITES_LOG].
+pub const WRITES_HEAD: &str = "writes.head";
+pub const CHECKPOINT_EVERY: u64 = 100;
+/// [ChainRecord::prev] of the first record.
+pub const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// One line of [WRITES_LOG].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ChainRecord {
+    /// The line number, from 0.
+    pub seq: u64,
+    /// Hex SHA-256 of the previous line (without its newline), or [GENESIS].
+    pub prev: String,
+    #[serde(flatten)]
+    pub entry: ChainEntry,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChainEntry {
+    Write(AccessRecord),
+    Checkpoint {
+        /// Milliseconds since the Unix epoch.
+        time: u64,
+        /// Hex HMAC of `checkpoint:<seq>:<prev>:<time>`.
+        hmac: String,
+    },
+    /// Only the first record: The previous log didn't resume cleanly.
+    Segment {
+        /// Milliseconds since the Unix epoch.
+        time: u64,
+        /// File name of the previous log, set aside.
+        after: String,
+        /// Hex SHA-256 of the previous log (of nothing, if it was missing).
+        hash: String,
+        /// Why the previous log didn't resume.
+        problem: String,
+        /// Hex HMAC of `segment:<time>:<after>:<hash>:<problem>`.
+        hmac: String,
+    },
+}
+
+/// Content of [WRITES_HEAD].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Head {
+    seq: u64,
+    hash: String,
+    /// Hex HMAC of `head:<seq>:<hash>`.
+    hmac: String,
+}
+
+/// Where the next record goes.
+#[derive(Debug)]
+struct Tip {
+    next_seq: u64,
+    last_hash: String,
+}
+
+/// Whether requests with `method` go to [WRITES_LOG]: All but those that only read.
+pub fn is_write(method: &str) -> bool {
+    !matches!(method, "GET" | "HEAD" | "OPTIONS" | "PROPFIND")
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn hash(line: &str) -> String {
+    hex(&Sha256::digest(line.as_bytes()))
+}
+
+fn mac(key: &[u8], message: &str) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any size");
+    mac.update(message.as_bytes());
+    mac
+}
+
+fn sign(key: &[u8], message: &str) -> String {
+    hex(&mac(key, message).finalize().into_bytes())
+}
+
+fn is_signed(key: &[u8], message: &str, hmac: &str) -> bool {
+    from_hex(hmac).is_some_and(|tag| mac(key, message).verify_slice(&tag).is_ok())
+}
+
+fn checkpoint_message(seq: u64, prev: &str, time: u64) -> String {
+    format!("checkpoint:{seq}:{prev}:{time}")
+}
+
+fn head_message(seq: u64, hash: &str) -> String {
+    format!("head:{seq}:{hash}")
+}
+
+fn segment_message(time: u64, after: &str, hash: &str, problem: &str) -> String {
+    format!("segment:{time}:{after}:{hash}:{problem}")
+}
+
+/// Write `content` to `path` and flush it to disk.
+fn write_synced(path: &Path, content: &str) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(content.as_bytes())?;
+    file.sync_all()
+}
+
+/// Content of `path`, or [None] if it's missing.
+fn read_optional(path: &Path) -> io::Result<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Appends to [WRITES_LOG] (and [WRITES_HEAD]) in one directory. Clones share where the next record
+/// goes, so there should be only one (and its clones) per directory.
+#[derive(Clone)]
+pub struct WriteChain {
+    dir: PathBuf,
+    key: Arc<Vec<u8>>,
+    /// Read from the log on the first append.
+    tip: Arc<Mutex<Option<Tip>>>,
+}
+
+impl fmt::Debug for WriteChain {
+    /// Without the key.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WriteChain")
+            .field("dir", &self.dir)
+            .finish_non_exhaustive()
+    }
+}
+
+impl WriteChain {
+    pub fn new(dir: impl Into<PathBuf>, key: impl AsRef<[u8]>) -> Self {
+        Self {
+            dir: dir.into(),
+            key: Arc::new(key.as_ref().to_vec()),
+            tip: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub fn log_path(&self) -> PathBuf {
+        self.dir.join(WRITES_LOG)
+    }
+
+    pub fn head_path(&self) -> PathBuf {
+        self.dir.join(WRITES_HEAD)
+    }
+
+    /// Append `record`, then a checkpoint if one is due, and sign the new last line.
+    pub fn append(&self, record: AccessRecord) -> io::Result<()> {
+        let mut tip = self.tip.lock().unwrap_or_else(|e| e.into_inner());
+        if tip.is_none() {
+            *tip = Some(self.resume()?);
+        }
+        let result = self.append_record(tip.as_mut().expect("Resumed"), record);
+        if result.is_err() {
+            // A line may be partial now. Resume from the files next time.
+            *tip = None;
+        }
+        result
+    }
+
+    fn append_record(&self, tip: &mut Tip, record: AccessRecord) -> io::Result<()> {
+        self.append_entry(tip, ChainEntry::Write(record))?;
+        if (tip.next_seq + 1).is_multiple_of(CHECKPOINT_EVERY) {
+            let time = now_ms();
+            let hmac = sign(
+                &self.key,
+                &checkpoint_message(tip.next_seq, &tip.last_hash, time),
+            );
+            self.append_entry(tip, ChainEntry::Checkpoint { time, hmac })?;
+        }
+        self.sign_head(tip)
+    }
+
+    fn append_entry(&self, tip: &mut Tip, entry: ChainEntry) -> io::Result<()> {
+        let line = serde_json::to_string(&ChainRecord {
+            seq: tip.next_seq,
+            prev: tip.last_hash.clone(),
+            entry,
+        })?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.log_path())?;
+        file.write_all(format!("{line}\n").as_bytes())?;
+        // On disk before the head that signs it.
+        file.sync_data()?;
+        tip.next_seq += 1;
+        tip.last_hash = hash(&line);
+        Ok(())
+    }
+
+    fn sign_head(&self, tip: &Tip) -> io::Result<()> {
+        let seq = tip.next_seq - 1;
+        let head = Head {
+            seq,
+            hash: tip.last_hash.clone(),
+            hmac: sign(&self.key, &head_message(seq, &tip.last_hash)),
+        };
+        // Replaced in one rename, so that it's never half-written.
+        let staged = self.dir.join(format!(".{WRITES_HEAD}"));
+        write_synced(&staged, &serde_json::to_string(&head)?)?;
+        fs::rename(staged, self.head_path())
+    }
+
+    /// Where the next record goes: After the last line, if the head signs it. Otherwise in a new
+    /// segment.
+    fn resume(&self) -> io::Result<Tip> {
+        let content = read_optional(&self.log_path())?.unwrap_or_default();
+        let head = read_optional(&self.head_path())?;
+        match (content.lines().last(), head) {
+            (None, None) => Ok(Tip {
+                next_seq: 0,
+                last_hash: GENESIS.to_owned(),
+            }),
+            (Some(line), Some(head)) => match self.check_tip(&content, line, &head) {
+                Ok(tip) => Ok(tip),
+                Err(problem) => self.new_segment(&content, &problem),
+            },
+            (Some(_), None) => self.new_segment(&content, "the head is missing"),
+            (None, Some(_)) => self.new_segment(&content, "the log is missing or empty"),
+        }
+    }
+
+    /// The [Tip] after `line`, the last line of `content`, if it's complete and `head` signs it.
+    fn check_tip(&self, content: &str, line: &str, head: &str) -> Result<Tip, String> {
+        if !content.ends_with('\n') {
+            return Err("the last line is partial".to_owned());
+        }
+        let record = serde_json::from_str::<ChainRecord>(line)
+            .map_err(|e| format!("the last line is not a record ({e})"))?;
+        let head = serde_json::from_str::<Head>(head).map_err(|e| format!("not a head ({e})"))?;
+        let last_hash = hash(line);
+        if !is_signed(&self.key, &head_message(head.seq, &head.hash), &head.hmac)
+            || head.seq != record.seq
+            || head.hash != last_hash
+        {
+            return Err("the head doesn't sign the last line".to_owned());
+        }
+        Ok(Tip {
+            next_seq: record.seq + 1,
+            last_hash,
+        })
+    }
+
+    /// Set the log (with `content`) and the head aside, and start a new log with a signed
+    /// [ChainEntry::Segment].
+    fn new_segment(&self, content: &str, problem: &str) -> io::Result<Tip> {
+        let mut time = now_ms();
+        // Never over a log set aside before.
+        while self.dir.join(format!("writes.{time}.jsonl")).exists() {
+            time += 1;
+        }
+        let after = format!("writes.{time}.jsonl");
+        for (from, to) in [
+            (self.log_path(), self.dir.join(&after)),
+            (
+                self.head_path(),
+                self.dir.join(format!("writes.{time}.head")),
+            ),
+        ] {
+            match fs::rename(from, to) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+        log::error!(
+            "The log of write operations doesn't resume: {problem}. Set it aside as {after}, and \
+             started a new segment."
+        );
+        let hash = hash(content);
+        let hmac = sign(&self.key, &segment_message(time, &after, &hash, problem));
+        let mut tip = Tip {
+            next_seq: 0,
+            last_hash: GENESIS.to_owned(),
+        };
+        let segment = ChainEntry::Segment {
+            time,
+            after,
+            hash,
+            problem: problem.to_owned(),
+            hmac,
+        };
+        self.append_entry(&mut tip, segment)?;
+        self.sign_head(&tip)?;
+        Ok(tip)
+    }
+}
+
+/// What [verify] found intact.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Verified {
+    /// Including checkpoints.
+    pub records: u64,
+    pub checkpoints: u64,
+    /// File name of the previous log that this one is a new segment after (see
+    /// [ChainEntry::Segment]).
+    pub after: Option<String>,
+}
+
+/// Why [verify] failed.
+#[derive(Debug)]
+pub enum ChainError {
+    Io(io::Error),
+    /// Line `line` (from 1) of [WRITES_LOG] is not what the chain before it expects.
+    Broken {
+        line: u64,
+        problem: String,
+    },
+    /// [WRITES_HEAD] is missing, not signed with the key, or it doesn't match the last line.
+    Head(String),
+}
+
+impl From<io::Error> for ChainError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl fmt::Display for ChainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{e}"),
+            Self::Broken { line, problem } => write!(f, "Line {line}: {problem}."),
+            Self::Head(problem) => write!(f, "{WRITES_HEAD}: {problem}."),
+        }
+    }
+}
+
+impl Error for ChainError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Check [WRITES_LOG] and [WRITES_HEAD] under `dir` against `key`: That each line follows the one
+/// before it, that checkpoints are signed, and that the head is signed and matches the last line.
+/// That detects any line changed, removed or added (except by the holder of the key), and a log cut
+/// short. But not a log removed altogether (with its head): That verifies as empty.
+pub fn verify(dir: &Path, key: &[u8]) -> Result<Verified, ChainError> {
+    let content = read_optional(&dir.join(WRITES_LOG))?.unwrap_or_default();
+    let mut prev = GENESIS.to_owned();
+    let mut verified = Verified {
+        records: 0,
+        checkpoints: 0,
+        after: None,
+    };
+    for (seq, line) in (0u64..).zip(content.lines()) {
+        let broken = |problem: String| ChainError::Broken {
+            line: seq + 1,
+            problem,
+        };
+        let record = serde_json::from_str::<ChainRecord>(line)
+            .map_err(|e| broken(format!("not a record ({e})")))?;
+        if record.seq != seq {
+            return Err(broken(format!(
+                "sequence number {}, but expected {seq}",
+                record.seq
+            )));
+        }
+        if record.prev != prev {
+            return Err(broken(
+                "the hash of the previous line doesn't match (it was changed or removed)"
+                    .to_owned(),
+            ));
+        }
+        if let ChainEntry::Checkpoint { time, hmac } = &record.entry {
+            if !is_signed(key, &checkpoint_message(seq, &prev, *time), hmac) {
+                return Err(broken(
+                    "the checkpoint isn't signed with this key".to_owned(),
+                ));
+            }
+            verified.checkpoints += 1;
+        }
+        if let ChainEntry::Segment {
+            time,
+            after,
+            hash,
+            problem,
+            hmac,
+        } = &record.entry
+        {
+            if seq != 0 {
+                return Err(broken("a new segment, but not at the start".to_owned()));
+            }
+            if !is_signed(key, &segment_message(*time, after, hash, problem), hmac) {
+                return Err(broken("the segment isn't signed with this key".to_owned()));
+            }
+            verified.after = Some(after.clone());
+        }
+        prev = hash(line);
+        verified.records += 1;
+    }
+
+    let head = match fs::read_to_string(dir.join(WRITES_HEAD)) {
+        Ok(head) => serde_json::from_str::<Head>(&head)
+            .map_err(|e| ChainError::Head(format!("not a head ({e})")))?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound && verified.records == 0 => {
+            return Ok(verified)
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Err(ChainError::Head("missing".to_owned()))
+        }
+        Err(e) => return Err(e.into()),
+    };
+    if !is_signed(key, &head_message(head.seq, &head.hash), &head.hmac) {
+        return Err(ChainError::Head("not signed with this key".to_owned()));
+    }
+    if head.seq + 1 != verified.records {
+        return Err(ChainError::Head(format!(
+            "signed {} records, but the log has {}",
+            head.seq + 1,
+            verified.records
+        )));
+    }
+    if head.hash != prev {
+        return Err(ChainError::Head(
+            "the last line was changed after it was signed".to_owned(),
+        ));
+    }
+    Ok(verified)
+}
+
+/// `verify-log [dir]` command: [verify] the log under `dir` (by default [crate::LOGS]) with the
+/// data key, print the result, and exit with 1 if it failed.
+pub fn verify_command(dir: Option<&str>) -> io::Result<()> {
+    let dir = Path::new(dir.unwrap_or(crate::LOGS));
+    let key = env::var(ENV_DATA_KEY)
+        .map_err(|_| io::Error::other(format!("Requiring {ENV_DATA_KEY} env variable.")))?;
+    match verify(dir, key.as_bytes()) {
+        Ok(verified) => {
+            println!(
+                "{}: {} records ({} checkpoints), intact.",
+                dir.join(WRITES_LOG).display(),
+                verified.records,
+                verified.checkpoints
+            );
+            if let Some(after) = verified.after {
+                println!("It's a new segment after {after}, which didn't resume.");
+            }
+            Ok(())
+        }
+        Err(e) => {
+            eprintln!("{}: {e}", dir.join(WRITES_LOG).display());
+            std::process::exit(1);
+        }
+    }
+}
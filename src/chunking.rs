@@ -0,0 +1,464 @@
+//! Chunked uploads like Nextcloud's (and ownCloud's) "chunking v2", which desktop and mobile sync
+//! clients use for big files. Under [crate::CHUNKING], the first segment is a write hash. It stands
+//! for the user in Nextcloud's `/remote.php/dav/uploads/<user>/`, as [crate::WRITE] does for
+//! `/remote.php/dav/files/<user>/`:
+//!
+//! 1. `MKCOL /uploads/<hash>/<transfer-id>` creates an upload folder.
+//! 2. `PUT /uploads/<hash>/<transfer-id>/<number>` stores a chunk (numbered from 1 to
+//!    [MAX_CHUNKS]). PROPFIND of the folder lists the chunks that it has, so that a client can
+//!    resume.
+//! 3. `MOVE /uploads/<hash>/<transfer-id>/.file`, with `Destination: /write/<hash>/<path>` (and
+//!    optionally `OC-Total-Length`), assembles the chunks in the order of their numbers. The file
+//!    is moved to its destination (in one rename) only once it's complete.
+//!
+//! DELETE of the folder discards it. Folders expire [CHUNKS_EXPIRY] after their last chunk, and
+//! [crate::cleanup] purges them. Up to [MAX_UPLOAD_FOLDERS] are open per write hash. The share's
+//! method policy (of PUT) and [crate::quota] apply to the chunks received so far (together with
+//! what the share has already), and to the assembled file, like in [crate::tus].
+//!
+//! Upload folders are under [crate::CHUNKS], as `<hash>/<transfer-id>/`.
+
+use crate::error::{self, Error};
+use crate::manager::MAX_UPLOAD_BYTES;
+use crate::meta::ShareMeta;
+use crate::props::{log_failure, PropStore};
+use crate::quota;
+use crate::server::{accept_header, decode_tail, destination_relative, receive, share_of};
+use crate::versions::ShareVersions;
+use core::time::Duration;
+use futures_util::Stream;
+use http::header::{HeaderMap, CONTENT_TYPE};
+use http::{Method, Response, StatusCode};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tokio::fs as tokio_fs;
+use tokio::task;
+use warp::hyper::Body;
+use warp::path::Peek;
+use warp::{reject::Rejection, reply, Buf, Filter, Reply};
+
+/// Chunks are numbered from 1 up to this (like in Nextcloud).
+pub const MAX_CHUNKS: u32 = 10_000;
+
+/// How long an upload folder is kept after its last chunk.
+pub const CHUNKS_EXPIRY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Max. number of upload folders (including ones being assembled) per write hash.
+pub const MAX_UPLOAD_FOLDERS: usize = 16;
+
+/// The name that MOVE of an upload folder's assembled file has.
+pub const ASSEMBLED: &str = ".file";
+
+/// Prefix of an upload folder's name while MOVE assembles it, so that nothing else gets to it.
+/// (Transfer ids don't start with a dot.)
+const ASSEMBLING: &str = ".assembling-";
+
+/// Whether `transfer_id` (chosen by the client) is fine as a folder name.
+fn is_transfer_id(transfer_id: &str) -> bool {
+    !transfer_id.is_empty()
+        && transfer_id.len() <= 128
+        && !transfer_id.starts_with('.')
+        && transfer_id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"-_.".contains(&b))
+}
+
+/// Number of chunk `name`, if it's a chunk.
+fn chunk_number(name: &str) -> Option<u32> {
+    // Not "+1", nor "01".
+    if !name.bytes().all(|b| b.is_ascii_digit()) || name.starts_with('0') {
+        return None;
+    }
+    name.parse()
+        .ok()
+        .filter(|number| (1..=MAX_CHUNKS).contains(number))
+}
+
+/// Chunks in upload folder `dir`, ordered by their numbers, with their sizes. Blocking.
+pub fn chunks(dir: &Path) -> io::Result<Vec<(u32, u64)>> {
+    let mut chunks = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if let Some(number) = name.to_str().and_then(chunk_number) {
+            chunks.push((number, entry.metadata()?.len()));
+        }
+    }
+    chunks.sort();
+    Ok(chunks)
+}
+
+/// Write the chunks of upload folder `dir` one after another to `path`. Return the total size.
+/// Blocking.
+pub fn assemble(dir: &Path, path: &Path) -> io::Result<u64> {
+    let mut file = fs::File::create(path)?;
+    let mut total = 0;
+    for (number, _) in chunks(dir)? {
+        total += io::copy(
+            &mut fs::File::open(dir.join(number.to_string()))?,
+            &mut file,
+        )?;
+    }
+    file.sync_all()?;
+    Ok(total)
+}
+
+/// Remove upload folders (under the per-hash directories in `dir`) that weren't written to for
+/// [CHUNKS_EXPIRY] before `now`. Return how many. Blocking.
+pub fn purge_expired(dir: &Path, now: SystemTime) -> io::Result<usize> {
+    let mut purged = 0;
+    for link in fs::read_dir(dir)? {
+        for folder in fs::read_dir(link?.path())? {
+            let folder = folder?;
+            let modified = folder.metadata()?.modified()?;
+            if modified + CHUNKS_EXPIRY <= now {
+                fs::remove_dir_all(folder.path())?;
+                purged += 1;
+            }
+        }
+    }
+    Ok(purged)
+}
+
+/// Where upload folders are kept, and the metadata and versions of the shares that they go to.
+#[derive(Debug, Clone)]
+struct Dirs {
+    uploads: PathBuf,
+    /// Like [crate::META].
+    meta: PathBuf,
+    /// Like [crate::VERSIONS].
+    versions: PathBuf,
+}
+
+/// How many upload folders `link_dir` (of one write hash) has.
+async fn folders(link_dir: &Path) -> Result<usize, Error> {
+    let mut entries = match tokio_fs::read_dir(link_dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e.into()),
+    };
+    let mut count = 0;
+    while entries.next_entry().await?.is_some() {
+        count += 1;
+    }
+    Ok(count)
+}
+
+fn empty(status: StatusCode) -> Result<reply::Response, Error> {
+    Response::builder()
+        .status(status)
+        .body(Body::empty())
+        .map_err(|e| Error::Io(io::Error::other(e)))
+}
+
+fn header<'h>(headers: &'h HeaderMap, name: &str) -> Option<&'h str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+/// `207 Multi-Status` with upload folder `dir` (at `href`), and (with `Depth: 1`) its chunks.
+async fn propfind(dir: PathBuf, href: String, depth_one: bool) -> Result<reply::Response, Error> {
+    let listed = task::spawn_blocking(move || chunks(&dir)).await?;
+    let chunks = listed.map_err(|e| Error::from_io(e, "upload folder"))?;
+    let response = |href: &str, prop: &str| {
+        format!(
+            "<d:response><d:href>{href}</d:href><d:propstat><d:prop>{prop}</d:prop>\
+             <d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>"
+        )
+    };
+    let mut xml =
+        String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?><d:multistatus xmlns:d=\"DAV:\">");
+    xml += &response(&href, "<d:resourcetype><d:collection/></d:resourcetype>");
+    if depth_one {
+        for (number, size) in chunks {
+            xml += &response(
+                &format!("{href}{number}"),
+                &format!("<d:resourcetype/><d:getcontentlength>{size}</d:getcontentlength>"),
+            );
+        }
+    }
+    xml += "</d:multistatus>";
+    Response::builder()
+        .status(StatusCode::MULTI_STATUS)
+        .header(CONTENT_TYPE, "application/xml; charset=utf-8")
+        .body(Body::from(xml))
+        .map_err(|e| Error::Io(io::Error::other(e)))
+}
+
+/// Store chunk `number` of upload folder `dir`, within the limits of the share that `link` is for.
+async fn put_chunk(
+    symlinks_dir: &Path,
+    meta_dir: &Path,
+    link: &str,
+    dir: &Path,
+    number: u32,
+    body: impl Stream<Item = Result<impl Buf, warp::Error>>,
+) -> Result<reply::Response, Error> {
+    let share = share_of(symlinks_dir, Path::new(link))
+        .await
+        .ok_or_else(|| Error::NotFound(link.to_owned()))?;
+    let meta = ShareMeta::load_in(meta_dir, &share.name).await?;
+    let listed = {
+        let dir = dir.to_owned();
+        task::spawn_blocking(move || chunks(&dir)).await?
+    };
+    let chunks = listed.map_err(|e| Error::from_io(e, "upload folder"))?;
+    // What the other chunks leave for this one (this one may be sent again).
+    let others: u64 = chunks
+        .iter()
+        .filter(|(other, _)| *other != number)
+        .map(|(_, size)| size)
+        .sum();
+    let mut limit = MAX_UPLOAD_BYTES.saturating_sub(others);
+    let mut quota_limit = None;
+    if let Some(max) = meta.policy.max_put_bytes {
+        limit = limit.min(max.saturating_sub(others));
+    }
+    if let Some(remaining) = quota::remaining(share.dir.clone(), meta.quota_bytes, None).await? {
+        let remaining = remaining.saturating_sub(others);
+        limit = limit.min(remaining);
+        quota_limit = Some(remaining);
+    }
+
+    let path = dir.join(number.to_string());
+    let existed = tokio_fs::metadata(&path).await.is_ok();
+    if let Err(e) = receive(body, &path, Some(limit)).await {
+        let _ = tokio_fs::remove_file(&path).await;
+        return Err(match e {
+            Error::PayloadTooLarge { limit } if Some(limit) == quota_limit => Error::QuotaExceeded,
+            e => e,
+        });
+    }
+    empty(if existed {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::CREATED
+    })
+}
+
+/// Assemble upload folder `dir` into the `Destination` (under `write_prefix`), which has to be in
+/// the share that `link` is for.
+async fn move_assembled(
+    write_prefix: &str,
+    symlinks_dir: &Path,
+    dirs: &Dirs,
+    props: &PropStore,
+    link: &str,
+    dir: PathBuf,
+    headers: &HeaderMap,
+) -> Result<reply::Response, Error> {
+    let relative = header(headers, "destination")
+        .and_then(|destination| destination_relative(destination, write_prefix))
+        .ok_or_else(|| Error::BadRequest(format!("Destination under /{write_prefix}/")))?;
+    if relative.iter().next() != Some(link.as_ref()) || relative.iter().count() < 2 {
+        return Err(Error::Forbidden(
+            "a destination outside of this share".to_owned(),
+        ));
+    }
+    let share = share_of(symlinks_dir, &relative)
+        .await
+        .ok_or_else(|| Error::NotFound(link.to_owned()))?;
+    let path = symlinks_dir.join(&relative);
+    let what = share.within.to_string_lossy().to_string();
+    let parent_is_dir = match path.parent() {
+        Some(parent) => tokio_fs::metadata(parent).await.is_ok_and(|m| m.is_dir()),
+        None => false,
+    };
+    if !parent_is_dir {
+        return Err(Error::Conflict("no such directory".to_owned()));
+    }
+    let existing = tokio_fs::symlink_metadata(&path).await.ok();
+    match &existing {
+        Some(metadata) if !metadata.is_file() => return Err(Error::AlreadyExists(what)),
+        Some(_) if header(headers, "overwrite") == Some("F") => {
+            return Err(Error::PreconditionFailed(format!("{what} already exists")))
+        }
+        _ => {}
+    }
+
+    let listed = {
+        let dir = dir.clone();
+        task::spawn_blocking(move || chunks(&dir)).await?
+    };
+    let total: u64 = listed
+        .map_err(|e| Error::from_io(e, "upload folder"))?
+        .iter()
+        .map(|(_, size)| size)
+        .sum();
+    if let Some(expected) = header(headers, "oc-total-length") {
+        if expected.trim().parse::<u64>().ok() != Some(total) {
+            return Err(Error::BadRequest(format!(
+                "OC-Total-Length {expected}, but the chunks have {total} bytes"
+            )));
+        }
+    }
+    let meta = ShareMeta::load_in(&dirs.meta, &share.name).await?;
+    meta.policy.check(&Method::PUT, Some(total))?;
+    let remaining =
+        quota::remaining(share.dir.clone(), meta.quota_bytes, Some(path.clone())).await?;
+    quota::check(remaining, total)?;
+
+    // Take the folder, so that neither more chunks nor another MOVE get to it meanwhile.
+    let transfer_id = dir.file_name().unwrap_or_default().to_string_lossy();
+    let taken = dir.with_file_name(format!("{ASSEMBLING}{transfer_id}"));
+    tokio_fs::rename(&dir, &taken)
+        .await
+        .map_err(|e| Error::from_io(e, "upload folder"))?;
+    let versions_dir = dirs.versions.clone();
+    let moved = task::spawn_blocking(move || {
+        let assembled = taken.join(ASSEMBLED);
+        let result = assemble(&taken, &assembled)
+            .and_then(|_| {
+                ShareVersions::new(versions_dir, &share.name)
+                    .with_cap(meta.quota_bytes)
+                    .snapshot(&share.dir, &share.within, meta.versions)
+            })
+            .and_then(|()| fs::rename(&assembled, &path));
+        let _ = fs::remove_dir_all(&taken);
+        result
+    })
+    .await?;
+    moved?;
+    // A replaced file's extended attributes went with it, and so do its sidecar entries.
+    if existing.is_some() {
+        log_failure(
+            "removed",
+            relative.display(),
+            props.removed(&relative).await,
+        );
+    }
+    empty(if existing.is_some() {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::CREATED
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn handle(
+    prefix_segment: &str,
+    write_prefix: &str,
+    symlinks_dir: &Path,
+    dirs: &Dirs,
+    props: &PropStore,
+    method: Method,
+    tail: Peek,
+    headers: HeaderMap,
+    body: impl Stream<Item = Result<impl Buf, warp::Error>>,
+) -> Result<reply::Response, Error> {
+    let relative = decode_tail(&tail)?;
+    let segments: Vec<_> = relative
+        .iter()
+        .map(|segment| segment.to_string_lossy().to_string())
+        .collect();
+    let (Some(link), Some(transfer_id)) = (segments.first(), segments.get(1)) else {
+        return Err(Error::NotFound("upload folder".to_owned()));
+    };
+    // Only write hashes (of directories).
+    if share_of(symlinks_dir, Path::new(link)).await.is_none()
+        || !tokio_fs::metadata(symlinks_dir.join(link))
+            .await
+            .is_ok_and(|m| m.is_dir())
+    {
+        return Err(Error::NotFound(link.to_owned()));
+    }
+    if !is_transfer_id(transfer_id) {
+        return Err(Error::InvalidName(transfer_id.to_owned()));
+    }
+    let dir = dirs.uploads.join(link).join(transfer_id);
+    let dir_exists = tokio_fs::metadata(&dir).await.is_ok_and(|m| m.is_dir());
+    let not_found = || Error::NotFound(format!("upload folder {transfer_id}"));
+
+    match (method.as_str(), &segments[2..]) {
+        ("MKCOL", []) => {
+            if dir_exists {
+                return Err(Error::MethodNotAllowed(
+                    "MKCOL of an existing folder".to_owned(),
+                ));
+            }
+            let link_dir = dirs.uploads.join(link);
+            if folders(&link_dir).await? >= MAX_UPLOAD_FOLDERS {
+                return Err(Error::Conflict(format!(
+                    "{MAX_UPLOAD_FOLDERS} upload folders are open already"
+                )));
+            }
+            tokio_fs::create_dir_all(&dir).await?;
+            empty(StatusCode::CREATED)
+        }
+        ("DELETE", []) if dir_exists => {
+            tokio_fs::remove_dir_all(&dir).await?;
+            empty(StatusCode::NO_CONTENT)
+        }
+        ("PROPFIND", []) if dir_exists => {
+            let href = format!("/{prefix_segment}/{}", tail.as_str().trim_end_matches('/'));
+            propfind(
+                dir,
+                format!("{href}/"),
+                header(&headers, "depth") != Some("0"),
+            )
+            .await
+        }
+        ("PUT", [chunk]) if dir_exists => {
+            let number = chunk_number(chunk).ok_or_else(|| Error::InvalidName(chunk.to_owned()))?;
+            put_chunk(symlinks_dir, &dirs.meta, link, &dir, number, body).await
+        }
+        ("MOVE", [file]) if dir_exists && file == ASSEMBLED => {
+            move_assembled(write_prefix, symlinks_dir, dirs, props, link, dir, &headers).await
+        }
+        (_, [] | [_]) if !dir_exists => Err(not_found()),
+        (_, [] | [_]) => Err(Error::MethodNotAllowed(method.to_string())),
+        _ => Err(not_found()),
+    }
+}
+
+/// Handle [crate::CHUNKING] (see the module docs), after the URL prefix segment `prefix_segment`.
+/// Destinations are under `write_prefix` (like [crate::WRITE]), with write hashes as symlinks under
+/// `symlinks_dir`. Upload folders are under `uploads_dir` (on the same filesystem as the shares).
+/// The metadata of shares is under `meta_dir` (like [crate::META]), and versions of files that
+/// uploads replace go under `versions_dir` (like [crate::VERSIONS]). Their dead properties are in
+/// `props` (of `symlinks_dir`). Errors are replies.
+pub fn chunked_upload(
+    prefix_segment: &'static str,
+    write_prefix: &'static str,
+    symlinks_dir: impl Into<PathBuf>,
+    uploads_dir: impl Into<PathBuf>,
+    meta_dir: impl Into<PathBuf>,
+    versions_dir: impl Into<PathBuf>,
+    props: PropStore,
+) -> impl Filter<Extract = (reply::Response,), Error = Rejection> + Clone {
+    let symlinks_dir = symlinks_dir.into();
+    let dirs = Dirs {
+        uploads: uploads_dir.into(),
+        meta: meta_dir.into(),
+        versions: versions_dir.into(),
+    };
+    warp::method()
+        .and(warp::path::peek())
+        .and(warp::header::headers_cloned())
+        .and(accept_header())
+        .and(warp::body::stream())
+        .then(
+            move |method: Method, tail: Peek, headers: HeaderMap, accept: Option<String>, body| {
+                let (symlinks_dir, dirs) = (symlinks_dir.clone(), dirs.clone());
+                let props = props.clone();
+                async move {
+                    let handled = handle(
+                        prefix_segment,
+                        write_prefix,
+                        &symlinks_dir,
+                        &dirs,
+                        &props,
+                        method,
+                        tail,
+                        headers,
+                        body,
+                    )
+                    .await;
+                    handled.unwrap_or_else(|e| {
+                        error::reply(&warp::reject::custom(e), accept.as_deref()).into_response()
+                    })
+                }
+            },
+        )
+}
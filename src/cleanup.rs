@@ -0,0 +1,82 @@
+//! Periodic cleanup: Empties expired [crate::trash] of every share, and removes expired uploads
+//! (see [crate::tus] and [crate::chunking]).
+
+use crate::chunking;
+use crate::meta::ShareMeta;
+use crate::trash::ShareTrash;
+use crate::tus;
+use crate::{CHUNKS, CLEANUP_IN_PROGRESS, TRASH, TUS, UPLOADS};
+use core::time::Duration;
+use std::io;
+use std::path::Path;
+use std::time::SystemTime;
+use tokio::fs as tokio_fs;
+use tokio::task;
+use tokio::time::{self, MissedTickBehavior};
+
+/// How often to clean up.
+pub const CLEANUP_PERIOD: Duration = Duration::from_secs(60 * 60);
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Clean up once, and then every [CLEANUP_PERIOD]. Never returns. Failures are logged, and retried
+/// next time.
+pub async fn run() {
+    let mut interval = time::interval(CLEANUP_PERIOD);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        if let Err(e) = cleanup().await {
+            log::error!("Cleanup failed: {e}.");
+        }
+    }
+}
+
+/// Purge expired trash of all shares, and expired uploads, with [CLEANUP_IN_PROGRESS] present
+/// meanwhile.
+pub async fn cleanup() -> io::Result<()> {
+    tokio_fs::write(CLEANUP_IN_PROGRESS, "").await?;
+    let trash = purge_expired_trash().await;
+    let uploads = purge_expired_uploads().await;
+    tokio_fs::remove_file(CLEANUP_IN_PROGRESS).await?;
+    trash.and(uploads)
+}
+
+async fn purge_expired_uploads() -> io::Result<()> {
+    let dir = Path::new(UPLOADS).join(TUS);
+    let purged = task::spawn_blocking(move || {
+        let now = SystemTime::now();
+        Ok::<_, io::Error>(
+            tus::purge_expired(&dir, now)? + chunking::purge_expired(Path::new(CHUNKS), now)?,
+        )
+    })
+    .await
+    .map_err(|e| io::Error::other(e.to_string()))??;
+    if purged > 0 {
+        log::info!("Cleanup: purged {purged} expired upload(s).");
+    }
+    Ok(())
+}
+
+async fn purge_expired_trash() -> io::Result<()> {
+    let mut shares = tokio_fs::read_dir(TRASH).await?;
+    while let Some(share) = shares.next_entry().await? {
+        let name = share.file_name().to_string_lossy().to_string();
+        let trash_days = match ShareMeta::load(&name).await {
+            Ok(meta) => meta.trash_days,
+            Err(e) => {
+                log::error!("Cleanup: metadata of {name}: {e:?}. Skipping its trash.");
+                continue;
+            }
+        };
+        let max_age = Duration::from_secs(trash_days * SECONDS_PER_DAY);
+        let trash = ShareTrash::of(&name);
+        let purged = task::spawn_blocking(move || trash.purge_expired(max_age))
+            .await
+            .map_err(|e| io::Error::other(e.to_string()))??;
+        if purged > 0 {
+            log::info!("Cleanup: purged {purged} expired item(s) from trash of {name}.");
+        }
+    }
+    Ok(())
+}
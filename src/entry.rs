@@ -1,21 +1,10 @@
-use crate::{DIRS, SYMLINKS_READ, SYMLINKS_WRITE};
-#[cfg_attr(feature = "mock_entry", mockall_double::double)]
-pub use entry_mockable::Entry;
+use crate::fs::{file_name_leaf, FileSystem};
+use crate::DIRS;
+use core::fmt;
 use std::collections::HashMap;
-use std::fs;
+use std::error::Error;
 use std::io;
-use std::path::Path;
-
-mod entry_mockable;
-
-/// Require `path` leaf part not to be `..`.
-fn file_name_leaf(path: &Path) -> String {
-    assert!(false);
-    path.file_name()
-        .expect("The path must not be `..`")
-        .to_string_lossy()
-        .to_string()
-}
+use std::path::{Component, Path, PathBuf};
 
 #[derive(Debug)]
 pub enum SecondaryIncorrectKind {
@@ -47,4 +36,905 @@ pub enum ReadAndOrWriteIncorrectKind {
     },
 }
 
+/// Which directory of symlinks a secondary entry is under.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SecondaryDir {
+    /// [crate::SYMLINKS_READ]
+    Read,
+    /// [crate::SYMLINKS_WRITE]
+    Write,
+    /// [crate::SYMLINKS_APPEND]
+    Append,
+    /// [crate::SYMLINKS_DROP]
+    Drop,
+    /// [crate::SYMLINKS_FILE]
+    File,
+}
+
+/// Directory entry immediately below either [DIRS], and/or [crate::SYMLINKS_READ] and/or
+/// [crate::SYMLINKS_WRITE] and/or [crate::SYMLINKS_APPEND] and/or [crate::SYMLINKS_DROP] and/or
+/// [crate::SYMLINKS_FILE].
+#[derive(Debug)]
+pub enum Entry {
+    PrimaryOnly {
+        name: String,
+    },
+    PrimaryAndReadOnly {
+        name: String,
+    },
+    PrimaryAndReadWrite {
+        name: String,
+        // Write symlink (hash-based) source name
+        write_name: String,
+    },
+    PrimaryAndReadAndOrWriteIncorrect {
+        name: String,
+        kind: ReadAndOrWriteIncorrectKind,
+    },
+    /// Readable, and append-only through [crate::APPEND]: New files and directories, but no
+    /// DELETE, MOVE nor overwriting PUT.
+    PrimaryAndReadAppend {
+        name: String,
+        // Append symlink (hash-based) source name
+        append_name: String,
+    },
+    /// The append symlink is incorrect, and/or the primary is not (only) readable: For example,
+    /// it's also fully writable (which would defeat append-only).
+    PrimaryAndAppendIncorrect {
+        name: String,
+        append_name: String,
+        append: Result<(), SecondaryIncorrectKind>,
+        /// Classification before the append symlink.
+        also: Box<Entry>,
+    },
+    /// Write-only ("drop box"): Uploads through [crate::DROP], but no listing/reading.
+    PrimaryAndDropOnly {
+        name: String,
+        // Drop symlink (hash-based) source name
+        drop_name: String,
+    },
+    /// The drop symlink is incorrect, and/or the primary is also readable and/or writable (which
+    /// would defeat the drop box).
+    PrimaryAndDropIncorrect {
+        name: String,
+        drop_name: String,
+        drop: Result<(), SecondaryIncorrectKind>,
+        /// Classification before the drop symlink, unless it was [Entry::PrimaryOnly].
+        also: Option<Box<Entry>>,
+    },
+    /// Single-file share: A regular file under [DIRS]. Its symlinks (if any) are correct.
+    PrimaryFile {
+        name: String,
+        /// Published under [crate::SYMLINKS_READ].
+        read: bool,
+        /// Published under [crate::SYMLINKS_FILE]: Link (hash-based) source name.
+        link_name: Option<String>,
+    },
+    /// Single-file share with incorrect symlinks - or with any symlinks under
+    /// [crate::SYMLINKS_WRITE], [crate::SYMLINKS_APPEND] or [crate::SYMLINKS_DROP], which don't
+    /// apply to files ([Ok] if the symlink itself points to the file).
+    PrimaryFileIncorrect {
+        name: String,
+        read: bool,
+        link_name: Option<String>,
+        incorrect: Vec<(SecondaryDir, Result<(), SecondaryIncorrectKind>)>,
+    },
+    /// A symlink under [crate::SYMLINKS_FILE] for anything other than [Entry::PrimaryFile]: Those
+    /// links are for single files only.
+    FileLinkIncorrect {
+        name: String,
+        link_name: String,
+        link: Result<(), SecondaryIncorrectKind>,
+        /// Classification before the file link.
+        also: Box<Entry>,
+    },
+    /// Two or more symlinks under `under` for the same entry: Its hashed one (see [crate::links]),
+    /// and one named plainly by the share name (as made before links were hashed). `also` is the
+    /// classification with the hashed one, and `link_names` are the others.
+    DuplicateLinksIncorrect {
+        name: String,
+        under: SecondaryDir,
+        link_names: Vec<String>,
+        also: Box<Entry>,
+    },
+    /// Nested publication: A directory inside a share (at least two levels below [DIRS]), published
+    /// with its own name - under [crate::SYMLINKS_READ], and optionally [crate::SYMLINKS_WRITE].
+    /// Its symlinks (if any) are correct. (It has no primary: `name` is the symlinks' name.)
+    Nested {
+        name: String,
+        /// Relative to [DIRS]. Its first component is the share that it's in.
+        path: PathBuf,
+        read: bool,
+        // Write symlink (hash-based) source name
+        write_name: Option<String>,
+    },
+    /// Nested publication with incorrect symlinks - or with any under [crate::SYMLINKS_APPEND],
+    /// [crate::SYMLINKS_DROP] or [crate::SYMLINKS_FILE], which don't apply to it ([Ok] if the
+    /// symlink itself points to the same directory).
+    NestedIncorrect {
+        name: String,
+        path: PathBuf,
+        read: bool,
+        write_name: Option<String>,
+        incorrect: Vec<(SecondaryDir, Result<(), SecondaryIncorrectKind>)>,
+    },
+    /// Neither a directory nor a regular file (for example, an orphan symlink) under [DIRS].
+    PrimaryNonDir {
+        name: String,
+        path: PathBuf,
+    },
+
+    SecondaryIncorrect {
+        name: String,
+        under: SecondaryDir,
+        kind: SecondaryIncorrectKind,
+    },
+    /// Under two or more of [crate::SYMLINKS_READ], [crate::SYMLINKS_WRITE],
+    /// [crate::SYMLINKS_APPEND] and [crate::SYMLINKS_DROP], but not under [DIRS].
+    SecondariesIncorrect {
+        name: String,
+        read: Option<SecondaryIncorrectKind>,
+        write: Option<SecondaryIncorrectKind>,
+        append: Option<SecondaryIncorrectKind>,
+        drop: Option<SecondaryIncorrectKind>,
+    },
+}
+
+/// Where a correct (readable, writable, append or drop) symlink for primary `name` points to.
+fn primary_target(name: &str) -> String {
+    format!("{DIRS}/{name}")
+}
+
+/// Why [Entry] classification failed.
+#[derive(Debug)]
+pub enum EntryError {
+    Io(io::Error),
+    /// A path whose leaf is `..`, or that has no leaf.
+    NoFileName(PathBuf),
+    /// A transition (like [Entry::and_writable_symlink]) was invoked on a variant that it doesn't
+    /// apply to.
+    UnexpectedVariant {
+        expected: &'static str,
+        entry: Box<Entry>,
+    },
+}
+
+impl From<io::Error> for EntryError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl fmt::Display for EntryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{e}"),
+            Self::NoFileName(path) => write!(f, "Path {} has no file name.", path.display()),
+            Self::UnexpectedVariant { expected, entry } => write!(
+                f,
+                "Expected variant {expected}, but called on variant {entry:?}."
+            ),
+        }
+    }
+}
+
+impl Error for EntryError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+pub type EntryResult<T> = Result<T, EntryError>;
+
+/// Classify a secondary `path` (under any of the symlink directories) that is known to be incorrect
+/// (for example, because there is no primary).
+fn incorrect_secondary_kind<F: FileSystem + ?Sized>(
+    fs: &F,
+    path: &Path,
+) -> EntryResult<SecondaryIncorrectKind> {
+    Ok(if fs.is_symlink(path) {
+        let target = fs.read_link_full(path)?;
+        let is_orphan = !fs.exists(path);
+        SecondaryIncorrectKind::OrphanOrDifferentSymlink { target, is_orphan }
+    } else {
+        SecondaryIncorrectKind::NonSymlink {
+            is_dir: fs.is_dir(path),
+        }
+    })
+}
+
+/// If `path` is a symlink to a directory nested inside a share (at least two levels below [DIRS]),
+/// return that directory relative to [DIRS].
+fn nested_dir<F: FileSystem + ?Sized>(fs: &F, path: &Path) -> EntryResult<Option<PathBuf>> {
+    if !fs.is_symlink(path) || !fs.is_dir(path) {
+        return Ok(None);
+    }
+    let target = PathBuf::from(fs.read_link_full(path)?);
+    let Ok(relative) = target.strip_prefix(DIRS) else {
+        return Ok(None);
+    };
+    let is_nested = relative
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)))
+        && relative.components().count() >= 2;
+    Ok(is_nested.then(|| relative.to_path_buf()))
+}
+
+/// Classify a secondary `path`. It's correct ([Ok] of [Ok]) only if it's a symlink to
+/// `expected_target`.
+fn secondary_kind<F: FileSystem + ?Sized>(
+    fs: &F,
+    path: &Path,
+    expected_target: &str,
+) -> EntryResult<Result<(), SecondaryIncorrectKind>> {
+    if fs.is_symlink(path) && fs.read_link_full(path)? == expected_target {
+        Ok(Ok(()))
+    } else {
+        Ok(Err(incorrect_secondary_kind(fs, path)?))
+    }
+}
+
+impl Entry {
+    pub fn is_ok_and_complete(&self) -> bool {
+        matches!(
+            self,
+            Self::PrimaryAndReadOnly { .. }
+                | Self::PrimaryAndReadWrite { .. }
+                | Self::PrimaryAndReadAppend { .. }
+                | Self::PrimaryAndDropOnly { .. }
+        ) || matches!(self, Self::PrimaryFile { read, link_name, .. } if *read || link_name.is_some())
+            || matches!(self, Self::Nested { read: true, .. })
+    }
+    /// Published under [crate::SYMLINKS_READ] (a directory, or a [Entry::PrimaryFile]).
+    pub fn is_readable(&self) -> bool {
+        matches!(
+            self,
+            Self::PrimaryAndReadOnly { .. }
+                | Self::PrimaryAndReadWrite { .. }
+                | Self::PrimaryAndReadAppend { .. }
+                | Self::PrimaryFile { read: true, .. }
+                | Self::Nested { read: true, .. }
+        )
+    }
+    /// A nested publication (correct or not).
+    pub fn is_nested(&self) -> bool {
+        matches!(self, Self::Nested { .. } | Self::NestedIncorrect { .. })
+    }
+    /// Directory of a nested publication, relative to [DIRS].
+    pub fn nested_path(&self) -> Option<&Path> {
+        match &self {
+            Self::Nested { path, .. } | Self::NestedIncorrect { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+    /// A single-file share (correct or not).
+    pub fn is_file(&self) -> bool {
+        matches!(
+            self,
+            Self::PrimaryFile { .. } | Self::PrimaryFileIncorrect { .. }
+        )
+    }
+    /// Fully writable. (Not [Entry::is_appendable].)
+    pub fn is_writable(&self) -> bool {
+        matches!(self, Self::PrimaryAndReadWrite { .. })
+    }
+    pub fn is_appendable(&self) -> bool {
+        matches!(self, Self::PrimaryAndReadAppend { .. })
+    }
+    pub fn is_droppable(&self) -> bool {
+        matches!(self, Self::PrimaryAndDropOnly { .. })
+    }
+    pub fn name(&self) -> &str {
+        match &self {
+            Self::PrimaryOnly { name }
+            | Self::PrimaryAndReadOnly { name }
+            | Self::PrimaryAndReadWrite { name, .. }
+            | Self::PrimaryAndReadAndOrWriteIncorrect { name, .. }
+            | Self::PrimaryAndReadAppend { name, .. }
+            | Self::PrimaryAndAppendIncorrect { name, .. }
+            | Self::PrimaryAndDropOnly { name, .. }
+            | Self::PrimaryAndDropIncorrect { name, .. }
+            | Self::PrimaryFile { name, .. }
+            | Self::PrimaryFileIncorrect { name, .. }
+            | Self::FileLinkIncorrect { name, .. }
+            | Self::DuplicateLinksIncorrect { name, .. }
+            | Self::Nested { name, .. }
+            | Self::NestedIncorrect { name, .. }
+            | Self::PrimaryNonDir { name, .. }
+            | Self::SecondaryIncorrect { name, .. }
+            | Self::SecondariesIncorrect { name, .. } => name,
+        }
+    }
+    /// [Some] only for [Entry::PrimaryAndReadWrite], and for a readable [Entry::Nested] with a
+    /// write symlink.
+    pub fn write_name(&self) -> Option<&str> {
+        match &self {
+            Self::PrimaryAndReadWrite {
+                name: _,
+                write_name: write,
+            } => Some(write),
+            Self::Nested {
+                read: true,
+                write_name: Some(write_name),
+                ..
+            } => Some(write_name),
+            _ => None,
+        }
+    }
+    /// [Some] only for [Entry::PrimaryAndReadAppend].
+    pub fn append_name(&self) -> Option<&str> {
+        match &self {
+            Self::PrimaryAndReadAppend { append_name, .. } => Some(append_name),
+            _ => None,
+        }
+    }
+    /// [Some] only for [Entry::PrimaryAndDropOnly].
+    pub fn drop_name(&self) -> Option<&str> {
+        match &self {
+            Self::PrimaryAndDropOnly { drop_name, .. } => Some(drop_name),
+            _ => None,
+        }
+    }
+    /// [Some] only for [Entry::PrimaryFile] published under [crate::SYMLINKS_FILE].
+    pub fn link_name(&self) -> Option<&str> {
+        match &self {
+            Self::PrimaryFile {
+                link_name: Some(link_name),
+                ..
+            } => Some(link_name),
+            _ => None,
+        }
+    }
+
+    pub fn new_under_dirs<F: FileSystem + ?Sized>(fs: &F, path: PathBuf) -> EntryResult<Self> {
+        let name = file_name_leaf(&path)?;
+        Ok(if fs.is_dir(&path) {
+            Self::PrimaryOnly { name }
+        } else if fs.is_file(&path) {
+            Self::PrimaryFile {
+                name,
+                read: false,
+                link_name: None,
+            }
+        } else {
+            Self::PrimaryNonDir { name, path }
+        })
+    }
+
+    /// Add symlink `symlink_name` under `under` (with its `kind`) to [Entry::PrimaryFile] or
+    /// [Entry::PrimaryFileIncorrect]: A correct [SecondaryDir::Read] or [SecondaryDir::File] one
+    /// publishes the file. Any other makes it [Entry::PrimaryFileIncorrect].
+    fn and_file_secondary(
+        self,
+        under: SecondaryDir,
+        symlink_name: String,
+        kind: Result<(), SecondaryIncorrectKind>,
+    ) -> EntryResult<Self> {
+        let (name, mut read, mut link_name, mut incorrect) = match self {
+            Self::PrimaryFile {
+                name,
+                read,
+                link_name,
+            } => (name, read, link_name, Vec::new()),
+            Self::PrimaryFileIncorrect {
+                name,
+                read,
+                link_name,
+                incorrect,
+            } => (name, read, link_name, incorrect),
+            _ => {
+                return Err(EntryError::UnexpectedVariant {
+                    expected: "PrimaryFile or PrimaryFileIncorrect",
+                    entry: Box::new(self),
+                })
+            }
+        };
+        match (under, kind) {
+            (SecondaryDir::Read, Ok(())) => read = true,
+            (SecondaryDir::File, Ok(())) => link_name = Some(symlink_name),
+            (under, kind) => incorrect.push((under, kind)),
+        }
+        Ok(if incorrect.is_empty() {
+            Self::PrimaryFile {
+                name,
+                read,
+                link_name,
+            }
+        } else {
+            Self::PrimaryFileIncorrect {
+                name,
+                read,
+                link_name,
+                incorrect,
+            }
+        })
+    }
+
+    /// Add symlink `symlink_name` under `under` (with its `kind`) to [Entry::Nested] or
+    /// [Entry::NestedIncorrect]: A correct [SecondaryDir::Read] or [SecondaryDir::Write] one
+    /// publishes the directory. Any other makes it [Entry::NestedIncorrect].
+    fn and_nested_secondary(
+        self,
+        under: SecondaryDir,
+        symlink_name: String,
+        kind: Result<(), SecondaryIncorrectKind>,
+    ) -> EntryResult<Self> {
+        let (name, path, mut read, mut write_name, mut incorrect) = match self {
+            Self::Nested {
+                name,
+                path,
+                read,
+                write_name,
+            } => (name, path, read, write_name, Vec::new()),
+            Self::NestedIncorrect {
+                name,
+                path,
+                read,
+                write_name,
+                incorrect,
+            } => (name, path, read, write_name, incorrect),
+            _ => {
+                return Err(EntryError::UnexpectedVariant {
+                    expected: "Nested or NestedIncorrect",
+                    entry: Box::new(self),
+                })
+            }
+        };
+        match (under, kind) {
+            (SecondaryDir::Read, Ok(())) if !read => read = true,
+            (SecondaryDir::Write, Ok(())) if write_name.is_none() => {
+                write_name = Some(symlink_name)
+            }
+            (under, kind) => incorrect.push((under, kind)),
+        }
+        Ok(if incorrect.is_empty() {
+            Self::Nested {
+                name,
+                path,
+                read,
+                write_name,
+            }
+        } else {
+            Self::NestedIncorrect {
+                name,
+                path,
+                read,
+                write_name,
+                incorrect,
+            }
+        })
+    }
+
+    /// Where a correct symlink of [Entry::Nested] or [Entry::NestedIncorrect] points to.
+    fn nested_target(&self) -> Option<String> {
+        self.nested_path()
+            .map(|path| format!("{DIRS}/{}", path.display()))
+    }
+
+    /// Call on results of [Entry::new_under_dirs]. A [Entry::PrimaryNonDir] stays as-is.
+    pub fn and_readable_symlink<F: FileSystem + ?Sized>(
+        self,
+        fs: &F,
+        path: PathBuf,
+    ) -> EntryResult<Self> {
+        match self {
+            Self::PrimaryOnly { name } => {
+                Ok(match secondary_kind(fs, &path, &primary_target(&name))? {
+                    Ok(()) => Self::PrimaryAndReadOnly { name },
+                    Err(read) => Self::PrimaryAndReadAndOrWriteIncorrect {
+                        name,
+                        kind: ReadAndOrWriteIncorrectKind::PrimaryAndReadIncorrect {
+                            read,
+                            write: None,
+                        },
+                    },
+                })
+            }
+            Self::PrimaryFile { .. } => {
+                let read = secondary_kind(fs, &path, &primary_target(self.name()))?;
+                self.and_file_secondary(SecondaryDir::Read, file_name_leaf(&path)?, read)
+            }
+            Self::PrimaryNonDir { .. } => Ok(self),
+            _ => Err(EntryError::UnexpectedVariant {
+                expected: "PrimaryOnly, PrimaryFile or PrimaryNonDir",
+                entry: Box::new(self),
+            }),
+        }
+    }
+
+    fn _new_under_symlinks<F: FileSystem + ?Sized>(
+        fs: &F,
+        path: &Path,
+        under: SecondaryDir,
+    ) -> EntryResult<Self> {
+        let name = file_name_leaf(path)?;
+        if matches!(under, SecondaryDir::Read | SecondaryDir::Write) {
+            if let Some(path) = nested_dir(fs, path)? {
+                let write_name = (under == SecondaryDir::Write).then(|| name.clone());
+                return Ok(Self::Nested {
+                    read: under == SecondaryDir::Read,
+                    name,
+                    path,
+                    write_name,
+                });
+            }
+        }
+        let kind = incorrect_secondary_kind(fs, path)?;
+        Ok(Self::SecondaryIncorrect { name, under, kind })
+    }
+
+    pub fn new_under_readable_symlinks<F: FileSystem + ?Sized>(
+        fs: &F,
+        path: &Path,
+    ) -> EntryResult<Self> {
+        Self::_new_under_symlinks(fs, path, SecondaryDir::Read)
+    }
+
+    /// Call on results of [Entry::and_readable_symlink] or [Entry::new_under_readable_symlinks]. A
+    /// [Entry::PrimaryNonDir] stays as-is.
+    pub fn and_writable_symlink<F: FileSystem + ?Sized>(
+        self,
+        fs: &F,
+        path: PathBuf,
+    ) -> EntryResult<Self> {
+        let write_name = file_name_leaf(&path)?;
+
+        match self {
+            Self::PrimaryAndReadOnly { name } => {
+                Ok(match secondary_kind(fs, &path, &primary_target(&name))? {
+                    Ok(()) => Self::PrimaryAndReadWrite { name, write_name },
+                    Err(write) => Self::PrimaryAndReadAndOrWriteIncorrect {
+                        name,
+                        kind: ReadAndOrWriteIncorrectKind::PrimaryAndReadOkButWriteIncorrect {
+                            write_name,
+                            write,
+                        },
+                    },
+                })
+            }
+            Self::PrimaryOnly { name } => {
+                let kind = match secondary_kind(fs, &path, &primary_target(&name))? {
+                    Ok(()) => ReadAndOrWriteIncorrectKind::PrimaryAndWriteOnly { write_name },
+                    Err(write) => ReadAndOrWriteIncorrectKind::PrimaryAndWriteOnlyAndIncorrect {
+                        write_name,
+                        write,
+                    },
+                };
+                Ok(Self::PrimaryAndReadAndOrWriteIncorrect { name, kind })
+            }
+            Self::PrimaryAndReadAndOrWriteIncorrect {
+                name,
+                kind: ReadAndOrWriteIncorrectKind::PrimaryAndReadIncorrect { read, write: None },
+            } => {
+                let write = secondary_kind(fs, &path, &primary_target(&name))?;
+                Ok(Self::PrimaryAndReadAndOrWriteIncorrect {
+                    name,
+                    kind: ReadAndOrWriteIncorrectKind::PrimaryAndReadIncorrect {
+                        read,
+                        write: Some((write_name, write)),
+                    },
+                })
+            }
+            Self::SecondaryIncorrect {
+                name,
+                under: SecondaryDir::Read,
+                kind: read,
+            } => {
+                let write = incorrect_secondary_kind(fs, &path)?;
+                Ok(Self::SecondariesIncorrect {
+                    name,
+                    read: Some(read),
+                    write: Some(write),
+                    append: None,
+                    drop: None,
+                })
+            }
+            Self::Nested { .. } | Self::NestedIncorrect { .. } => {
+                let target = self.nested_target().unwrap_or_default();
+                let write = secondary_kind(fs, &path, &target)?;
+                self.and_nested_secondary(SecondaryDir::Write, write_name, write)
+            }
+            Self::PrimaryFile { .. } | Self::PrimaryFileIncorrect { .. } => {
+                let write = secondary_kind(fs, &path, &primary_target(self.name()))?;
+                self.and_file_secondary(SecondaryDir::Write, write_name, write)
+            }
+            Self::PrimaryNonDir { .. } => Ok(self),
+            _ => Err(EntryError::UnexpectedVariant {
+                expected: "PrimaryAndReadOnly, PrimaryOnly, PrimaryAndReadIncorrect without write, readable SecondaryIncorrect, Nested*, PrimaryFile* or PrimaryNonDir",
+                entry: Box::new(self),
+            }),
+        }
+    }
+
+    pub fn new_under_writable_symlinks<F: FileSystem + ?Sized>(
+        fs: &F,
+        path: &Path,
+    ) -> EntryResult<Self> {
+        Self::_new_under_symlinks(fs, path, SecondaryDir::Write)
+    }
+
+    /// Call on results of [Entry::and_writable_symlink] or [Entry::new_under_writable_symlinks] (or
+    /// of earlier steps, if there was no writable symlink). A [Entry::PrimaryNonDir] stays as-is.
+    pub fn and_append_symlink<F: FileSystem + ?Sized>(
+        self,
+        fs: &F,
+        path: PathBuf,
+    ) -> EntryResult<Self> {
+        if matches!(self, Self::DuplicateLinksIncorrect { .. }) {
+            return self.map_duplicated(|also| also.and_append_symlink(fs, path));
+        }
+        let append_name = file_name_leaf(&path)?;
+
+        match self {
+            Self::PrimaryAndReadOnly { name } => {
+                Ok(match secondary_kind(fs, &path, &primary_target(&name))? {
+                    Ok(()) => Self::PrimaryAndReadAppend { name, append_name },
+                    Err(append) => Self::PrimaryAndAppendIncorrect {
+                        name: name.clone(),
+                        append_name,
+                        append: Err(append),
+                        also: Box::new(Self::PrimaryAndReadOnly { name }),
+                    },
+                })
+            }
+            Self::PrimaryOnly { .. }
+            | Self::PrimaryAndReadWrite { .. }
+            | Self::PrimaryAndReadAndOrWriteIncorrect { .. } => {
+                let name = self.name().to_owned();
+                let append = secondary_kind(fs, &path, &primary_target(&name))?;
+                Ok(Self::PrimaryAndAppendIncorrect {
+                    name,
+                    append_name,
+                    append,
+                    also: Box::new(self),
+                })
+            }
+            Self::SecondaryIncorrect {
+                name,
+                under: under @ (SecondaryDir::Read | SecondaryDir::Write),
+                kind,
+            } => {
+                let append = Some(incorrect_secondary_kind(fs, &path)?);
+                let (read, write) = if under == SecondaryDir::Read {
+                    (Some(kind), None)
+                } else {
+                    (None, Some(kind))
+                };
+                Ok(Self::SecondariesIncorrect {
+                    name,
+                    read,
+                    write,
+                    append,
+                    drop: None,
+                })
+            }
+            Self::SecondariesIncorrect {
+                name,
+                read,
+                write,
+                append: None,
+                drop: None,
+            } => {
+                let append = Some(incorrect_secondary_kind(fs, &path)?);
+                Ok(Self::SecondariesIncorrect {
+                    name,
+                    read,
+                    write,
+                    append,
+                    drop: None,
+                })
+            }
+            Self::Nested { .. } | Self::NestedIncorrect { .. } => {
+                let target = self.nested_target().unwrap_or_default();
+                let append = secondary_kind(fs, &path, &target)?;
+                self.and_nested_secondary(SecondaryDir::Append, append_name, append)
+            }
+            Self::PrimaryFile { .. } | Self::PrimaryFileIncorrect { .. } => {
+                let append = secondary_kind(fs, &path, &primary_target(self.name()))?;
+                self.and_file_secondary(SecondaryDir::Append, append_name, append)
+            }
+            Self::PrimaryNonDir { .. } => Ok(self),
+            _ => Err(EntryError::UnexpectedVariant {
+                expected: "PrimaryOnly, PrimaryAndRead* (other than append), readable or writable SecondaryIncorrect, SecondariesIncorrect without append nor drop, Nested*, PrimaryFile* or PrimaryNonDir",
+                entry: Box::new(self),
+            }),
+        }
+    }
+
+    pub fn new_under_append_symlinks<F: FileSystem + ?Sized>(
+        fs: &F,
+        path: &Path,
+    ) -> EntryResult<Self> {
+        Self::_new_under_symlinks(fs, path, SecondaryDir::Append)
+    }
+
+    /// Call on results of [Entry::and_append_symlink] or [Entry::new_under_append_symlinks] (or of
+    /// earlier steps, if there was no append symlink). A [Entry::PrimaryNonDir] stays as-is.
+    pub fn and_drop_symlink<F: FileSystem + ?Sized>(
+        self,
+        fs: &F,
+        path: PathBuf,
+    ) -> EntryResult<Self> {
+        if matches!(self, Self::DuplicateLinksIncorrect { .. }) {
+            return self.map_duplicated(|also| also.and_drop_symlink(fs, path));
+        }
+        let drop_name = file_name_leaf(&path)?;
+
+        match self {
+            Self::PrimaryOnly { name } => {
+                Ok(match secondary_kind(fs, &path, &primary_target(&name))? {
+                    Ok(()) => Self::PrimaryAndDropOnly { name, drop_name },
+                    Err(drop) => Self::PrimaryAndDropIncorrect {
+                        name,
+                        drop_name,
+                        drop: Err(drop),
+                        also: None,
+                    },
+                })
+            }
+            Self::PrimaryAndReadOnly { .. }
+            | Self::PrimaryAndReadWrite { .. }
+            | Self::PrimaryAndReadAndOrWriteIncorrect { .. }
+            | Self::PrimaryAndReadAppend { .. }
+            | Self::PrimaryAndAppendIncorrect { .. } => {
+                let name = self.name().to_owned();
+                let drop = secondary_kind(fs, &path, &primary_target(&name))?;
+                Ok(Self::PrimaryAndDropIncorrect {
+                    name,
+                    drop_name,
+                    drop,
+                    also: Some(Box::new(self)),
+                })
+            }
+            Self::SecondaryIncorrect { name, under, kind } => {
+                let drop = Some(incorrect_secondary_kind(fs, &path)?);
+                let (read, write, append) = match under {
+                    SecondaryDir::Read => (Some(kind), None, None),
+                    SecondaryDir::Write => (None, Some(kind), None),
+                    SecondaryDir::Append => (None, None, Some(kind)),
+                    SecondaryDir::Drop | SecondaryDir::File => {
+                        return Err(EntryError::UnexpectedVariant {
+                            expected: "SecondaryIncorrect not under SYMLINKS_DROP nor SYMLINKS_FILE",
+                            entry: Box::new(Self::SecondaryIncorrect { name, under, kind }),
+                        })
+                    }
+                };
+                Ok(Self::SecondariesIncorrect {
+                    name,
+                    read,
+                    write,
+                    append,
+                    drop,
+                })
+            }
+            Self::SecondariesIncorrect {
+                name,
+                read,
+                write,
+                append,
+                drop: None,
+            } => {
+                let drop = Some(incorrect_secondary_kind(fs, &path)?);
+                Ok(Self::SecondariesIncorrect {
+                    name,
+                    read,
+                    write,
+                    append,
+                    drop,
+                })
+            }
+            Self::Nested { .. } | Self::NestedIncorrect { .. } => {
+                let target = self.nested_target().unwrap_or_default();
+                let drop = secondary_kind(fs, &path, &target)?;
+                self.and_nested_secondary(SecondaryDir::Drop, drop_name, drop)
+            }
+            Self::PrimaryFile { .. } | Self::PrimaryFileIncorrect { .. } => {
+                let drop = secondary_kind(fs, &path, &primary_target(self.name()))?;
+                self.and_file_secondary(SecondaryDir::Drop, drop_name, drop)
+            }
+            Self::PrimaryNonDir { .. } => Ok(self),
+            _ => Err(EntryError::UnexpectedVariant {
+                expected: "PrimaryOnly, PrimaryAndRead*, SecondaryIncorrect, SecondariesIncorrect without drop, Nested*, PrimaryFile* or PrimaryNonDir",
+                entry: Box::new(self),
+            }),
+        }
+    }
+
+    pub fn new_under_drop_symlinks<F: FileSystem + ?Sized>(
+        fs: &F,
+        path: &Path,
+    ) -> EntryResult<Self> {
+        Self::_new_under_symlinks(fs, path, SecondaryDir::Drop)
+    }
+
+    /// Call on results of [Entry::and_drop_symlink] or [Entry::new_under_drop_symlinks] (or of
+    /// earlier steps, if there was no drop symlink). A [Entry::PrimaryNonDir] stays as-is.
+    pub fn and_file_symlink<F: FileSystem + ?Sized>(
+        self,
+        fs: &F,
+        path: PathBuf,
+    ) -> EntryResult<Self> {
+        if matches!(self, Self::DuplicateLinksIncorrect { .. }) {
+            return self.map_duplicated(|also| also.and_file_symlink(fs, path));
+        }
+        let link_name = file_name_leaf(&path)?;
+        let link = secondary_kind(fs, &path, &primary_target(self.name()))?;
+
+        match self {
+            Self::PrimaryFile { .. } | Self::PrimaryFileIncorrect { .. } => {
+                self.and_file_secondary(SecondaryDir::File, link_name, link)
+            }
+            Self::PrimaryNonDir { .. } => Ok(self),
+            Self::FileLinkIncorrect { .. } => Err(EntryError::UnexpectedVariant {
+                expected: "anything but FileLinkIncorrect",
+                entry: Box::new(self),
+            }),
+            _ => Ok(Self::FileLinkIncorrect {
+                name: self.name().to_owned(),
+                link_name,
+                link,
+                also: Box::new(self),
+            }),
+        }
+    }
+
+    pub fn new_under_file_symlinks<F: FileSystem + ?Sized>(
+        fs: &F,
+        path: &Path,
+    ) -> EntryResult<Self> {
+        Self::_new_under_symlinks(fs, path, SecondaryDir::File)
+    }
+
+    /// Call after any of the `and_*_symlink` transitions (or `new_under_*_symlinks`) for symlinks
+    /// under `under`, with the `duplicates` of the symlink that it classified: Any make it
+    /// [Entry::DuplicateLinksIncorrect].
+    pub fn and_duplicate_links(
+        self,
+        under: SecondaryDir,
+        duplicates: &[PathBuf],
+    ) -> EntryResult<Self> {
+        if duplicates.is_empty() {
+            return Ok(self);
+        }
+        let link_names = duplicates
+            .iter()
+            .map(|path| file_name_leaf(path))
+            .collect::<EntryResult<_>>()?;
+        Ok(Self::DuplicateLinksIncorrect {
+            name: self.name().to_owned(),
+            under,
+            link_names,
+            also: Box::new(self),
+        })
+    }
+
+    /// Apply `transition` to what [Entry::DuplicateLinksIncorrect] wraps, and keep it wrapped. (Call
+    /// on that variant only.)
+    fn map_duplicated(
+        self,
+        transition: impl FnOnce(Self) -> EntryResult<Self>,
+    ) -> EntryResult<Self> {
+        match self {
+            Self::DuplicateLinksIncorrect {
+                name,
+                under,
+                link_names,
+                also,
+            } => Ok(Self::DuplicateLinksIncorrect {
+                name,
+                under,
+                link_names,
+                also: Box::new(transition(*also)?),
+            }),
+            _ => Err(EntryError::UnexpectedVariant {
+                expected: "DuplicateLinksIncorrect",
+                entry: Box::new(self),
+            }),
+        }
+    }
+}
+
 pub type EntriesMap = HashMap<String, Entry>;
@@ -0,0 +1,203 @@
+use crate::entry::EntryError;
+use askama::Template;
+use core::fmt;
+use http::StatusCode;
+use std::error::Error as StdError;
+use std::io;
+use tokio::task::JoinError;
+use warp::reject::{self, Reject, Rejection};
+use warp::reply::{self as warp_reply, Reply};
+
+/// Crate-wide error. Handlers reject with it (through [warp::reject::custom]), and [reply] maps it
+/// (and warp's own rejections) to a status code.
+#[derive(Debug)]
+pub enum Error {
+    NotFound(String),
+    AlreadyExists(String),
+    /// The request doesn't fit the current state, like a resumable upload at a different offset.
+    Conflict(String),
+    InvalidName(String),
+    BadRequest(String),
+    /// For example, an overwriting PUT in an append-only share.
+    PreconditionFailed(String),
+    /// Denied by the share's [crate::meta::MethodPolicy].
+    MethodNotAllowed(String),
+    LengthRequired,
+    UnsupportedMediaType(String),
+    PayloadTooLarge {
+        limit: u64,
+    },
+    QuotaExceeded,
+    Unauthorized,
+    Forbidden(String),
+    Io(io::Error),
+    Template(askama::Error),
+}
+
+impl Error {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::AlreadyExists(_) | Self::Conflict(_) => StatusCode::CONFLICT,
+            Self::InvalidName(_) | Self::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Self::PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
+            Self::MethodNotAllowed(_) => StatusCode::METHOD_NOT_ALLOWED,
+            Self::LengthRequired => StatusCode::LENGTH_REQUIRED,
+            Self::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            Self::PayloadTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::QuotaExceeded => StatusCode::INSUFFICIENT_STORAGE,
+            Self::Unauthorized => StatusCode::UNAUTHORIZED,
+            Self::Forbidden(_) => StatusCode::FORBIDDEN,
+            Self::Io(_) | Self::Template(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// Map [io::ErrorKind::NotFound] and [io::ErrorKind::AlreadyExists] to their own variants.
+    /// `what` describes the path/name (without leaking full filesystem paths to the client).
+    pub fn from_io(e: io::Error, what: impl Into<String>) -> Self {
+        match e.kind() {
+            io::ErrorKind::NotFound => Self::NotFound(what.into()),
+            io::ErrorKind::AlreadyExists => Self::AlreadyExists(what.into()),
+            _ => Self::Io(e),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound(what) => write!(f, "Not found: {what}."),
+            Self::AlreadyExists(what) => write!(f, "Already exists: {what}."),
+            Self::Conflict(what) => write!(f, "Conflict: {what}."),
+            Self::InvalidName(name) => write!(f, "Invalid name: {name}."),
+            Self::BadRequest(what) => write!(f, "Bad request: {what}."),
+            Self::PreconditionFailed(what) => write!(f, "Precondition failed: {what}."),
+            Self::MethodNotAllowed(method) => {
+                write!(f, "Method not allowed in this share: {method}.")
+            }
+            Self::LengthRequired => write!(f, "Length required."),
+            Self::UnsupportedMediaType(what) => write!(f, "Unsupported media type: {what}."),
+            Self::PayloadTooLarge { limit } => {
+                write!(
+                    f,
+                    "Payload too large. This share accepts up to {limit} bytes."
+                )
+            }
+            Self::QuotaExceeded => write!(f, "Quota exceeded."),
+            Self::Unauthorized => write!(f, "Unauthorized."),
+            Self::Forbidden(what) => write!(f, "Forbidden: {what}."),
+            // Don't leak details of internal errors to the client. They are in the log.
+            Self::Io(_) | Self::Template(_) => write!(f, "Internal server error."),
+        }
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Template(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<EntryError> for Error {
+    fn from(e: EntryError) -> Self {
+        match e {
+            EntryError::Io(e) => Self::Io(e),
+            other => Self::Io(io::Error::other(other.to_string())),
+        }
+    }
+}
+
+/// A panicked (or cancelled) [tokio::task::spawn_blocking] task.
+impl From<JoinError> for Error {
+    fn from(e: JoinError) -> Self {
+        Self::Io(io::Error::other(e.to_string()))
+    }
+}
+
+impl From<askama::Error> for Error {
+    fn from(e: askama::Error) -> Self {
+        Self::Template(e)
+    }
+}
+
+impl Reject for Error {}
+
+#[derive(Template)]
+#[template(path = "error.html")]
+pub struct ErrorTemplate {
+    pub status: StatusCode,
+    pub message: String,
+}
+
+/// Status code and a client-facing message for `rejection`.
+pub fn status_and_message(rejection: &Rejection) -> (StatusCode, String) {
+    if let Some(e) = rejection.find::<Error>() {
+        if e.status().is_server_error() {
+            log::error!("Error: {e:?}");
+        }
+        (e.status(), e.to_string())
+    } else if rejection.is_not_found() {
+        (StatusCode::NOT_FOUND, "Not found.".to_owned())
+    } else if rejection.find::<reject::MethodNotAllowed>().is_some() {
+        (
+            StatusCode::METHOD_NOT_ALLOWED,
+            "Method not allowed.".to_owned(),
+        )
+    } else if rejection.find::<reject::LengthRequired>().is_some() {
+        (StatusCode::LENGTH_REQUIRED, "Length required.".to_owned())
+    } else if rejection.find::<reject::PayloadTooLarge>().is_some() {
+        (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "Payload too large.".to_owned(),
+        )
+    } else if rejection.find::<reject::InvalidQuery>().is_some()
+        || rejection.find::<reject::InvalidHeader>().is_some()
+        || rejection.find::<reject::MissingHeader>().is_some()
+    {
+        (StatusCode::BAD_REQUEST, "Bad request.".to_owned())
+    } else {
+        log::error!("Unhandled rejection: {rejection:?}");
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Internal server error.".to_owned(),
+        )
+    }
+}
+
+/// Whether the client prefers JSON over HTML, based on its `Accept` header (if any).
+fn accepts_json(accept: Option<&str>) -> bool {
+    accept
+        .is_some_and(|accept| accept.contains("application/json") && !accept.contains("text/html"))
+}
+
+/// Reply for `rejection`: a JSON body if `accept` asks for JSON, HTML otherwise.
+pub fn reply(rejection: &Rejection, accept: Option<&str>) -> Box<dyn Reply> {
+    let (status, message) = status_and_message(rejection);
+
+    if accepts_json(accept) {
+        let body = serde_json::json!({
+            "status": status.as_u16(),
+            "error": message,
+        });
+        Box::new(warp_reply::with_status(warp_reply::json(&body), status))
+    } else {
+        let template = ErrorTemplate { status, message };
+        match template.render() {
+            Ok(html) => Box::new(warp_reply::with_status(warp_reply::html(html), status)),
+            Err(e) => {
+                log::error!("Error rendering error page: {e:?}");
+                Box::new(status)
+            }
+        }
+    }
+}
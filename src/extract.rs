@@ -0,0 +1,489 @@
+//! Upload of an archive that is extracted into a directory of a writable share: PUT or POST of a
+//! zip or tar (plain or gzipped) to an existing directory under [crate::WRITE], with an
+//! [EXTRACT_HEADER] header or an `extract` query parameter that names the format (as
+//! [ArchiveFormat] parses it). For example
+//! `curl --data-binary @site.zip 'https://.../write/<hash>/docs/?extract=zip'`.
+//!
+//! The archive is received into [crate::STAGING] and extracted there (on a blocking thread) first.
+//! Only once all of it is extracted within [ExtractLimits] is it moved into the share, so a
+//! rejected archive leaves the share as it was:
+//!
+//! - Entries with absolute paths, or with `..`, reject the whole archive.
+//! - Symlinks, hard links and special files are skipped (and listed in the reply).
+//! - Replaced files keep previous versions (see [crate::versions]). An entry can't replace a
+//!   directory with a file (or the other way around), nor go through a symlink.
+//! - The extracted files count towards the share's [crate::quota], less what they replace.
+//!
+//! The reply is `201 Created` with a JSON summary ([Extracted]).
+
+use crate::archive::ArchiveFormat;
+use crate::error::{self, Error};
+use crate::manager::MAX_UPLOAD_BYTES;
+use crate::meta::ShareMeta;
+use crate::quota;
+use crate::server::{accept_header, decode_tail, receive, share_of, ShareTarget};
+use crate::versions::ShareVersions;
+use flate2::read::GzDecoder;
+use http::header::CONTENT_LENGTH;
+use http::{Method, StatusCode};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{self, BufReader, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::fs as tokio_fs;
+use tokio::task;
+use warp::path::Peek;
+use warp::{reject::Rejection, reply, Buf, Filter, Reply};
+use zip::ZipArchive;
+
+/// Request header that asks for extraction, with the archive format as its value.
+pub const EXTRACT_HEADER: &str = "x-extract";
+
+/// Default of [ExtractLimits::max_entries].
+pub const MAX_ENTRIES: usize = 10_000;
+
+/// Default of [ExtractLimits::max_bytes]: No more than an upload can be. (It's all staged in
+/// [crate::STAGING] before the share's [crate::quota] applies to what's replaced.)
+pub const MAX_EXTRACTED_BYTES: u64 = MAX_UPLOAD_BYTES;
+
+/// Environment variable name: [ExtractLimits::max_bytes] instead of [MAX_EXTRACTED_BYTES].
+pub const ENV_EXTRACT_MAX_BYTES: &str = "EXTRACT_MAX_BYTES";
+
+/// How much one archive can extract to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExtractLimits {
+    /// Entries of any kind (including skipped ones).
+    pub max_entries: usize,
+    /// Total size of the extracted files.
+    pub max_bytes: u64,
+    /// The share's [crate::quota] (if any): No archive can extract to more than that.
+    pub quota: Option<u64>,
+}
+
+impl Default for ExtractLimits {
+    fn default() -> Self {
+        Self {
+            max_entries: MAX_ENTRIES,
+            max_bytes: MAX_EXTRACTED_BYTES,
+            quota: None,
+        }
+    }
+}
+
+impl ExtractLimits {
+    /// The defaults, with [ExtractLimits::max_bytes] from [ENV_EXTRACT_MAX_BYTES] (if set).
+    pub fn from_env() -> Result<Self, String> {
+        let mut limits = Self::default();
+        if let Ok(max_bytes) = env::var(ENV_EXTRACT_MAX_BYTES) {
+            limits.max_bytes = max_bytes
+                .trim()
+                .parse()
+                .map_err(|e| format!("{ENV_EXTRACT_MAX_BYTES} {max_bytes}: {e}"))?;
+        }
+        Ok(limits)
+    }
+}
+
+/// Summary of an extracted archive.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Extracted {
+    pub files: usize,
+    pub directories: usize,
+    /// Total size of the files.
+    pub bytes: u64,
+    /// Names of entries that were not extracted (symlinks, hard links and special files).
+    pub skipped: Vec<String>,
+}
+
+/// `name` (of an archive entry) as a relative path, without `.` components. [None] if it's
+/// absolute or has `..`.
+fn safe_path(name: &Path) -> Option<PathBuf> {
+    let mut safe = PathBuf::new();
+    for component in name.components() {
+        match component {
+            Component::Normal(segment) => safe.push(segment),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    Some(safe)
+}
+
+fn corrupt(format: ArchiveFormat, e: impl std::fmt::Display) -> Error {
+    Error::BadRequest(format!("{} archive: {e}", format.extension()))
+}
+
+/// Entries that get in each other's way (like a file, and a directory of the same name) are the
+/// client's fault. Other errors are ours.
+fn conflict(e: io::Error, name: &str) -> Error {
+    match e.kind() {
+        io::ErrorKind::AlreadyExists
+        | io::ErrorKind::NotADirectory
+        | io::ErrorKind::IsADirectory => {
+            Error::BadRequest(format!("entry {name} conflicts with another entry"))
+        }
+        _ => Error::Io(e),
+    }
+}
+
+/// Writes entries under a (fresh) directory, within [ExtractLimits].
+struct Unpacker<'a> {
+    into: &'a Path,
+    format: ArchiveFormat,
+    limits: &'a ExtractLimits,
+    entries: usize,
+    extracted: Extracted,
+}
+
+impl Unpacker<'_> {
+    /// Count an entry named `name`, and return where it goes. [None] for the top directory itself
+    /// (like `./`).
+    fn entry(&mut self, name: &str) -> Result<Option<PathBuf>, Error> {
+        self.entries += 1;
+        if self.entries > self.limits.max_entries {
+            return Err(Error::BadRequest(format!(
+                "more than {} archive entries",
+                self.limits.max_entries
+            )));
+        }
+        let relative = safe_path(Path::new(name))
+            .ok_or_else(|| Error::BadRequest(format!("unsafe archive entry {name}")))?;
+        Ok((!relative.as_os_str().is_empty()).then(|| self.into.join(relative)))
+    }
+
+    fn dir(&mut self, name: &str, path: &Path) -> Result<(), Error> {
+        fs::create_dir_all(path).map_err(|e| conflict(e, name))?;
+        self.extracted.directories += 1;
+        Ok(())
+    }
+
+    fn file(&mut self, name: &str, path: &Path, content: &mut dyn Read) -> Result<(), Error> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| conflict(e, name))?;
+        }
+        let mut file = fs::File::create(path).map_err(|e| conflict(e, name))?;
+        let limit = self.limits.quota.map_or(self.limits.max_bytes, |quota| {
+            quota.min(self.limits.max_bytes)
+        });
+        let mut buffer = vec![0; 64 * 1024];
+        loop {
+            let read = content
+                .read(&mut buffer)
+                .map_err(|e| corrupt(self.format, e))?;
+            if read == 0 {
+                break;
+            }
+            self.extracted.bytes += read as u64;
+            if self.extracted.bytes > limit {
+                return Err(match self.limits.quota {
+                    Some(quota) if quota < self.limits.max_bytes => Error::QuotaExceeded,
+                    _ => Error::PayloadTooLarge {
+                        limit: self.limits.max_bytes,
+                    },
+                });
+            }
+            file.write_all(&buffer[..read])?;
+        }
+        self.extracted.files += 1;
+        Ok(())
+    }
+
+    fn skip(&mut self, name: &str) {
+        self.extracted.skipped.push(name.to_owned());
+    }
+}
+
+/// Extract `archive` into directory `into` (which should be empty). On error, `into` may have
+/// some of the entries. Blocking.
+pub fn extract(
+    archive: &Path,
+    format: ArchiveFormat,
+    into: &Path,
+    limits: &ExtractLimits,
+) -> Result<Extracted, Error> {
+    let mut unpacker = Unpacker {
+        into,
+        format,
+        limits,
+        entries: 0,
+        extracted: Extracted::default(),
+    };
+    let file = BufReader::new(fs::File::open(archive)?);
+    match format {
+        ArchiveFormat::Zip => {
+            let mut zip = ZipArchive::new(file).map_err(|e| corrupt(format, e))?;
+            for index in 0..zip.len() {
+                let mut entry = zip.by_index(index).map_err(|e| corrupt(format, e))?;
+                let name = entry.name().to_owned();
+                let Some(path) = unpacker.entry(&name)? else {
+                    continue;
+                };
+                if entry.is_symlink() {
+                    unpacker.skip(&name);
+                } else if entry.is_dir() {
+                    unpacker.dir(&name, &path)?;
+                } else {
+                    unpacker.file(&name, &path, &mut entry)?;
+                }
+            }
+        }
+        ArchiveFormat::TarGz | ArchiveFormat::Tar => {
+            let content: Box<dyn Read> = if format == ArchiveFormat::TarGz {
+                Box::new(GzDecoder::new(file))
+            } else {
+                Box::new(file)
+            };
+            let mut tar = tar::Archive::new(content);
+            for entry in tar.entries().map_err(|e| corrupt(format, e))? {
+                let mut entry = entry.map_err(|e| corrupt(format, e))?;
+                let name = entry
+                    .path()
+                    .map_err(|e| corrupt(format, e))?
+                    .to_string_lossy()
+                    .to_string();
+                let Some(path) = unpacker.entry(&name)? else {
+                    continue;
+                };
+                match entry.header().entry_type() {
+                    tar::EntryType::Directory => unpacker.dir(&name, &path)?,
+                    kind if kind.is_file() => unpacker.file(&name, &path, &mut entry)?,
+                    // Metadata of other entries, rather than entries.
+                    kind if kind.is_pax_global_extensions()
+                        || kind.is_pax_local_extensions()
+                        || kind.is_gnu_longname()
+                        || kind.is_gnu_longlink() => {}
+                    _ => unpacker.skip(&name),
+                }
+            }
+        }
+    }
+    Ok(unpacker.extracted)
+}
+
+/// Check whether the content of directory `from` can go into directory `into` (see the module
+/// docs), and return the total size of the files in `into` that it would replace. Blocking.
+pub fn replaced_bytes(from: &Path, into: &Path) -> Result<u64, Error> {
+    walk_merge(from, into, Path::new(""), &mut |_, _, _| Ok(()))
+}
+
+/// Move the content of directory `from` into directory `into`, merging directories. Call
+/// `before_replace` with the path (relative to `into`) of every file that is about to be replaced.
+/// It checks everything first (with [replaced_bytes]), so it only fails half-way on I/O errors.
+/// Blocking.
+pub fn merge(
+    from: &Path,
+    into: &Path,
+    before_replace: &mut dyn FnMut(&Path) -> io::Result<()>,
+) -> Result<(), Error> {
+    replaced_bytes(from, into)?;
+    walk_merge(
+        from,
+        into,
+        Path::new(""),
+        &mut |source, relative, replaces| {
+            if replaces {
+                before_replace(relative)?;
+            }
+            fs::rename(source, into.join(relative))
+        },
+    )?;
+    Ok(())
+}
+
+/// For each child of `from` (at `relative`): If it's a file, or a directory that `into` doesn't
+/// have yet, call `visit` with its path, its path relative to the top, and whether it replaces a
+/// file. Recurse into directories that both have. Fail on conflicts. Return the total size of
+/// replaced files.
+fn walk_merge(
+    from: &Path,
+    into: &Path,
+    relative: &Path,
+    visit: &mut dyn FnMut(&Path, &Path, bool) -> io::Result<()>,
+) -> Result<u64, Error> {
+    let mut replaced = 0;
+    let mut children = fs::read_dir(from)?.collect::<io::Result<Vec<_>>>()?;
+    children.sort_by_key(|child| child.file_name());
+    for child in children {
+        let (source, relative) = (child.path(), relative.join(child.file_name()));
+        let target = into.join(&relative);
+        let source_is_dir = fs::symlink_metadata(&source)?.is_dir();
+        let in_the_way = || Error::AlreadyExists(relative.to_string_lossy().to_string());
+        match fs::symlink_metadata(&target) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                visit(&source, &relative, false)?;
+            }
+            Err(e) => return Err(Error::Io(e)),
+            Ok(existing) if existing.is_symlink() => return Err(in_the_way()),
+            Ok(existing) if existing.is_dir() && source_is_dir => {
+                replaced += walk_merge(&source, into, &relative, visit)?;
+            }
+            Ok(existing) if existing.is_file() && !source_is_dir => {
+                visit(&source, &relative, true)?;
+                replaced += existing.len();
+            }
+            Ok(_) => return Err(in_the_way()),
+        }
+    }
+    Ok(replaced)
+}
+
+/// A new path (starting with `kind`) under `staging_dir`, for one upload.
+pub(crate) fn staging_path(staging_dir: &Path, kind: &str) -> PathBuf {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+    staging_dir.join(format!("{kind}_{nanos}_{count}"))
+}
+
+/// Receive and extract an archive into directory `target` (within `share`, and `limits` and its
+/// quota), and move it there.
+async fn upload(
+    share: ShareTarget,
+    target: PathBuf,
+    format: ArchiveFormat,
+    content_length: Option<u64>,
+    body: impl futures_util::Stream<Item = Result<impl Buf, warp::Error>>,
+    staging_dir: &Path,
+    limits: ExtractLimits,
+) -> Result<Extracted, Error> {
+    // Like PUT of every file (and MKCOL of every directory) in the archive.
+    let meta = ShareMeta::load(&share.name).await?;
+    meta.policy.check(&Method::PUT, content_length)?;
+    meta.policy
+        .check(&Method::from_bytes(b"MKCOL").expect("MKCOL"), None)?;
+    let metadata = tokio_fs::metadata(&target)
+        .await
+        .map_err(|e| Error::from_io(e, "target directory"))?;
+    if !metadata.is_dir() {
+        return Err(Error::BadRequest(
+            "extracting needs a target directory".to_owned(),
+        ));
+    }
+
+    let unpacked = staging_path(staging_dir, "extract");
+    let archive = unpacked.with_extension(format.extension());
+    let limit = meta
+        .policy
+        .max_put_bytes
+        .map_or(MAX_UPLOAD_BYTES, |max| max.min(MAX_UPLOAD_BYTES));
+    let received = receive(body, &archive, Some(limit)).await;
+
+    let result = match received {
+        Ok(()) => {
+            let (archive, unpacked) = (archive.clone(), unpacked.clone());
+            task::spawn_blocking(move || {
+                let limits = ExtractLimits {
+                    quota: meta.quota_bytes,
+                    ..limits
+                };
+                fs::create_dir(&unpacked)?;
+                let extracted = extract(&archive, format, &unpacked, &limits)?;
+
+                let replaced = replaced_bytes(&unpacked, &target)?;
+                if let Some(quota) = meta.quota_bytes {
+                    let used = quota::usage(&share.dir)?.saturating_sub(replaced);
+                    quota::check(Some(quota.saturating_sub(used)), extracted.bytes)?;
+                }
+                // Capped once, rather than after each file.
+                let versions = ShareVersions::of(&share.name);
+                merge(&unpacked, &target, &mut |relative| {
+                    versions.snapshot(&share.dir, &share.within.join(relative), meta.versions)
+                })?;
+                versions.with_cap(meta.quota_bytes).cap()?;
+                Ok(extracted)
+            })
+            .await
+            .map_err(Error::from)
+            .and_then(|extracted| extracted)
+        }
+        Err(e) => Err(e),
+    };
+    let _ = tokio_fs::remove_file(&archive).await;
+    let _ = tokio_fs::remove_dir_all(&unpacked).await;
+    result
+}
+
+/// Extract archives that are PUT or POSTed with [EXTRACT_HEADER] (or `?extract=...`). Reject
+/// anything else (with [warp::reject::not_found]), so that the request can go on through
+/// [Filter::or]. Its errors are replies (like in [crate::server::trash_delete]). Put it in front of
+/// the handlers of [crate::WRITE], after the URL prefix segment and [crate::server::method_policy],
+/// but before [crate::server::keep_versions]. Archives are received and extracted under
+/// `staging_dir` (on the same filesystem as the shares), within `limits` (and the share's quota).
+pub fn extract_upload(
+    symlinks_dir: impl Into<PathBuf>,
+    staging_dir: impl Into<PathBuf>,
+    limits: ExtractLimits,
+) -> impl Filter<Extract = (reply::Response,), Error = Rejection> + Clone {
+    let symlinks_dir = symlinks_dir.into();
+    let staging_dir = staging_dir.into();
+    warp::method()
+        .and(warp::path::peek())
+        .and(warp::header::optional::<String>(EXTRACT_HEADER))
+        .and(warp::query::<HashMap<String, String>>())
+        .and_then(
+            move |method: Method,
+                  tail: Peek,
+                  header: Option<String>,
+                  query: HashMap<String, String>| {
+                let symlinks_dir = symlinks_dir.clone();
+                async move {
+                    // Decide before taking the body, so that other requests keep theirs.
+                    let Some(format) = header.or_else(|| query.get("extract").cloned()) else {
+                        return Err(warp::reject::not_found());
+                    };
+                    if !(method == Method::PUT || method == Method::POST) {
+                        return Err(warp::reject::not_found());
+                    }
+                    let relative = decode_tail(&tail).map_err(|_| warp::reject::not_found())?;
+                    let Some(share) = share_of(&symlinks_dir, &relative).await else {
+                        return Err(warp::reject::not_found());
+                    };
+                    Ok((format, share, symlinks_dir.join(relative)))
+                }
+            },
+        )
+        .and(warp::header::optional::<u64>(CONTENT_LENGTH.as_str()))
+        .and(accept_header())
+        .and(warp::body::stream())
+        .then(
+            move |(format, share, target): (String, ShareTarget, PathBuf),
+                  content_length: Option<u64>,
+                  accept: Option<String>,
+                  body| {
+                let staging_dir = staging_dir.clone();
+                async move {
+                    let extracted = match format.trim().parse::<ArchiveFormat>() {
+                        Ok(format) => {
+                            let staging_dir = &staging_dir;
+                            upload(
+                                share,
+                                target,
+                                format,
+                                content_length,
+                                body,
+                                staging_dir,
+                                limits,
+                            )
+                            .await
+                        }
+                        Err(e) => Err(e),
+                    };
+                    match extracted {
+                        Ok(extracted) => {
+                            reply::with_status(reply::json(&extracted), StatusCode::CREATED)
+                                .into_response()
+                        }
+                        Err(e) => error::reply(&warp::reject::custom(e), accept.as_deref())
+                            .into_response(),
+                    }
+                }
+            },
+        )
+}
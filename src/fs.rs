@@ -1,18 +1,341 @@
-#[cfg_attr(feature = "mock_fs", mockall_double::double)]
-pub use fs_mockable::FileSystem;
-//#[cfg(test)]
-//pub use fs_mockable::FileSystem as ProductionFileSystem;
+use crate::entry::{EntriesMap, Entry, EntryError, EntryResult, SecondaryDir};
+use crate::links::{link_name, symlink_path};
+use crate::{DIRS, SYMLINKS_APPEND, SYMLINKS_DROP, SYMLINKS_FILE, SYMLINKS_READ, SYMLINKS_WRITE};
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use tokio::sync::Semaphore;
+use tokio::task;
 
-pub use fs_mockable::UnmockFileSystem;
+pub use fake::{FakeFileSystem, FakeNode};
+pub use real::RealFileSystem;
 
-use std::path::Path;
+mod fake;
+mod real;
 
-mod fs_mockable;
+/// Filesystem operations that [Entry] classification depends on. Passed (as a generic or as `dyn`)
+/// to [get_entries] and to [Entry]'s constructors/transitions, so that tests can use
+/// [FakeFileSystem] instead of [RealFileSystem].
+pub trait FileSystem {
+    /// Paths of immediate children of `dir`. Their order is unspecified.
+    fn read_dir(&self, dir: &Path) -> io::Result<Vec<PathBuf>>;
+
+    /// Like [Path::is_dir]: It follows symlinks.
+    fn is_dir(&self, path: &Path) -> bool;
+
+    /// Like [Path::is_file]: It follows symlinks.
+    fn is_file(&self, path: &Path) -> bool;
+
+    /// Like [Path::is_symlink]: It does NOT follow symlinks.
+    fn is_symlink(&self, path: &Path) -> bool;
+
+    /// Return the target - but as-is, NOT canonical!
+    fn read_link_full(&self, path: &Path) -> io::Result<String>;
+
+    /// Like [Path::try_exists], but any error counts as "doesn't exist". It follows symlinks, so an
+    /// orphan symlink doesn't exist.
+    fn exists(&self, path: &Path) -> bool;
+}
 
 /// Require `path` leaf part not to be `..`.
-fn file_name_leaf(path: &Path) -> String {
+pub(crate) fn file_name_leaf(path: &Path) -> EntryResult<String> {
     path.file_name()
-        .expect("The path must not be `..`")
-        .to_string_lossy()
-        .to_string()
+        .map(|leaf| leaf.to_string_lossy().to_string())
+        .ok_or_else(|| EntryError::NoFileName(path.to_path_buf()))
+}
+
+/// Map names of symlinks under `under` (see [link_name]) back to the names of `entries`.
+fn names_by_link(under: SecondaryDir, entries: &EntriesMap) -> HashMap<String, String> {
+    entries
+        .keys()
+        .map(|name| (link_name(under, name), name.clone()))
+        .collect()
+}
+
+/// Name of the entry that symlink `path` under `under` belongs to: One of `names` (see
+/// [names_by_link]), or else (for a symlink of no known entry) its own name.
+fn secondary_name(path: &Path, names: &HashMap<String, String>) -> EntryResult<String> {
+    let leaf = file_name_leaf(path)?;
+    Ok(names.get(&leaf).cloned().unwrap_or(leaf))
+}
+
+/// Group symlinks `paths` under `under` by the names of the entries that they belong to (see
+/// [secondary_name]). Each group starts with the symlink named by [link_name], if any: The rest are
+/// duplicates (see [Entry::and_duplicate_links]).
+fn links_by_name(
+    under: SecondaryDir,
+    paths: Vec<PathBuf>,
+    names: &HashMap<String, String>,
+) -> EntryResult<HashMap<String, Vec<PathBuf>>> {
+    let mut groups = HashMap::<String, Vec<PathBuf>>::new();
+    for path in paths {
+        groups
+            .entry(secondary_name(&path, names)?)
+            .or_default()
+            .push(path);
+    }
+    for (name, paths) in &mut groups {
+        let hashed = link_name(under, name);
+        paths.sort_by_key(|path| path.file_name() != Some(hashed.as_ref()));
+    }
+    Ok(groups)
+}
+
+/// Max. number of [get_entries_async] scans running at the same time. Any more wait, so that
+/// repeated admin page loads can't occupy all of Tokio's blocking threads.
+pub const MAX_PARALLEL_SCANS: usize = 2;
+
+static SCAN_PERMITS: Semaphore = Semaphore::const_new(MAX_PARALLEL_SCANS);
+
+/// Like [get_entries], but for async handlers: It runs on Tokio's blocking thread pool (through
+/// [task::spawn_blocking]), so that a slow filesystem doesn't stall Tokio worker threads (and with
+/// them WebDAV traffic). Max. [MAX_PARALLEL_SCANS] run in parallel.
+pub async fn get_entries_async<F: FileSystem + Send + 'static>(fs: F) -> EntryResult<EntriesMap> {
+    let _permit = SCAN_PERMITS
+        .acquire()
+        .await
+        .expect("SCAN_PERMITS is never closed.");
+
+    task::spawn_blocking(move || get_entries(&fs))
+        .await
+        .map_err(|e| io::Error::other(e.to_string()))?
+}
+
+/// Classify all entries under [DIRS], [SYMLINKS_READ], [SYMLINKS_WRITE], [SYMLINKS_APPEND],
+/// [SYMLINKS_DROP] and [SYMLINKS_FILE].
+pub fn get_entries<F: FileSystem + ?Sized>(fs: &F) -> EntryResult<EntriesMap> {
+    let primaries = get_primaries(fs)?;
+    let secondaries_read = get_secondaries_read(fs, primaries)?;
+    let secondaries_write = get_secondaries_write(fs, secondaries_read)?;
+    let secondaries_append = get_secondaries_append(fs, secondaries_write)?;
+    let secondaries_drop = get_secondaries_drop(fs, secondaries_append)?;
+    get_secondaries_file(fs, secondaries_drop)
+}
+
+pub fn get_primaries<F: FileSystem + ?Sized>(fs: &F) -> EntryResult<EntriesMap> {
+    let mut entries = EntriesMap::new();
+    for path in fs.read_dir(Path::new(DIRS))? {
+        let entry = Entry::new_under_dirs(fs, path)?;
+        entries.insert(entry.name().to_owned(), entry);
+    }
+    Ok(entries)
+}
+
+/// Call on result of [get_primaries].
+pub fn get_secondaries_read<F: FileSystem + ?Sized>(
+    fs: &F,
+    mut primaries: EntriesMap,
+) -> EntryResult<EntriesMap> {
+    let mut entries = EntriesMap::new();
+
+    for path in fs.read_dir(Path::new(SYMLINKS_READ))? {
+        let name = file_name_leaf(&path)?;
+
+        let primary = primaries.remove(&name);
+        let new_entry = if let Some(primary) = primary {
+            primary.and_readable_symlink(fs, path)?
+        } else {
+            Entry::new_under_readable_symlinks(fs, &path)?
+        };
+
+        entries.insert(name, new_entry);
+    }
+    // Primaries without a readable symlink.
+    entries.extend(primaries);
+    Ok(entries)
+}
+
+/// Call on result of [get_secondaries_read].
+pub fn get_secondaries_write<F: FileSystem + ?Sized>(
+    fs: &F,
+    mut secondaries_read: EntriesMap,
+) -> EntryResult<EntriesMap> {
+    let mut entries = EntriesMap::new();
+    let names = names_by_link(SecondaryDir::Write, &secondaries_read);
+
+    let paths = fs.read_dir(Path::new(SYMLINKS_WRITE))?;
+    for (name, mut paths) in links_by_name(SecondaryDir::Write, paths, &names)? {
+        let path = paths.remove(0);
+
+        let secondary_read = secondaries_read.remove(&name);
+        let new_entry = if let Some(secondary_read) = secondary_read {
+            secondary_read.and_writable_symlink(fs, path)?
+        } else {
+            Entry::new_under_writable_symlinks(fs, &path)?
+        };
+
+        entries.insert(
+            name,
+            new_entry.and_duplicate_links(SecondaryDir::Write, &paths)?,
+        );
+    }
+    // Entries without a writable symlink.
+    entries.extend(secondaries_read);
+    Ok(entries)
+}
+
+/// Call on result of [get_secondaries_write].
+pub fn get_secondaries_append<F: FileSystem + ?Sized>(
+    fs: &F,
+    mut secondaries_write: EntriesMap,
+) -> EntryResult<EntriesMap> {
+    let mut entries = EntriesMap::new();
+    let names = names_by_link(SecondaryDir::Append, &secondaries_write);
+
+    let paths = fs.read_dir(Path::new(SYMLINKS_APPEND))?;
+    for (name, mut paths) in links_by_name(SecondaryDir::Append, paths, &names)? {
+        let path = paths.remove(0);
+
+        let secondary_write = secondaries_write.remove(&name);
+        let new_entry = if let Some(secondary_write) = secondary_write {
+            secondary_write.and_append_symlink(fs, path)?
+        } else {
+            Entry::new_under_append_symlinks(fs, &path)?
+        };
+
+        entries.insert(
+            name,
+            new_entry.and_duplicate_links(SecondaryDir::Append, &paths)?,
+        );
+    }
+    // Entries without an append symlink.
+    entries.extend(secondaries_write);
+    Ok(entries)
+}
+
+/// Call on result of [get_secondaries_append].
+pub fn get_secondaries_drop<F: FileSystem + ?Sized>(
+    fs: &F,
+    mut secondaries_append: EntriesMap,
+) -> EntryResult<EntriesMap> {
+    let mut entries = EntriesMap::new();
+    let names = names_by_link(SecondaryDir::Drop, &secondaries_append);
+
+    let paths = fs.read_dir(Path::new(SYMLINKS_DROP))?;
+    for (name, mut paths) in links_by_name(SecondaryDir::Drop, paths, &names)? {
+        let path = paths.remove(0);
+
+        let secondary_append = secondaries_append.remove(&name);
+        let new_entry = if let Some(secondary_append) = secondary_append {
+            secondary_append.and_drop_symlink(fs, path)?
+        } else {
+            Entry::new_under_drop_symlinks(fs, &path)?
+        };
+
+        entries.insert(
+            name,
+            new_entry.and_duplicate_links(SecondaryDir::Drop, &paths)?,
+        );
+    }
+    // Entries without a drop symlink.
+    entries.extend(secondaries_append);
+    Ok(entries)
+}
+
+/// Call on result of [get_secondaries_drop].
+pub fn get_secondaries_file<F: FileSystem + ?Sized>(
+    fs: &F,
+    mut secondaries_drop: EntriesMap,
+) -> EntryResult<EntriesMap> {
+    let mut entries = EntriesMap::new();
+    let names = names_by_link(SecondaryDir::File, &secondaries_drop);
+
+    let paths = fs.read_dir(Path::new(SYMLINKS_FILE))?;
+    for (name, mut paths) in links_by_name(SecondaryDir::File, paths, &names)? {
+        let path = paths.remove(0);
+
+        let secondary_drop = secondaries_drop.remove(&name);
+        let new_entry = if let Some(secondary_drop) = secondary_drop {
+            secondary_drop.and_file_symlink(fs, path)?
+        } else {
+            Entry::new_under_file_symlinks(fs, &path)?
+        };
+
+        entries.insert(
+            name,
+            new_entry.and_duplicate_links(SecondaryDir::File, &paths)?,
+        );
+    }
+    // Entries without a file link symlink.
+    entries.extend(secondaries_drop);
+    Ok(entries)
+}
+
+/// Whether `path` is present at all. Unlike [FileSystem::exists] an orphan symlink is present.
+fn is_present<F: FileSystem + ?Sized>(fs: &F, path: &Path) -> bool {
+    fs.is_symlink(path) || fs.exists(path)
+}
+
+/// Present symlinks of entry `name` under `under`: The one named by [link_name] first, and then
+/// one named plainly `name` (see [links_by_name]).
+fn present_links<F: FileSystem + ?Sized>(fs: &F, under: SecondaryDir, name: &str) -> Vec<PathBuf> {
+    let hashed = PathBuf::from(symlink_path(under, name));
+    let plain = hashed.with_file_name(name);
+    let mut paths = vec![hashed];
+    if plain != paths[0] {
+        paths.push(plain);
+    }
+    paths.retain(|path| is_present(fs, path));
+    paths
+}
+
+/// Classify one entry `name` - as [get_entries] would, but without scanning whole directories.
+/// [None] if `name` is not present under any of [DIRS], [SYMLINKS_READ], [SYMLINKS_WRITE],
+/// [SYMLINKS_APPEND], [SYMLINKS_DROP] and [SYMLINKS_FILE].
+pub fn get_entry<F: FileSystem + ?Sized>(fs: &F, name: &str) -> EntryResult<Option<Entry>> {
+    let primary = PathBuf::from(format!("{DIRS}/{name}"));
+    let mut entry = if is_present(fs, &primary) {
+        Some(Entry::new_under_dirs(fs, primary)?)
+    } else {
+        None
+    };
+
+    let read = PathBuf::from(format!("{SYMLINKS_READ}/{name}"));
+    if is_present(fs, &read) {
+        entry = Some(match entry {
+            Some(primary) => primary.and_readable_symlink(fs, read)?,
+            None => Entry::new_under_readable_symlinks(fs, &read)?,
+        });
+    }
+
+    let mut writes = present_links(fs, SecondaryDir::Write, name);
+    if !writes.is_empty() {
+        let write = writes.remove(0);
+        let new_entry = match entry {
+            Some(secondary_read) => secondary_read.and_writable_symlink(fs, write)?,
+            None => Entry::new_under_writable_symlinks(fs, &write)?,
+        };
+        entry = Some(new_entry.and_duplicate_links(SecondaryDir::Write, &writes)?);
+    }
+
+    let mut appends = present_links(fs, SecondaryDir::Append, name);
+    if !appends.is_empty() {
+        let append = appends.remove(0);
+        let new_entry = match entry {
+            Some(secondary_write) => secondary_write.and_append_symlink(fs, append)?,
+            None => Entry::new_under_append_symlinks(fs, &append)?,
+        };
+        entry = Some(new_entry.and_duplicate_links(SecondaryDir::Append, &appends)?);
+    }
+
+    let mut drops = present_links(fs, SecondaryDir::Drop, name);
+    if !drops.is_empty() {
+        let drop = drops.remove(0);
+        let new_entry = match entry {
+            Some(secondary_append) => secondary_append.and_drop_symlink(fs, drop)?,
+            None => Entry::new_under_drop_symlinks(fs, &drop)?,
+        };
+        entry = Some(new_entry.and_duplicate_links(SecondaryDir::Drop, &drops)?);
+    }
+
+    let mut files = present_links(fs, SecondaryDir::File, name);
+    if !files.is_empty() {
+        let file = files.remove(0);
+        let new_entry = match entry {
+            Some(secondary_drop) => secondary_drop.and_file_symlink(fs, file)?,
+            None => Entry::new_under_file_symlinks(fs, &file)?,
+        };
+        entry = Some(new_entry.and_duplicate_links(SecondaryDir::File, &files)?);
+    }
+    Ok(entry)
 }
@@ -0,0 +1,140 @@
+use super::FileSystem;
+use std::collections::HashMap;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// Same as Linux `MAXSYMLINKS`. Following more symlinks than this counts as a loop.
+const MAX_SYMLINK_HOPS: usize = 40;
+
+/// A node of [FakeFileSystem].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FakeNode {
+    Dir,
+    File,
+    /// Target as-is (absolute, or relative to the symlink's parent directory).
+    Symlink {
+        target: String,
+    },
+}
+
+/// In-memory [FileSystem] for tests. Keys are absolute paths. Parent directories are NOT created
+/// implicitly - add them with [FakeFileSystem::with_dir] (unless a test wants them missing).
+#[derive(Debug, Default, Clone)]
+pub struct FakeFileSystem {
+    nodes: HashMap<PathBuf, FakeNode>,
+}
+
+impl FakeFileSystem {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_dir(mut self, path: impl Into<PathBuf>) -> Self {
+        self.nodes.insert(path.into(), FakeNode::Dir);
+        self
+    }
+
+    pub fn with_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.nodes.insert(path.into(), FakeNode::File);
+        self
+    }
+
+    pub fn with_symlink(mut self, path: impl Into<PathBuf>, target: impl Into<String>) -> Self {
+        self.nodes.insert(
+            path.into(),
+            FakeNode::Symlink {
+                target: target.into(),
+            },
+        );
+        self
+    }
+
+    /// Remove the node at `path` (if any). Its children (if any) stay.
+    pub fn without(mut self, path: impl AsRef<Path>) -> Self {
+        self.nodes.remove(path.as_ref());
+        self
+    }
+
+    /// Follow symlinks (if any), and return the final node. [None] if it doesn't exist, or if there
+    /// are too many symlinks.
+    fn resolve(&self, path: &Path) -> Option<&FakeNode> {
+        let mut path = path.to_path_buf();
+        for _ in 0..=MAX_SYMLINK_HOPS {
+            match self.nodes.get(&path)? {
+                FakeNode::Symlink { target } => {
+                    let parent = path.parent().unwrap_or(Path::new("/"));
+                    path = normalize(&parent.join(target));
+                }
+                node => return Some(node),
+            }
+        }
+        None
+    }
+}
+
+/// Remove `.` and resolve `..` lexically. (Good enough for tests, where intermediate directories
+/// are not symlinks.)
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+impl FileSystem for FakeFileSystem {
+    fn read_dir(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        match self.resolve(dir) {
+            Some(FakeNode::Dir) => Ok(self
+                .nodes
+                .keys()
+                .filter(|path| path.parent() == Some(dir))
+                .cloned()
+                .collect()),
+            Some(_) => Err(io::Error::other(format!(
+                "Not a directory: {}",
+                dir.display()
+            ))),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No such directory: {}", dir.display()),
+            )),
+        }
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        matches!(self.resolve(path), Some(FakeNode::Dir))
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        matches!(self.resolve(path), Some(FakeNode::File))
+    }
+
+    fn is_symlink(&self, path: &Path) -> bool {
+        matches!(self.nodes.get(path), Some(FakeNode::Symlink { .. }))
+    }
+
+    fn read_link_full(&self, path: &Path) -> io::Result<String> {
+        match self.nodes.get(path) {
+            Some(FakeNode::Symlink { target }) => Ok(target.clone()),
+            Some(_) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Not a symlink: {}", path.display()),
+            )),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No such file: {}", path.display()),
+            )),
+        }
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.resolve(path).is_some()
+    }
+}
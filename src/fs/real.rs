@@ -0,0 +1,38 @@
+use super::FileSystem;
+use std::fs as std_fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// [FileSystem] backed by [std::fs]. Used in production.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RealFileSystem {}
+
+impl FileSystem for RealFileSystem {
+    fn read_dir(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        std_fs::read_dir(dir)?
+            .map(|dir_entry| dir_entry.map(|dir_entry| dir_entry.path()))
+            .collect()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+
+    fn is_symlink(&self, path: &Path) -> bool {
+        path.is_symlink()
+    }
+
+    fn read_link_full(&self, path: &Path) -> io::Result<String> {
+        let link = std_fs::read_link(path)?;
+        Ok(link.as_os_str().to_string_lossy().to_string())
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        let target_exists = path.try_exists();
+        matches!(target_exists, Ok(true))
+    }
+}
@@ -0,0 +1,256 @@
+//! In-memory index of [Entry] states, so that admin pages don't rescan (and re-classify) every
+//! entry. Built once at startup, then updated incrementally from inotify events on [DIRS],
+//! [SYMLINKS_READ], [SYMLINKS_WRITE], [SYMLINKS_APPEND], [SYMLINKS_DROP] and [SYMLINKS_FILE]. A
+//! periodic full rescan covers anything that inotify misses (for example, a queue overflow), and
+//! is all that's left if inotify fails.
+//!
+//! Only the top level of [DIRS] is watched, not the directories within shares: A nested
+//! publication (`DIRS/<share>/<sub>`) whose directory is removed or renamed shows as such with the
+//! next rescan (or with the next event on one of its symlinks).
+
+use crate::entry::{EntriesMap, Entry, EntryResult, SecondaryDir};
+use crate::fs::{self as wdav_fs, FileSystem, RealFileSystem};
+use crate::links::link_name;
+use crate::{DIRS, SYMLINKS_APPEND, SYMLINKS_DROP, SYMLINKS_FILE, SYMLINKS_READ, SYMLINKS_WRITE};
+use core::time::Duration;
+use futures_util::StreamExt;
+use inotify::{EventMask, EventStream, Inotify, WatchDescriptor, WatchMask};
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, RwLock, RwLockReadGuard};
+use tokio::task;
+use tokio::time::{self, MissedTickBehavior};
+
+/// Symlink directories whose symlinks are named by [link_name], rather than by entry names.
+const HASHED: [SecondaryDir; 4] = [
+    SecondaryDir::Write,
+    SecondaryDir::Append,
+    SecondaryDir::Drop,
+    SecondaryDir::File,
+];
+
+/// How often to rescan everything, regardless of inotify events.
+pub const RESCAN_PERIOD: Duration = Duration::from_secs(5 * 60);
+
+/// Buffer for reading inotify events. Big enough for dozens of events (each 16 bytes + name).
+const EVENT_BUFFER_SIZE: usize = 16 * 1024;
+
+/// Shared index of [Entry] states, mapped by their (potentially lossy) names. Cheap to clone.
+/// Updating it requires [crate::links::init] (for [ShareIndex::entry_of_link]).
+#[derive(Debug, Clone, Default)]
+pub struct ShareIndex {
+    entries: Arc<RwLock<EntriesMap>>,
+    /// Names of [ShareIndex::entries] by the [link_name]s of their symlinks under [HASHED]
+    /// directories (whether those symlinks exist or not).
+    links: Arc<RwLock<HashMap<(SecondaryDir, String), String>>>,
+}
+
+/// The [link_name]s of entry `name` under [HASHED] directories.
+fn links_of(name: &str) -> impl Iterator<Item = (SecondaryDir, String)> + '_ {
+    HASHED
+        .into_iter()
+        .map(move |under| (under, link_name(under, name)))
+}
+
+impl ShareIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Don't hold the guard across `.await`.
+    pub fn read(&self) -> RwLockReadGuard<'_, EntriesMap> {
+        // A panic while holding the lock can't leave the map half-updated: Writers only insert,
+        // remove or swap.
+        self.entries.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Name of the entry that symlink `link` under `under` (one of [HASHED]) is named for, if
+    /// any.
+    pub fn entry_of_link(&self, under: SecondaryDir, link: &str) -> Option<String> {
+        let links = self.links.read().unwrap_or_else(|e| e.into_inner());
+        links.get(&(under, link.to_owned())).cloned()
+    }
+
+    // Writers lock entries first, then links.
+    fn replace(&self, entries: EntriesMap) {
+        let links = entries
+            .keys()
+            .flat_map(|name| links_of(name).map(move |link| (link, name.clone())))
+            .collect();
+        let mut current = self.entries.write().unwrap_or_else(|e| e.into_inner());
+        *current = entries;
+        *self.links.write().unwrap_or_else(|e| e.into_inner()) = links;
+    }
+
+    fn set(&self, name: &str, entry: Option<Entry>) {
+        let mut entries = self.entries.write().unwrap_or_else(|e| e.into_inner());
+        let mut links = self.links.write().unwrap_or_else(|e| e.into_inner());
+        match entry {
+            Some(entry) => {
+                if entries.insert(name.to_owned(), entry).is_none() {
+                    links.extend(links_of(name).map(|link| (link, name.to_owned())));
+                }
+            }
+            None => {
+                if entries.remove(name).is_some() {
+                    for link in links_of(name) {
+                        links.remove(&link);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Rescan and re-classify everything. Blocking.
+    pub fn rescan<F: FileSystem + ?Sized>(&self, fs: &F) -> EntryResult<()> {
+        self.replace(wdav_fs::get_entries(fs)?);
+        Ok(())
+    }
+
+    /// Re-classify one entry `name` (and remove it if it's gone). Blocking.
+    pub fn refresh<F: FileSystem + ?Sized>(&self, fs: &F, name: &str) -> EntryResult<()> {
+        self.set(name, wdav_fs::get_entry(fs, name)?);
+        Ok(())
+    }
+
+    /// Like [ShareIndex::rescan], but through [wdav_fs::get_entries_async].
+    pub async fn rescan_async<F: FileSystem + Send + 'static>(&self, fs: F) -> EntryResult<()> {
+        self.replace(wdav_fs::get_entries_async(fs).await?);
+        Ok(())
+    }
+
+    /// Like [ShareIndex::refresh], but on Tokio's blocking thread pool.
+    pub async fn refresh_async<F: FileSystem + Send + 'static>(
+        &self,
+        fs: F,
+        name: String,
+    ) -> EntryResult<()> {
+        let index = self.clone();
+        task::spawn_blocking(move || index.refresh(&fs, &name))
+            .await
+            .map_err(|e| io::Error::other(e.to_string()))?
+    }
+
+    /// Rescan now, then every `period`, forever. What [Watcher::run] falls back to once inotify
+    /// fails.
+    pub async fn rescan_periodically<F: FileSystem + Clone + Send + 'static>(
+        &self,
+        fs: F,
+        period: Duration,
+    ) {
+        let mut rescan = time::interval(period);
+        rescan.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            rescan.tick().await;
+            if let Err(e) = self.rescan_async(fs.clone()).await {
+                log::error!("Share index: rescan failed: {e}.");
+            }
+        }
+    }
+}
+
+/// Keeps a [ShareIndex] fresh. Create with [Watcher::start], then spawn [Watcher::run].
+pub struct Watcher {
+    index: ShareIndex,
+    events: EventStream<[u8; EVENT_BUFFER_SIZE]>,
+    /// Watched directories whose symlinks are named by [link_name], rather than by entry names.
+    hashed: HashMap<WatchDescriptor, SecondaryDir>,
+}
+
+impl Watcher {
+    /// Watch [DIRS] and the symlink directories, and build the index.
+    /// (Watching starts before the initial scan, so that no change gets lost in between.)
+    pub async fn start(index: ShareIndex) -> io::Result<Self> {
+        let inotify = Inotify::init()?;
+        let mask = WatchMask::CREATE
+            | WatchMask::DELETE
+            | WatchMask::MOVED_FROM
+            | WatchMask::MOVED_TO
+            | WatchMask::ATTRIB
+            | WatchMask::DELETE_SELF
+            | WatchMask::MOVE_SELF;
+        let mut hashed = HashMap::new();
+        for (dir, under) in [
+            (DIRS, None),
+            (SYMLINKS_READ, None),
+            (SYMLINKS_WRITE, Some(SecondaryDir::Write)),
+            (SYMLINKS_APPEND, Some(SecondaryDir::Append)),
+            (SYMLINKS_DROP, Some(SecondaryDir::Drop)),
+            (SYMLINKS_FILE, Some(SecondaryDir::File)),
+        ] {
+            let watch = inotify.watches().add(dir, mask)?;
+            if let Some(under) = under {
+                hashed.insert(watch, under);
+            }
+        }
+        let events = inotify.into_event_stream([0u8; EVENT_BUFFER_SIZE])?;
+
+        index
+            .rescan_async(RealFileSystem {})
+            .await
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        Ok(Self {
+            index,
+            events,
+            hashed,
+        })
+    }
+
+    /// Apply inotify events, and rescan every [RESCAN_PERIOD]. Never returns: If inotify fails,
+    /// keep the index fresh by [ShareIndex::rescan_periodically] only.
+    pub async fn run(mut self) {
+        let mut rescan = time::interval_at(time::Instant::now() + RESCAN_PERIOD, RESCAN_PERIOD);
+        rescan.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        let error = loop {
+            tokio::select! {
+                event = self.events.next() => {
+                    let event = match event {
+                        Some(Ok(event)) => event,
+                        Some(Err(e)) => break e,
+                        None => break io::Error::other("event stream ended"),
+                    };
+                    if event.mask.intersects(
+                        EventMask::Q_OVERFLOW | EventMask::DELETE_SELF | EventMask::MOVE_SELF,
+                    ) {
+                        log::warn!("Share index: {:?}. Rescanning.", event.mask);
+                        self.rescan().await;
+                    } else if let Some(name) = event.name {
+                        let name = name.to_string_lossy().to_string();
+                        let Some(name) = self.entry_name(&event.wd, name) else {
+                            // A symlink of no indexed entry.
+                            self.rescan().await;
+                            continue;
+                        };
+                        if let Err(e) = self.index.refresh_async(RealFileSystem {}, name).await {
+                            log::warn!("Share index: refresh failed: {e}. Rescanning.");
+                            self.rescan().await;
+                        }
+                    }
+                }
+                _ = rescan.tick() => self.rescan().await,
+            }
+        };
+        log::error!("Share index: inotify failed: {error}. Only periodic rescans from now on.");
+        // Drop the watches. Events missed since the failure get picked up by the first rescan.
+        drop(self.events);
+        self.index
+            .rescan_periodically(RealFileSystem {}, RESCAN_PERIOD)
+            .await
+    }
+
+    /// Name of the entry that `leaf` (changed under the directory watched by `watch`) belongs to.
+    /// [None] for a symlink named by [link_name] that matches no indexed entry.
+    fn entry_name(&self, watch: &WatchDescriptor, leaf: String) -> Option<String> {
+        match self.hashed.get(watch) {
+            None => Some(leaf),
+            Some(&under) => self.index.entry_of_link(under, &leaf),
+        }
+    }
+
+    async fn rescan(&self) {
+        if let Err(e) = self.index.rescan_async(RealFileSystem {}).await {
+            log::error!("Share index: rescan failed: {e}.");
+        }
+    }
+}
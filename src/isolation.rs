@@ -0,0 +1,186 @@
+//! Keep user content (like HTML uploaded to a share) from scripting [crate::ADMIN], which is on the
+//! same origin by default.
+//!
+//! - With a separate origin for user content ([ENV_CONTENT_ORIGIN]), GET and HEAD of user content
+//!   on any other host redirect there, and the content host doesn't serve [crate::ADMIN].
+//! - Without it, DAV responses to GET and HEAD are sandboxed (`Content-Security-Policy: sandbox`),
+//!   with `X-Content-Type-Options: nosniff`, and (with [ENV_FORCE_ATTACHMENT]) downloaded rather
+//!   than displayed. Static sites (see [crate::site]) get a sandbox that allows scripts, but still
+//!   in an origin of their own.
+
+use http::header::{HeaderValue, CONTENT_DISPOSITION, HOST, LOCATION, X_CONTENT_TYPE_OPTIONS};
+use http::{uri::Uri, Method, Response, StatusCode};
+use std::env;
+use warp::hyper::Body;
+use warp::path::FullPath;
+use warp::{reject::Rejection, reply, Filter, Reply};
+
+/// Environment variable name that contains the origin (like `https://files.example.com`) that
+/// serves user content. It has to reach this server, too (with a different `Host`).
+pub const ENV_CONTENT_ORIGIN: &str = "CONTENT_ORIGIN";
+
+/// Environment variable name: With `1` or `true` (and without [ENV_CONTENT_ORIGIN]), files are
+/// served with `Content-Disposition: attachment`.
+pub const ENV_FORCE_ATTACHMENT: &str = "FORCE_ATTACHMENT";
+
+/// `Content-Security-Policy` of user content on the admin origin: No scripts, no forms, no plugins,
+/// and a unique (opaque) origin.
+pub const SANDBOX: &str = "sandbox";
+
+/// `Content-Security-Policy` of static sites on the admin origin: Scripts run, but still in an
+/// opaque origin (without `allow-same-origin`), so they can't reach [crate::ADMIN] as the admin.
+pub const SITE_SANDBOX: &str = "sandbox allow-scripts allow-forms allow-popups";
+
+/// How user content is isolated from [crate::ADMIN].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Isolation {
+    /// Scheme and authority of the separate origin for user content, if any.
+    pub content_origin: Option<Uri>,
+    /// Whether files are served as downloads (only without [Self::content_origin]).
+    pub force_attachment: bool,
+}
+
+impl Isolation {
+    /// Validate `content_origin` (if any): It needs a scheme and a host, and no path.
+    pub fn new(content_origin: Option<&str>, force_attachment: bool) -> Result<Self, String> {
+        let content_origin = content_origin
+            .map(str::trim)
+            .filter(|origin| !origin.is_empty())
+            .map(|origin| {
+                let uri = origin
+                    .trim_end_matches('/')
+                    .parse::<Uri>()
+                    .map_err(|e| format!("{ENV_CONTENT_ORIGIN} {origin}: {e}"))?;
+                match (uri.scheme(), uri.authority(), uri.path()) {
+                    (Some(_), Some(_), "" | "/") => Ok(uri),
+                    _ => Err(format!(
+                        "{ENV_CONTENT_ORIGIN} {origin}: Expecting an origin, like https://files.example.com"
+                    )),
+                }
+            })
+            .transpose()?;
+        Ok(Self {
+            content_origin,
+            force_attachment,
+        })
+    }
+
+    /// From [ENV_CONTENT_ORIGIN] and [ENV_FORCE_ATTACHMENT].
+    pub fn from_env() -> Result<Self, String> {
+        let force_attachment = env::var(ENV_FORCE_ATTACHMENT)
+            .is_ok_and(|force| force == "1" || force.eq_ignore_ascii_case("true"));
+        Self::new(
+            env::var(ENV_CONTENT_ORIGIN).ok().as_deref(),
+            force_attachment,
+        )
+    }
+
+    /// Whether `host` (the value of a `Host` header) is that of [Self::content_origin].
+    pub fn is_content_host(&self, host: Option<&str>) -> bool {
+        match (&self.content_origin, host) {
+            (Some(origin), Some(host)) => origin
+                .authority()
+                .is_some_and(|authority| authority.as_str().eq_ignore_ascii_case(host)),
+            _ => false,
+        }
+    }
+
+    /// Add the headers of user content on the admin origin (see the module docs) to a `reply` to
+    /// `method`. `policy` is [SANDBOX] or [SITE_SANDBOX].
+    pub fn harden(
+        &self,
+        method: &Method,
+        policy: &'static str,
+        reply: impl Reply,
+    ) -> reply::Response {
+        let mut response = reply.into_response();
+        if self.content_origin.is_some() || !(method == Method::GET || method == Method::HEAD) {
+            return response;
+        }
+        let success = response.status().is_success();
+        let headers = response.headers_mut();
+        headers.insert(
+            http::header::CONTENT_SECURITY_POLICY,
+            HeaderValue::from_static(policy),
+        );
+        headers.insert(X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+        if self.force_attachment && success && !headers.contains_key(CONTENT_DISPOSITION) {
+            headers.insert(CONTENT_DISPOSITION, HeaderValue::from_static("attachment"));
+        }
+        response
+    }
+}
+
+/// [Isolation::harden] every reply of `filter` with `policy`.
+pub fn hardened<F, R>(
+    isolation: Isolation,
+    policy: &'static str,
+    filter: F,
+) -> impl Filter<Extract = (reply::Response,), Error = Rejection> + Clone
+where
+    F: Filter<Extract = (R,), Error = Rejection> + Clone + Send + Sync + 'static,
+    R: Reply,
+{
+    warp::method().and(filter.map(Reply::into_response)).map(
+        move |method: Method, response: reply::Response| {
+            isolation.harden(&method, policy, response)
+        },
+    )
+}
+
+/// Redirect GET and HEAD of user content to [Isolation::content_origin] (with the same path and
+/// query), unless it's already there. Reject anything else (with [warp::reject::not_found]), so
+/// that the request can go on through [Filter::or]. Put it in front of the handlers of user
+/// content.
+pub fn to_content_origin(
+    isolation: Isolation,
+) -> impl Filter<Extract = (reply::Response,), Error = Rejection> + Clone {
+    warp::method()
+        .and(warp::header::optional::<String>(HOST.as_str()))
+        .and(warp::path::full())
+        .and(warp::query::raw().or(warp::any().map(String::new)).unify())
+        .and_then(
+            move |method: Method, host: Option<String>, path: FullPath, query: String| {
+                let isolation = isolation.clone();
+                async move {
+                    let Some(origin) = &isolation.content_origin else {
+                        return Err(warp::reject::not_found());
+                    };
+                    if !(method == Method::GET || method == Method::HEAD)
+                        || isolation.is_content_host(host.as_deref())
+                    {
+                        return Err(warp::reject::not_found());
+                    }
+                    let origin = origin.to_string();
+                    let mut location = format!("{}{}", origin.trim_end_matches('/'), path.as_str());
+                    if !query.is_empty() {
+                        location = format!("{location}?{query}");
+                    }
+                    Response::builder()
+                        .status(StatusCode::PERMANENT_REDIRECT)
+                        .header(LOCATION, location)
+                        .body(Body::empty())
+                        .map_err(|_| warp::reject::not_found())
+                }
+            },
+        )
+}
+
+/// Reject (with [warp::reject::not_found]) requests to [Isolation::content_origin], so that it
+/// doesn't serve [crate::ADMIN]. Put it in front of the admin handlers.
+pub fn not_on_content_origin(
+    isolation: Isolation,
+) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::header::optional::<String>(HOST.as_str())
+        .and_then(move |host: Option<String>| {
+            let is_content_host = isolation.is_content_host(host.as_deref());
+            async move {
+                if is_content_host {
+                    Err(warp::reject::not_found())
+                } else {
+                    Ok(())
+                }
+            }
+        })
+        .untuple_one()
+}
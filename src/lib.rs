@@ -1,20 +1,31 @@
+#![allow(clippy::redundant_static_lifetimes)]
 /// This does use Syn + Parse crate, which increase build times. But Askama's attribute (procedural)
 /// macro uses those anyway.
 use const_format::formatcp;
 
+pub mod archive;
+pub mod autoindex;
+pub mod chain;
+pub mod chunking;
+pub mod cleanup;
 pub mod entry;
+pub mod error;
+pub mod extract;
 pub mod fs;
+pub mod index;
+pub mod isolation;
+pub mod links;
+pub mod logs;
+pub mod manager;
+pub mod meta;
+pub mod props;
+pub mod quota;
 pub mod server;
-
-const _MOCKABLE_IN_DEBUG_ONLY: () = {
-    #[cfg(all(not(debug_assertions), feature = "mockable"))]
-    panic!("Use `mockable` (and related features) only in debug build.");
-};
-
-const _MOCKABLE_NOT_IN_DIRECT_TEST: () = {
-    #[cfg(all(test, feature = "mockable"))]
-    panic!("Use `mockable` (and related features) only in non-test build. (Don't invoke that directly, but as a non-test debug build through `test-binary`.");
-};
+pub mod share;
+pub mod site;
+pub mod trash;
+pub mod tus;
+pub mod versions;
 
 /// Environment variable name that contains the port number assigned by Deta.Space.
 const ENV_PORT: &'static str = "PORT";
@@ -31,20 +42,62 @@ const ENV_SALT: &'static str = "SALT";
 
 // Directory names here don't have a trailing slash.
 //
-const TMP: &'static str = "/tmp";
-const DIRS: &'static str = formatcp!("{TMP}/wdav_dirs");
+pub const TMP: &'static str = "/tmp";
+pub const DIRS: &'static str = formatcp!("{TMP}/wdav_dirs");
+/// Per-share metadata. See [meta].
+pub const META: &'static str = formatcp!("{TMP}/wdav_meta");
+/// Previous versions of files. See [versions].
+pub const VERSIONS: &'static str = formatcp!("{TMP}/wdav_versions");
+/// Dead properties of files where extended attributes don't work. See [props].
+pub const PROPS: &'static str = formatcp!("{TMP}/wdav_props");
+/// Deleted files and directories. See [trash].
+pub const TRASH: &'static str = formatcp!("{TMP}/wdav_trash");
+/// Where [share::freeze] makes copies, and where uploads of single-file shares go, before they are
+/// moved to [DIRS]. (On the same filesystem.)
+pub const STAGING: &'static str = formatcp!("{TMP}/wdav_staging");
+/// Unfinished resumable uploads (see [tus]). On the same filesystem as [DIRS], too.
+pub const UPLOADS: &'static str = formatcp!("{TMP}/wdav_uploads");
+/// Upload folders of [chunking].
+pub const CHUNKS: &'static str = formatcp!("{UPLOADS}/chunking");
+/// Access log and audit log. See [logs].
+pub const LOGS: &'static str = formatcp!("{TMP}/wdav_logs");
 
 // Leading URL "segments" (top level directories). Warp requires them NOT to contain any slash.
 const READ: &'static str = "read";
+/// WebDAV (and directory listing) of read shares, even of those that [site] serves as websites.
+const READ_DAV: &'static str = "read-dav";
 const WRITE: &'static str = "write";
+const DROP: &'static str = "drop";
+const APPEND: &'static str = "append";
+/// Single-file shares by link (see [entry::Entry::PrimaryFile]).
+const FILE_LINK: &'static str = "f";
+/// Resumable uploads (see [tus]) into writable shares, by their write hashes.
+const TUS: &'static str = "tus";
+/// Chunked uploads (see [chunking]) into writable shares, by their write hashes.
+const CHUNKING: &'static str = "uploads";
 const ADMIN: &'static str = "admin";
 const ADD: &'static str = "add";
+const POLICY: &'static str = "policy";
+const VERSIONS_SEGMENT: &'static str = "versions";
+const RESTORE: &'static str = "restore";
+const RETENTION: &'static str = "retention";
+const TRASH_SEGMENT: &'static str = "trash";
+const PURGE: &'static str = "purge";
+const FREEZE: &'static str = "freeze";
+const FILE: &'static str = "file";
+const NESTED: &'static str = "nested";
+const SITE: &'static str = "site";
+const LOGS_SEGMENT: &'static str = "logs";
 
 // Directories containing symlinks. These constants could use `const_format` crate. But that
 // involves quote + syn = long build times. TODO reconsider because of Tokio, or don't use Tokio
 // attrib. macro.
-const SYMLINKS: &'static str = "/tmp/wdav_symlinks";
-const SYMLINKS_WRITE: &'static str = formatcp!("{SYMLINKS}/{WRITE}");
-const SYMLINKS_READ: &'static str = formatcp!("{SYMLINKS}/{READ}");
+pub const SYMLINKS: &'static str = "/tmp/wdav_symlinks";
+pub const SYMLINKS_WRITE: &'static str = formatcp!("{SYMLINKS}/{WRITE}");
+pub const SYMLINKS_READ: &'static str = formatcp!("{SYMLINKS}/{READ}");
+pub const SYMLINKS_DROP: &'static str = formatcp!("{SYMLINKS}/{DROP}");
+pub const SYMLINKS_APPEND: &'static str = formatcp!("{SYMLINKS}/{APPEND}");
+pub const SYMLINKS_FILE: &'static str = formatcp!("{SYMLINKS}/{FILE_LINK}");
 
+/// Present while [cleanup] runs.
 const CLEANUP_IN_PROGRESS: &'static str = formatcp!("{SYMLINKS}/CLEANUP_IN_PROGRESS");
@@ -0,0 +1,59 @@
+//! Names of the symlinks under [SYMLINKS_WRITE], [SYMLINKS_APPEND], [SYMLINKS_DROP] and
+//! [SYMLINKS_FILE]. Those grant more than the (public) share name does, so they are not guessable:
+//! Each is a one-way hash of the share name, keyed by the data key and salted by [crate::ENV_SALT].
+//! The same key and salt re-generate the same names, so the links survive restarts.
+//!
+//! Names under [SYMLINKS_READ] stay plain share names.
+
+use crate::chain::hex;
+use crate::entry::SecondaryDir;
+use crate::{SYMLINKS_APPEND, SYMLINKS_DROP, SYMLINKS_FILE, SYMLINKS_READ, SYMLINKS_WRITE};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::sync::OnceLock;
+
+/// Hex digits of a link name (of the 64 of HMAC-SHA256): 128 bits.
+pub const LINK_NAME_LEN: usize = 32;
+
+static KEY: OnceLock<(Vec<u8>, String)> = OnceLock::new();
+
+/// Set the data key and the salt, once per process (at startup, before any [link_name]). Later
+/// calls are ignored.
+pub fn init(data_key: &str, salt: &str) {
+    let _ = KEY.set((data_key.as_bytes().to_vec(), salt.to_owned()));
+}
+
+/// Name of the symlink of share (or nested publication) `name` under `under`. For
+/// [SecondaryDir::Read] that's `name` itself.
+///
+/// # Panics
+///
+/// If [init] wasn't called.
+pub fn link_name(under: SecondaryDir, name: &str) -> String {
+    let prefix = match under {
+        SecondaryDir::Read => return name.to_owned(),
+        SecondaryDir::Write => crate::WRITE,
+        SecondaryDir::Append => crate::APPEND,
+        SecondaryDir::Drop => crate::DROP,
+        SecondaryDir::File => crate::FILE_LINK,
+    };
+    let (key, salt) = KEY.get().expect("links::init sets the key at startup.");
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any size");
+    // Separate by NUL: It can't be in a salt from the environment, nor in a file name.
+    mac.update(format!("{salt}\0{prefix}\0{name}").as_bytes());
+    let mut hex = hex(&mac.finalize().into_bytes());
+    hex.truncate(LINK_NAME_LEN);
+    hex
+}
+
+/// Path of the symlink of share (or nested publication) `name` under `under`.
+pub fn symlink_path(under: SecondaryDir, name: &str) -> String {
+    let dir = match under {
+        SecondaryDir::Read => SYMLINKS_READ,
+        SecondaryDir::Write => SYMLINKS_WRITE,
+        SecondaryDir::Append => SYMLINKS_APPEND,
+        SecondaryDir::Drop => SYMLINKS_DROP,
+        SecondaryDir::File => SYMLINKS_FILE,
+    };
+    format!("{dir}/{}", link_name(under, name))
+}
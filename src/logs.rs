@@ -0,0 +1,396 @@
+//! Access log and audit log, as JSON lines under [crate::LOGS]:
+//!
+//! - `access.jsonl` has one [AccessRecord] per request (see [access_log]). It has the name of the
+//!   share, never the hash of a share's URL, so that the log doesn't leak write access.
+//! - `audit.jsonl` has one [AuditRecord] per admin action (see [audited]), successful or not.
+//!
+//! Once a log is over [MAX_LOG_BYTES], it's renamed with a `.1` suffix (replacing any previous
+//! one), and a new one starts. The admin sees the most recent records of both at `/admin/logs`.
+//!
+//! With a key ([Logs::chained]), access records of write operations also go to the tamper-evident
+//! log of [crate::chain].
+
+use crate::chain::{is_write, WriteChain};
+use crate::error::{self, Error};
+use crate::server::{accept_header, share_of};
+use core::convert::Infallible;
+use http::{HeaderMap, Method};
+use percent_encoding::percent_decode_str;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::task;
+use warp::filters::path::FullPath;
+use warp::{reject::Rejection, reply, Filter, Reply};
+
+pub const ACCESS_LOG: &str = "access.jsonl";
+pub const AUDIT_LOG: &str = "audit.jsonl";
+/// Size of a log before it's rotated.
+pub const MAX_LOG_BYTES: u64 = 16 * 1024 * 1024;
+/// Header that a reverse proxy (like the one of Deta.Space) sets with the client's address.
+const FORWARDED_FOR: &str = "x-forwarded-for";
+
+/// Serializes appending (and rotating).
+static APPENDING: Mutex<()> = Mutex::new(());
+
+/// One request.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AccessRecord {
+    /// Milliseconds since the Unix epoch.
+    pub time: u64,
+    pub client: Option<String>,
+    pub forwarded_for: Option<String>,
+    pub method: String,
+    /// The URL prefix segment, like [crate::WRITE].
+    pub prefix: String,
+    /// [None] for requests that are not for a share (or for a missing one).
+    pub share: Option<String>,
+    /// Within the share. For requests that are not for a share prefix, the path after the prefix.
+    /// [None] for a share prefix with a missing share.
+    pub path: Option<String>,
+    pub status: u16,
+    /// The request's `Content-Length`, if any.
+    pub bytes_received: Option<u64>,
+    /// The response's `Content-Length`, if any (streamed responses may not have it).
+    pub bytes_sent: Option<u64>,
+    /// Until the response starts.
+    pub duration_ms: u64,
+}
+
+/// One admin action.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// Milliseconds since the Unix epoch.
+    pub time: u64,
+    pub client: Option<String>,
+    pub forwarded_for: Option<String>,
+    /// Like `policy`, or `trash/purge`.
+    pub action: String,
+    /// The share (or file) that the action is for, if any.
+    pub share: Option<String>,
+    /// Query parameters.
+    pub params: BTreeMap<String, String>,
+    pub status: u16,
+}
+
+impl AccessRecord {
+    pub fn time(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(self.time)
+    }
+}
+
+impl AuditRecord {
+    pub fn time(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(self.time)
+    }
+}
+
+pub(crate) fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Both logs, in one directory.
+#[derive(Debug, Clone)]
+pub struct Logs {
+    dir: PathBuf,
+    chain: Option<WriteChain>,
+}
+
+impl Logs {
+    /// Logs under `dir` (instead of [crate::LOGS]).
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            chain: None,
+        }
+    }
+
+    /// Also chain write operations (see [crate::chain]), signed with `key`.
+    pub fn chained(mut self, key: impl AsRef<[u8]>) -> Self {
+        self.chain = Some(WriteChain::new(self.dir.clone(), key));
+        self
+    }
+
+    pub fn access_path(&self) -> PathBuf {
+        self.dir.join(ACCESS_LOG)
+    }
+
+    pub fn audit_path(&self) -> PathBuf {
+        self.dir.join(AUDIT_LOG)
+    }
+
+    /// Append `record` to the access log (and to the chain, if any, for write operations).
+    /// Failures are only logged (see [log]), so that logging never fails a request.
+    pub async fn access(&self, record: AccessRecord) {
+        if let Some(chain) = self.chain.clone().filter(|_| is_write(&record.method)) {
+            let record = record.clone();
+            match task::spawn_blocking(move || chain.append(record)).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => log::error!("Couldn't append to the log of write operations: {e}."),
+                Err(e) => log::error!("Couldn't append to the log of write operations: {e}."),
+            }
+        }
+        append_logged(self.access_path(), record).await;
+    }
+
+    pub async fn audit(&self, record: AuditRecord) {
+        append_logged(self.audit_path(), record).await;
+    }
+
+    /// Up to `limit` most recent records of the access log, the most recent first.
+    pub async fn recent_access(&self, limit: usize) -> Result<Vec<AccessRecord>, Error> {
+        recent(self.access_path(), limit).await
+    }
+
+    pub async fn recent_audit(&self, limit: usize) -> Result<Vec<AuditRecord>, Error> {
+        recent(self.audit_path(), limit).await
+    }
+}
+
+/// Where a log is rotated to.
+pub fn rotated(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_owned();
+    name.push(".1");
+    path.with_file_name(name)
+}
+
+/// Append `line` (without a newline) to log `path`, after rotating it if it would go over
+/// [MAX_LOG_BYTES]. Blocking.
+pub fn append_line(path: &Path, line: &str) -> io::Result<()> {
+    let _appending = APPENDING.lock().unwrap_or_else(|e| e.into_inner());
+    let len = match fs::metadata(path) {
+        Ok(metadata) => metadata.len(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => 0,
+        Err(e) => return Err(e),
+    };
+    if len > 0 && len + line.len() as u64 + 1 > MAX_LOG_BYTES {
+        fs::rename(path, rotated(path))?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    // One write per record, so that records don't interleave.
+    file.write_all(format!("{line}\n").as_bytes())
+}
+
+async fn append_logged(path: PathBuf, record: impl Serialize) {
+    let line = match serde_json::to_string(&record) {
+        Ok(line) => line,
+        Err(e) => {
+            log::error!("Couldn't serialize a log record: {e}.");
+            return;
+        }
+    };
+    let result = task::spawn_blocking(move || {
+        append_line(&path, &line).map_err(|e| format!("{}: {e}", path.display()))
+    })
+    .await;
+    match result {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => log::error!("Couldn't append to log {e}."),
+        Err(e) => log::error!("Couldn't append to a log: {e}."),
+    }
+}
+
+/// Records of log `path` (after those of its rotated predecessor). Lines that don't parse are
+/// skipped.
+async fn recent<R: DeserializeOwned + Send + 'static>(
+    path: PathBuf,
+    limit: usize,
+) -> Result<Vec<R>, Error> {
+    task::spawn_blocking(move || {
+        let mut records = Vec::new();
+        for path in [rotated(&path), path] {
+            match fs::read_to_string(&path) {
+                Ok(content) => records.extend(
+                    content
+                        .lines()
+                        .filter_map(|line| serde_json::from_str::<R>(line).ok()),
+                ),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(Error::from_io(e, path.display().to_string())),
+            }
+        }
+        let skip = records.len().saturating_sub(limit);
+        let mut records: Vec<_> = records.into_iter().skip(skip).collect();
+        records.reverse();
+        Ok(records)
+    })
+    .await
+    .map_err(Error::from)?
+}
+
+/// What both logs have of a request.
+#[derive(Debug, Clone)]
+struct Client {
+    start: Instant,
+    time: u64,
+    client: Option<String>,
+    forwarded_for: Option<String>,
+    bytes_received: Option<u64>,
+}
+
+fn client() -> impl Filter<Extract = (Client,), Error = Infallible> + Clone {
+    warp::addr::remote()
+        .and(warp::header::headers_cloned())
+        .map(|remote: Option<SocketAddr>, headers: HeaderMap| Client {
+            start: Instant::now(),
+            time: now_ms(),
+            client: remote.map(|remote| remote.ip().to_string()),
+            forwarded_for: headers
+                .get(FORWARDED_FOR)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_owned),
+            bytes_received: headers
+                .get(http::header::CONTENT_LENGTH)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse().ok()),
+        })
+}
+
+/// The first segment of `path`, and the (percent-decoded) rest.
+fn split_prefix(path: &FullPath) -> (String, String) {
+    let path = path.as_str().trim_start_matches('/');
+    let (prefix, rest) = path.split_once('/').unwrap_or((path, ""));
+    let decode = |s: &str| percent_decode_str(s).decode_utf8_lossy().to_string();
+    (decode(prefix), decode(rest))
+}
+
+/// Log every reply of `filter` to the access log of `logs`. `shares` maps URL prefix segments to
+/// their directories of symlinks, so that records have the share and the path within it. Put it
+/// around all routes (after recovering from rejections, since it only logs replies).
+pub fn access_log<F, R>(
+    logs: Logs,
+    shares: Vec<(&'static str, PathBuf)>,
+    filter: F,
+) -> impl Filter<Extract = (reply::Response,), Error = Rejection> + Clone
+where
+    F: Filter<Extract = (R,), Error = Rejection> + Clone + Send + Sync + 'static,
+    R: Reply,
+{
+    let shares = Arc::new(shares);
+    client()
+        .and(warp::method())
+        .and(warp::path::full())
+        .and(filter.map(Reply::into_response))
+        .then(
+            move |client: Client, method: Method, path: FullPath, response: reply::Response| {
+                let logs = logs.clone();
+                let shares = shares.clone();
+                async move {
+                    let duration_ms = client.start.elapsed().as_millis() as u64;
+                    let (prefix, rest) = split_prefix(&path);
+                    let symlinks_dir = shares
+                        .iter()
+                        .find(|(segment, _)| *segment == prefix)
+                        .map(|(_, dir)| dir);
+                    let (share, path) = match symlinks_dir {
+                        Some(symlinks_dir) => {
+                            match share_of(symlinks_dir, Path::new(&rest)).await {
+                                Some(target) => (
+                                    Some(target.name),
+                                    Some(target.within.to_string_lossy().to_string()),
+                                ),
+                                None => (None, None),
+                            }
+                        }
+                        None => (None, Some(rest)),
+                    };
+                    let bytes_sent = response
+                        .headers()
+                        .get(http::header::CONTENT_LENGTH)
+                        .and_then(|value| value.to_str().ok())
+                        .and_then(|value| value.parse().ok());
+                    logs.access(AccessRecord {
+                        time: client.time,
+                        client: client.client,
+                        forwarded_for: client.forwarded_for,
+                        method: method.to_string(),
+                        prefix,
+                        share,
+                        path,
+                        status: response.status().as_u16(),
+                        bytes_received: client.bytes_received,
+                        bytes_sent,
+                        duration_ms,
+                    })
+                    .await;
+                    response
+                }
+            },
+        )
+}
+
+/// Log every POST that `filter` handles (or fails) to the audit log of `logs`. The action and the
+/// share come from the path after the (admin) prefix segment: `<action>/<share>[/<sub-action>]`.
+/// Rejections other than [warp::reject::not_found] become error replies here, so that failed
+/// actions are logged, too. Put it around the admin handlers that change anything.
+pub fn audited<F, R>(
+    logs: Logs,
+    filter: F,
+) -> impl Filter<Extract = (reply::Response,), Error = Rejection> + Clone
+where
+    F: Filter<Extract = (R,), Error = Rejection> + Clone + Send + Sync + 'static,
+    R: Reply,
+{
+    let result = filter
+        .map(|reply: R| Ok(reply.into_response()))
+        .or_else(|rejection| async move { Ok::<_, Rejection>((Err(rejection),)) });
+    warp::method()
+        .and(client())
+        .and(warp::path::full())
+        .and(
+            warp::query::<BTreeMap<String, String>>()
+                .or(warp::any().map(BTreeMap::new))
+                .unify(),
+        )
+        .and(accept_header())
+        .and(result)
+        .and_then(
+            move |method: Method,
+                  client: Client,
+                  path: FullPath,
+                  params: BTreeMap<String, String>,
+                  accept: Option<String>,
+                  result: Result<reply::Response, Rejection>| {
+                let logs = logs.clone();
+                async move {
+                    let response = match result {
+                        Ok(response) => response,
+                        // Like GET of a path that only has POST: Not an action.
+                        Err(rejection) if rejection.is_not_found() || method != Method::POST => {
+                            return Err(rejection)
+                        }
+                        Err(rejection) => {
+                            error::reply(&rejection, accept.as_deref()).into_response()
+                        }
+                    };
+                    let (_, rest) = split_prefix(&path);
+                    let mut segments = rest.split('/').filter(|segment| !segment.is_empty());
+                    let mut action = segments.next().unwrap_or_default().to_owned();
+                    let share = segments.next().map(str::to_owned);
+                    if let Some(sub_action) = segments.next() {
+                        action = format!("{action}/{sub_action}");
+                    }
+                    logs.audit(AuditRecord {
+                        time: client.time,
+                        client: client.client,
+                        forwarded_for: client.forwarded_for,
+                        action,
+                        share,
+                        params,
+                        status: response.status().as_u16(),
+                    })
+                    .await;
+                    Ok(response)
+                }
+            },
+        )
+}
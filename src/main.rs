@@ -1,14 +1,13 @@
+use std::env;
 use std::io;
-use wdav_crypto_rs::server;
-
-const _NOT_MOCKABLE: () = {
-    #[cfg(feature = "mockable")]
-    panic!(
-        "Use `mockable` (and related) feature only when testing (and only through `test-binary`)."
-    );
-};
+use wdav_crypto_rs::{chain, server};
 
 #[tokio::main]
 pub async fn main() -> io::Result<()> {
-    server::main().await
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    match args.first().map(String::as_str) {
+        // `verify-log [dir]`: Check the log of write operations, instead of serving.
+        Some("verify-log") => chain::verify_command(args.get(1).map(String::as_str)),
+        _ => server::main().await,
+    }
 }
@@ -0,0 +1,365 @@
+//! HTML file manager for [crate::WRITE]: Browsers (which can't list nor upload over WebDAV, see
+//! `docs/clients.md`) get a directory listing with forms to upload files, create folders, rename
+//! and delete - without JavaScript. WebDAV clients don't get it: It handles only GET of a directory
+//! that accepts `text/html`, and POST (which WebDAV doesn't use).
+//!
+//! Every action is subject to the share's [crate::meta::MethodPolicy] as its WebDAV counterpart
+//! (PUT, MKCOL, MOVE and DELETE), and it keeps versions and uses the trash like that counterpart.
+
+use crate::error::{self, Error};
+use crate::extract::staging_path;
+use crate::meta::ShareMeta;
+use crate::props::{log_failure, PropStore};
+use crate::quota;
+use crate::server::{accept_header, check_name, decode_tail, receive, share_of, ShareTarget};
+use crate::trash::ShareTrash;
+use crate::versions::ShareVersions;
+use askama::Template;
+use futures_util::StreamExt;
+use http::{uri::Uri, Method};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tokio::fs as tokio_fs;
+use tokio::task;
+use warp::multipart::{FormData, Part};
+use warp::path::Peek;
+use warp::{redirect, reject::Rejection, reply, Buf, Filter, Reply};
+
+/// Max. size of one POST (all uploaded files together).
+pub const MAX_UPLOAD_BYTES: u64 = 4 * 1024 * 1024 * 1024;
+
+/// Max. size of a text field (like a folder name).
+const MAX_FIELD_BYTES: usize = 4 * 1024;
+
+/// One (non-symlink) entry of a listed directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Listed {
+    pub name: String,
+    pub is_dir: bool,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+}
+
+/// Entries of `dir`: Directories first, then files, each sorted by name. Symlinks are skipped,
+/// like WebDAV listing skips them (see [crate::server]). Blocking.
+pub fn list(dir: &Path) -> io::Result<Vec<Listed>> {
+    let mut listed = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_symlink() {
+            continue;
+        }
+        listed.push(Listed {
+            name: entry.file_name().to_string_lossy().to_string(),
+            is_dir: metadata.is_dir(),
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+        });
+    }
+    listed.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+    Ok(listed)
+}
+
+#[derive(Template)]
+#[template(path = "write_manager.html")]
+pub struct ManagerTemplate<'a> {
+    /// Path of the directory within the share (empty for the share itself).
+    pub within: &'a str,
+    pub entries: &'a [Listed],
+}
+
+impl ManagerTemplate<'_> {
+    fn date(&self, listed: &Listed) -> String {
+        listed
+            .modified
+            .map(httpdate::fmt_http_date)
+            .unwrap_or_default()
+    }
+}
+
+/// The directory that a request (its path after the URL prefix segment) is for: Its share, and its
+/// path through `symlinks_dir`. [None] if it's not a directory in a share.
+async fn directory(symlinks_dir: &Path, tail: &Peek) -> Option<(ShareTarget, PathBuf)> {
+    let relative = decode_tail(tail).ok()?;
+    let share = share_of(symlinks_dir, &relative).await?;
+    let dir = symlinks_dir.join(&relative);
+    let is_dir = tokio_fs::metadata(&dir).await.is_ok_and(|m| m.is_dir());
+    is_dir.then_some((share, dir))
+}
+
+/// The file manager: GET of a directory (by browsers) lists it, and POST `?action=...` (see [post])
+/// changes it. Reject anything else (with [warp::reject::not_found]), so that the request can go on
+/// to the DAV handler through [Filter::or]. Errors of POST are replies (like in
+/// [crate::server::trash_delete]). Put it in front of the DAV handler of [crate::WRITE], after the
+/// URL prefix segment. Uploads are received into `staging_dir` (on the same filesystem as the
+/// shares) first. Dead properties (of `symlinks_dir`) in `props` follow renames, and go with
+/// deleted and replaced files.
+pub fn file_manager(
+    prefix_segment: &'static str,
+    symlinks_dir: impl Into<PathBuf>,
+    staging_dir: impl Into<PathBuf>,
+    props: PropStore,
+) -> impl Filter<Extract = (Box<dyn Reply>,), Error = Rejection> + Clone {
+    let symlinks_dir = symlinks_dir.into();
+    let staging_dir = staging_dir.into();
+
+    let get = {
+        let symlinks_dir = symlinks_dir.clone();
+        warp::get()
+            .and(warp::path::peek())
+            .and(accept_header())
+            .and_then(move |tail: Peek, accept: Option<String>| {
+                let symlinks_dir = symlinks_dir.clone();
+                async move {
+                    if !accept.is_some_and(|accept| accept.contains("text/html")) {
+                        return Err(warp::reject::not_found());
+                    }
+                    let Some((share, dir)) = directory(&symlinks_dir, &tail).await else {
+                        return Err(warp::reject::not_found());
+                    };
+                    // Relative links in the listing need the trailing slash.
+                    if !tail.as_str().ends_with('/') {
+                        let location = format!("/{prefix_segment}/{}/", tail.as_str());
+                        let uri = location
+                            .parse::<Uri>()
+                            .map_err(|_| warp::reject::not_found())?;
+                        return Ok(Box::new(redirect::see_other(uri)) as Box<dyn Reply>);
+                    }
+                    let entries = task::spawn_blocking(move || list(&dir))
+                        .await
+                        .map_err(Error::from)?
+                        .map_err(Error::from)?;
+                    let within = share.within.display().to_string();
+                    let html = ManagerTemplate {
+                        within: &within,
+                        entries: &entries,
+                    }
+                    .render()
+                    .map_err(Error::from)?;
+                    Ok(Box::new(reply::html(html)) as Box<dyn Reply>)
+                }
+            })
+    };
+
+    let post = warp::post()
+        .and(warp::path::peek())
+        .and(accept_header())
+        .and(warp::query::<HashMap<String, String>>())
+        .and(warp::multipart::form().max_length(MAX_UPLOAD_BYTES))
+        .and_then(
+            move |tail: Peek,
+                  accept: Option<String>,
+                  query: HashMap<String, String>,
+                  form: FormData| {
+                let (symlinks_dir, staging_dir) = (symlinks_dir.clone(), staging_dir.clone());
+                let props = props.clone();
+                async move {
+                    let action = query.get("action").map(String::as_str).unwrap_or_default();
+                    let posted =
+                        post(&symlinks_dir, &staging_dir, &props, &tail, action, form).await;
+                    Ok::<_, Rejection>(match posted {
+                        Ok(()) => {
+                            let location = format!("/{prefix_segment}/{}", tail.as_str());
+                            match location.parse::<Uri>() {
+                                Ok(uri) => Box::new(redirect::see_other(uri)) as Box<dyn Reply>,
+                                Err(_) => Box::new(http::StatusCode::NO_CONTENT),
+                            }
+                        }
+                        Err(e) => error::reply(&warp::reject::custom(e), accept.as_deref()),
+                    })
+                }
+            },
+        );
+
+    get.or(post).unify()
+}
+
+/// Apply `action` to the directory that the request is for: `upload` (one or more `file` fields),
+/// `mkdir` (`name`), `rename` (`from` and `to`) or `delete` (`name`). All names are leaves in that
+/// directory.
+async fn post(
+    symlinks_dir: &Path,
+    staging_dir: &Path,
+    props: &PropStore,
+    tail: &Peek,
+    action: &str,
+    mut form: FormData,
+) -> Result<(), Error> {
+    let (share, dir) = directory(symlinks_dir, tail)
+        .await
+        .ok_or_else(|| Error::NotFound("directory".to_owned()))?;
+    let meta = ShareMeta::load(&share.name).await?;
+    // Of `dir` (under `symlinks_dir`), for `props`.
+    let dir_relative = decode_tail(tail)?;
+
+    if action == "upload" {
+        meta.policy.check(&Method::PUT, Some(0))?;
+        while let Some(part) = form.next().await {
+            let part = part.map_err(|e| Error::BadRequest(format!("form: {e}")))?;
+            let Some(file_name) = part.filename().filter(|name| !name.is_empty()) else {
+                continue;
+            };
+            let file_name = file_name.to_owned();
+            check_name(&file_name)?;
+            upload(&share, &dir, staging_dir, &file_name, part, &meta).await?;
+            // A replaced file's extended attributes went with it, and so do its sidecar entries.
+            let replaced = dir_relative.join(&file_name);
+            log_failure(
+                "removed",
+                replaced.display(),
+                props.removed(&replaced).await,
+            );
+        }
+        return Ok(());
+    }
+
+    let fields = fields(form).await?;
+    let field = |key: &str| {
+        let value = fields
+            .get(key)
+            .ok_or_else(|| Error::BadRequest(key.to_owned()))?;
+        check_name(value)?;
+        Ok::<_, Error>(value.as_str())
+    };
+    match action {
+        "mkdir" => {
+            meta.policy
+                .check(&Method::from_bytes(b"MKCOL").expect("MKCOL"), None)?;
+            let name = field("name")?;
+            tokio_fs::create_dir(dir.join(name))
+                .await
+                .map_err(|e| Error::from_io(e, name))?;
+        }
+        "rename" => {
+            meta.policy
+                .check(&Method::from_bytes(b"MOVE").expect("MOVE"), None)?;
+            let (from, to) = (field("from")?, field("to")?);
+            visible(&dir, from).await?;
+            if tokio_fs::symlink_metadata(dir.join(to)).await.is_ok() {
+                return Err(Error::AlreadyExists(to.to_owned()));
+            }
+            tokio_fs::rename(dir.join(from), dir.join(to)).await?;
+            let (from, to) = (dir_relative.join(from), dir_relative.join(to));
+            log_failure("moved", from.display(), props.moved(&from, &to).await);
+        }
+        "delete" => {
+            meta.policy.check(&Method::DELETE, None)?;
+            let name = field("name")?;
+            let is_dir = visible(&dir, name).await?.is_dir();
+            let ShareTarget {
+                name: share_name,
+                dir: share_dir,
+                within,
+            } = share;
+            let relative = within.join(name);
+            if meta.trash_days > 0 {
+                task::spawn_blocking(move || {
+                    ShareTrash::of(&share_name)
+                        .with_cap(meta.quota_bytes)
+                        .put(&share_dir, &relative)
+                })
+                .await??;
+            } else {
+                let versions = ShareVersions::of(&share_name).with_cap(meta.quota_bytes);
+                let retention = meta.versions;
+                task::spawn_blocking(move || versions.snapshot(&share_dir, &relative, retention))
+                    .await??;
+                let path = dir.join(name);
+                if is_dir {
+                    tokio_fs::remove_dir_all(path).await?;
+                } else {
+                    tokio_fs::remove_file(path).await?;
+                }
+            }
+            // Extended attributes went to the trash (or away), too.
+            let removed = dir_relative.join(name);
+            log_failure("removed", removed.display(), props.removed(&removed).await);
+        }
+        _ => return Err(Error::BadRequest(format!("action {action}"))),
+    }
+    Ok(())
+}
+
+/// Metadata of `dir/name`, unless it's missing or a (hidden) symlink.
+async fn visible(dir: &Path, name: &str) -> Result<fs::Metadata, Error> {
+    match tokio_fs::symlink_metadata(dir.join(name)).await {
+        Ok(metadata) if !metadata.is_symlink() => Ok(metadata),
+        _ => Err(Error::NotFound(name.to_owned())),
+    }
+}
+
+/// Write uploaded `part` to `dir/file_name` (within the [crate::quota]), keeping a version of the
+/// file that it replaces. It's received under `staging_dir` first, so that a failed upload leaves
+/// the file that it would replace as it was.
+async fn upload(
+    share: &ShareTarget,
+    dir: &Path,
+    staging_dir: &Path,
+    file_name: &str,
+    part: Part,
+    meta: &ShareMeta,
+) -> Result<(), Error> {
+    let path = dir.join(file_name);
+    if let Ok(metadata) = tokio_fs::symlink_metadata(&path).await {
+        if !metadata.is_file() {
+            return Err(Error::AlreadyExists(file_name.to_owned()));
+        }
+    }
+    let remaining =
+        quota::remaining(share.dir.clone(), meta.quota_bytes, Some(path.clone())).await?;
+    let limit = match (meta.policy.max_put_bytes, remaining) {
+        (Some(max), Some(remaining)) => Some(max.min(remaining)),
+        (max, remaining) => max.or(remaining),
+    };
+    let staged = staging_path(staging_dir, "upload");
+    if let Err(e) = receive(part.stream(), &staged, limit).await {
+        let _ = tokio_fs::remove_file(&staged).await;
+        return Err(match e {
+            Error::PayloadTooLarge { limit } if Some(limit) == remaining => Error::QuotaExceeded,
+            e => e,
+        });
+    }
+
+    let versions = ShareVersions::of(&share.name).with_cap(meta.quota_bytes);
+    let (share_dir, relative, retention) = (
+        share.dir.clone(),
+        share.within.join(file_name),
+        meta.versions,
+    );
+    let moved = async {
+        task::spawn_blocking(move || versions.snapshot(&share_dir, &relative, retention)).await??;
+        tokio_fs::rename(&staged, &path).await?;
+        Ok::<_, Error>(())
+    };
+    if let Err(e) = moved.await {
+        let _ = tokio_fs::remove_file(&staged).await;
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Text fields of `form`, mapped by their names.
+async fn fields(mut form: FormData) -> Result<HashMap<String, String>, Error> {
+    let mut fields = HashMap::new();
+    while let Some(part) = form.next().await {
+        let part = part.map_err(|e| Error::BadRequest(format!("form: {e}")))?;
+        let name = part.name().to_owned();
+        let mut value = Vec::new();
+        let mut stream = Box::pin(part.stream());
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(|e| Error::BadRequest(format!("form: {e}")))?;
+            value.extend_from_slice(chunk.chunk());
+            if value.len() > MAX_FIELD_BYTES {
+                return Err(Error::BadRequest(format!("field {name} is too long")));
+            }
+        }
+        let value =
+            String::from_utf8(value).map_err(|_| Error::BadRequest(format!("field {name}")))?;
+        fields.insert(name, value);
+    }
+    Ok(fields)
+}
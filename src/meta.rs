@@ -0,0 +1,247 @@
+//! Per-share metadata, stored as `{META}/<name>.json` - outside of [crate::DIRS], so that WebDAV
+//! clients can't see nor change it.
+
+use crate::error::Error;
+use crate::META;
+use core::fmt;
+use core::str::FromStr;
+use dav_server::{DavMethod, DavMethodSet};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use tokio::fs as tokio_fs;
+
+/// Restrictions on top of the methods that a URL prefix (like [crate::WRITE]) allows.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MethodPolicy {
+    /// Method names (or groups, like `http-rw`), as [DavMethodSet::from_vec] accepts them.
+    pub denied_methods: Vec<String>,
+    /// Max. `Content-Length` of a PUT. With a limit, PUT requires `Content-Length`.
+    pub max_put_bytes: Option<u64>,
+}
+
+impl MethodPolicy {
+    /// Validate `denied_methods`.
+    pub fn new(denied_methods: Vec<String>, max_put_bytes: Option<u64>) -> Result<Self, Error> {
+        let policy = Self {
+            denied_methods,
+            max_put_bytes,
+        };
+        policy
+            .denied()
+            .map_err(|_| Error::BadRequest("unknown method".to_owned()))?;
+        Ok(policy)
+    }
+
+    fn denied(&self) -> Result<DavMethodSet, http::method::InvalidMethod> {
+        DavMethodSet::from_vec(self.denied_methods.clone())
+    }
+
+    /// Whether a request with `method` (and `content_length`, if any) is allowed. Methods unknown
+    /// to WebDAV pass, so that the DAV handler rejects them.
+    pub fn check(&self, method: &http::Method, content_length: Option<u64>) -> Result<(), Error> {
+        let Ok(dav_method) = DavMethod::try_from(method) else {
+            return Ok(());
+        };
+        let denied = self
+            .denied()
+            .map_err(|e| Error::Io(io::Error::other(format!("Stored method policy: {e}"))))?;
+        if denied.contains(dav_method) {
+            return Err(Error::MethodNotAllowed(method.to_string()));
+        }
+        if let (DavMethod::Put, Some(limit)) = (dav_method, self.max_put_bytes) {
+            match content_length {
+                None => return Err(Error::LengthRequired),
+                Some(length) if length > limit => return Err(Error::PayloadTooLarge { limit }),
+                Some(_) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for MethodPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.denied_methods.is_empty(), self.max_put_bytes) {
+            (true, None) => write!(f, "all methods"),
+            (false, None) => write!(f, "no {}", self.denied_methods.join(", ")),
+            (true, Some(limit)) => write!(f, "PUT up to {limit} bytes"),
+            (false, Some(limit)) => write!(
+                f,
+                "no {}; PUT up to {limit} bytes",
+                self.denied_methods.join(", ")
+            ),
+        }
+    }
+}
+
+/// Default number of versions that [Retention::KeepLast] keeps per file.
+pub const DEFAULT_KEEP_VERSIONS: usize = 10;
+
+/// How long [crate::versions] keeps previous versions of overwritten or deleted files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Retention {
+    /// Don't keep any.
+    Off,
+    /// The newest versions, this many per file.
+    KeepLast(usize),
+    /// Versions from this many last days.
+    KeepDays(u64),
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Self::KeepLast(DEFAULT_KEEP_VERSIONS)
+    }
+}
+
+impl fmt::Display for Retention {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Off => write!(f, "no versions"),
+            Self::KeepLast(count) => write!(f, "last {count} versions"),
+            Self::KeepDays(days) => write!(f, "versions from last {days} days"),
+        }
+    }
+}
+
+/// Parse `off`, `last:<count>` or `days:<days>`.
+impl FromStr for Retention {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::BadRequest(format!("retention {s}"));
+        match s.split_once(':') {
+            None if s == "off" => Ok(Self::Off),
+            Some(("last", count)) => Ok(Self::KeepLast(count.parse().map_err(|_| invalid())?)),
+            Some(("days", days)) => Ok(Self::KeepDays(days.parse().map_err(|_| invalid())?)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Default of [SiteConfig::max_age].
+pub const DEFAULT_SITE_MAX_AGE: u64 = 300;
+
+/// How [crate::site] serves a share as a static website under [crate::READ]. Paths are relative to
+/// the published directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SiteConfig {
+    /// Served (with 200) for paths that don't exist, like `index.html` of a single-page app.
+    pub fallback: Option<String>,
+    /// Served (with 404) for paths that don't exist, unless there is a [Self::fallback].
+    pub not_found: Option<String>,
+    /// `Cache-Control: max-age` (in seconds) of files other than HTML (which is always
+    /// revalidated).
+    pub max_age: u64,
+}
+
+impl Default for SiteConfig {
+    fn default() -> Self {
+        Self {
+            fallback: None,
+            not_found: None,
+            max_age: DEFAULT_SITE_MAX_AGE,
+        }
+    }
+}
+
+impl fmt::Display for SiteConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "static site (max-age {}", self.max_age)?;
+        if let Some(fallback) = &self.fallback {
+            write!(f, ", fallback {fallback}")?;
+        }
+        if let Some(not_found) = &self.not_found {
+            write!(f, ", 404 page {not_found}")?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Default of [ShareMeta::trash_days].
+pub const DEFAULT_TRASH_DAYS: u64 = 30;
+
+/// Everything about a share that isn't in the filesystem layout itself.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShareMeta {
+    pub policy: MethodPolicy,
+    /// Versions of files overwritten or deleted through [crate::WRITE].
+    pub versions: Retention,
+    /// How long [crate::trash] keeps what was deleted through [crate::WRITE]. With 0, DELETE
+    /// removes permanently.
+    pub trash_days: u64,
+    /// Set by [crate::share::freeze] on the frozen copy: Name of the share that it was copied from.
+    pub frozen_from: Option<String>,
+    /// With [Some], [crate::READ] serves the share as a static website (and WebDAV of it is at
+    /// [crate::READ_DAV]).
+    pub site: Option<SiteConfig>,
+    /// Max. total size of the share's files (see [crate::quota]).
+    pub quota_bytes: Option<u64>,
+}
+
+impl Default for ShareMeta {
+    fn default() -> Self {
+        Self {
+            policy: MethodPolicy::default(),
+            versions: Retention::default(),
+            trash_days: DEFAULT_TRASH_DAYS,
+            frozen_from: None,
+            site: None,
+            quota_bytes: None,
+        }
+    }
+}
+
+fn path(meta_dir: &Path, name: &str) -> PathBuf {
+    meta_dir.join(format!("{name}.json"))
+}
+
+impl ShareMeta {
+    /// Metadata of share `name`, or the default if it has none.
+    pub async fn load(name: &str) -> Result<Self, Error> {
+        Self::load_in(Path::new(META), name).await
+    }
+
+    /// Like [ShareMeta::load], but from `meta_dir` instead of [META].
+    pub async fn load_in(meta_dir: &Path, name: &str) -> Result<Self, Error> {
+        match tokio_fs::read(path(meta_dir, name)).await {
+            Ok(json) => serde_json::from_slice(&json).map_err(|e| Error::Io(e.into())),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Metadata of all shares that have any, mapped by share names.
+    pub async fn load_all() -> Result<HashMap<String, Self>, Error> {
+        let mut metas = HashMap::new();
+        let mut dir = tokio_fs::read_dir(META).await?;
+        while let Some(file) = dir.next_entry().await? {
+            let file_name = file.file_name().to_string_lossy().to_string();
+            if let Some(name) = file_name.strip_suffix(".json") {
+                metas.insert(name.to_owned(), Self::load(name).await?);
+            }
+        }
+        Ok(metas)
+    }
+
+    /// Save atomically (through a temporary file and a rename), so that requests never load a
+    /// partial file.
+    pub async fn save(&self, name: &str) -> Result<(), Error> {
+        self.save_in(Path::new(META), name).await
+    }
+
+    /// Like [ShareMeta::save], but into `meta_dir` instead of [META].
+    pub async fn save_in(&self, meta_dir: &Path, name: &str) -> Result<(), Error> {
+        let json = serde_json::to_vec_pretty(self).map_err(|e| Error::Io(e.into()))?;
+        let tmp = meta_dir.join(format!(".{name}.json.tmp"));
+        tokio_fs::write(&tmp, json).await?;
+        tokio_fs::rename(tmp, path(meta_dir, name)).await?;
+        Ok(())
+    }
+}
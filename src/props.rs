@@ -0,0 +1,538 @@
+//! Dead properties: What PROPPATCH sets (and PROPFIND reports), but what means nothing to the
+//! server, like tags, colors or sync state of clients. They're kept in an extended attribute
+//! ([XATTR]) of each file or directory, so that they go wherever it goes. Where the filesystem
+//! doesn't have (user) extended attributes, or they don't fit, they're kept in a sidecar store
+//! instead: `<share name>.json` under [crate::PROPS], by paths within the share.
+//!
+//! [PropsFs] serves them to dav-server, and it moves, copies and removes them along with files (and
+//! directories, except for COPY of directories: See [with_collection_props]). So do the file
+//! manager and the tus and chunked uploads, for what they rename, delete or replace.
+
+use crate::server::{decode_tail, destination_relative, share_of};
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
+use dav_server::davpath::DavPath;
+use dav_server::fs::{
+    DavDirEntry, DavFile, DavFileSystem, DavMetaData, DavProp, FsError, FsFuture, FsStream,
+    OpenOptions, ReadDirMeta,
+};
+use futures_util::{future, FutureExt};
+use http::{Method, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use tokio::task;
+use warp::path::Peek;
+use warp::{reject::Rejection, reply, Filter, Reply};
+
+/// Name of the extended attribute with the dead properties (as JSON) of a file or directory.
+pub const XATTR: &str = "user.wdav.props";
+
+/// A dead property, as stored.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StoredProp {
+    name: String,
+    prefix: Option<String>,
+    namespace: Option<String>,
+    /// The whole property element.
+    xml: Option<String>,
+}
+
+impl From<DavProp> for StoredProp {
+    fn from(prop: DavProp) -> Self {
+        Self {
+            name: prop.name,
+            prefix: prop.prefix,
+            namespace: prop.namespace,
+            xml: prop
+                .xml
+                .map(|xml| String::from_utf8_lossy(&xml).to_string()),
+        }
+    }
+}
+
+impl StoredProp {
+    fn to_dav(&self, with_content: bool) -> DavProp {
+        DavProp {
+            name: self.name.clone(),
+            prefix: self.prefix.clone(),
+            namespace: self.namespace.clone(),
+            xml: self
+                .xml
+                .as_ref()
+                .filter(|_| with_content)
+                .map(|xml| xml.clone().into_bytes()),
+        }
+    }
+}
+
+/// Dead properties of a file or directory, by `{namespace}name`.
+pub type Props = BTreeMap<String, StoredProp>;
+
+fn key(namespace: &Option<String>, name: &str) -> String {
+    format!("{{{}}}{name}", namespace.as_deref().unwrap_or_default())
+}
+
+/// [Props] in [XATTR] of `path`. [None] if the filesystem doesn't have extended attributes.
+/// Blocking.
+fn load_xattr(path: &Path) -> io::Result<Option<Props>> {
+    match xattr::get_deref(path, XATTR) {
+        Ok(Some(value)) => Ok(Some(serde_json::from_slice(&value)?)),
+        Ok(None) => Ok(Some(Props::new())),
+        Err(e) if e.kind() == io::ErrorKind::Unsupported => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Set (or, if empty, remove) [XATTR] of `path`. Whether it worked: Not if the filesystem doesn't
+/// have extended attributes, or if `props` don't fit. Blocking.
+fn save_xattr(path: &Path, props: &Props) -> io::Result<bool> {
+    let saved = if !props.is_empty() {
+        xattr::set_deref(path, XATTR, &serde_json::to_vec(props)?)
+    } else {
+        match xattr::get_deref(path, XATTR) {
+            Ok(Some(_)) => xattr::remove_deref(path, XATTR),
+            Ok(None) => Ok(()),
+            Err(e) => Err(e),
+        }
+    };
+    match saved {
+        Ok(()) => Ok(true),
+        Err(e)
+            if matches!(
+                e.kind(),
+                io::ErrorKind::Unsupported
+                    | io::ErrorKind::StorageFull
+                    | io::ErrorKind::ArgumentListTooLong
+            ) =>
+        {
+            Ok(false)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Serializes changes of sidecar files.
+static SIDECAR: Mutex<()> = Mutex::new(());
+
+/// [Props] by paths within a share, as in a sidecar file.
+type Sidecar = BTreeMap<String, Props>;
+
+/// Whether sidecar key `key` is `of`, or under it.
+fn is_within(key: &str, of: &str) -> bool {
+    of.is_empty()
+        || key == of
+        || key
+            .strip_prefix(of)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+fn read_sidecar(file: &Path) -> io::Result<Sidecar> {
+    match fs::read(file) {
+        Ok(json) => Ok(serde_json::from_slice(&json)?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Sidecar::new()),
+        Err(e) => Err(e),
+    }
+}
+
+fn write_sidecar(file: &Path, sidecar: &Sidecar) -> io::Result<()> {
+    if sidecar.is_empty() {
+        return match fs::remove_file(file) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        };
+    }
+    let partial = file.with_extension("json.partial");
+    fs::write(&partial, serde_json::to_vec(sidecar)?)?;
+    fs::rename(partial, file)
+}
+
+/// Change sidecar `file` by `change` (which returns whether it changed anything). Blocking.
+fn change_sidecar(file: &Path, change: impl FnOnce(&mut Sidecar) -> bool) -> io::Result<()> {
+    let _changing = SIDECAR.lock().unwrap_or_else(|e| e.into_inner());
+    let mut sidecar = read_sidecar(file)?;
+    if change(&mut sidecar) {
+        write_sidecar(file, &sidecar)?;
+    }
+    Ok(())
+}
+
+/// Where a path is in the sidecar store: Its sidecar file, and its key there.
+struct SidecarEntry {
+    file: PathBuf,
+    key: String,
+}
+
+/// Dead properties of paths under a symlinks directory (see [crate::SYMLINKS]).
+#[derive(Clone, Debug)]
+pub struct PropStore {
+    symlinks_dir: PathBuf,
+    sidecar_dir: PathBuf,
+    xattrs: bool,
+}
+
+impl PropStore {
+    /// Store of paths under `symlinks_dir`, with sidecar files in `sidecar_dir`. Without `xattrs`,
+    /// the sidecar store is used for everything.
+    pub fn new(
+        symlinks_dir: impl Into<PathBuf>,
+        sidecar_dir: impl Into<PathBuf>,
+        xattrs: bool,
+    ) -> Self {
+        Self {
+            symlinks_dir: symlinks_dir.into(),
+            sidecar_dir: sidecar_dir.into(),
+            xattrs,
+        }
+    }
+
+    async fn sidecar_entry(&self, relative: &Path) -> io::Result<SidecarEntry> {
+        let share = share_of(&self.symlinks_dir, relative)
+            .await
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?;
+        Ok(SidecarEntry {
+            file: self.sidecar_dir.join(format!("{}.json", share.name)),
+            key: share.within.to_string_lossy().to_string(),
+        })
+    }
+
+    /// [Props] of `relative` (a path under the symlinks directory).
+    pub async fn load(&self, relative: &Path) -> io::Result<Props> {
+        let entry = self.sidecar_entry(relative).await?;
+        let (path, xattrs) = (self.symlinks_dir.join(relative), self.xattrs);
+        task::spawn_blocking(move || {
+            if xattrs {
+                match load_xattr(&path)? {
+                    Some(props) if !props.is_empty() => return Ok(props),
+                    _ => {}
+                }
+            }
+            let _reading = SIDECAR.lock().unwrap_or_else(|e| e.into_inner());
+            Ok(read_sidecar(&entry.file)?
+                .remove(&entry.key)
+                .unwrap_or_default())
+        })
+        .await?
+    }
+
+    /// Replace [Props] of `relative` (a path under the symlinks directory) with `props`.
+    pub async fn save(&self, relative: &Path, props: Props) -> io::Result<()> {
+        let entry = self.sidecar_entry(relative).await?;
+        let (path, xattrs) = (self.symlinks_dir.join(relative), self.xattrs);
+        task::spawn_blocking(move || {
+            let in_xattr = xattrs && save_xattr(&path, &props)?;
+            change_sidecar(&entry.file, |sidecar| match in_xattr || props.is_empty() {
+                true => sidecar.remove(&entry.key).is_some(),
+                false => {
+                    sidecar.insert(entry.key, props);
+                    true
+                }
+            })
+        })
+        .await?
+    }
+
+    /// Move or copy sidecar entries of `from` and under it to `to`, replacing those of `to`.
+    /// ([XATTR] goes along with files on its own.)
+    async fn transfer_sidecar(&self, from: &Path, to: &Path, keep: bool) -> io::Result<()> {
+        let (from, to) = (
+            self.sidecar_entry(from).await?,
+            self.sidecar_entry(to).await?,
+        );
+        task::spawn_blocking(move || {
+            let _changing = SIDECAR.lock().unwrap_or_else(|e| e.into_inner());
+            let mut source = read_sidecar(&from.file)?;
+            let taken: Vec<_> = source
+                .iter()
+                .filter(|(key, _)| is_within(key, &from.key))
+                .map(|(key, props)| (key.clone(), props.clone()))
+                .collect();
+            let mut target = match from.file == to.file {
+                true => source.clone(),
+                false => read_sidecar(&to.file)?,
+            };
+            if taken.is_empty() && !target.keys().any(|key| is_within(key, &to.key)) {
+                return Ok(());
+            }
+            target.retain(|key, _| !is_within(key, &to.key));
+            if !keep {
+                source.retain(|key, _| !is_within(key, &from.key));
+                if from.file == to.file {
+                    target.retain(|key, _| !is_within(key, &from.key));
+                }
+            }
+            for (key, props) in taken {
+                let rest = key[from.key.len()..].trim_start_matches('/');
+                let key = match (to.key.is_empty(), rest.is_empty()) {
+                    (_, true) => to.key.clone(),
+                    (true, false) => rest.to_owned(),
+                    (false, false) => format!("{}/{rest}", to.key),
+                };
+                target.insert(key, props);
+            }
+            if from.file != to.file {
+                write_sidecar(&from.file, &source)?;
+            }
+            write_sidecar(&to.file, &target)
+        })
+        .await?
+    }
+
+    /// After `from` was moved to `to`.
+    pub async fn moved(&self, from: &Path, to: &Path) -> io::Result<()> {
+        self.transfer_sidecar(from, to, false).await
+    }
+
+    /// After file `from` was copied to `to`.
+    pub async fn copied(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let props = self.load(from).await?;
+        self.save(to, props).await
+    }
+
+    /// After `relative` was removed (with everything under it).
+    pub async fn removed(&self, relative: &Path) -> io::Result<()> {
+        let entry = self.sidecar_entry(relative).await?;
+        task::spawn_blocking(move || {
+            change_sidecar(&entry.file, |sidecar| {
+                let before = sidecar.len();
+                sidecar.retain(|key, _| !is_within(key, &entry.key));
+                sidecar.len() != before
+            })
+        })
+        .await?
+    }
+
+    /// After directory `from` was copied to `to` (with everything under it): Copy [Props] of the
+    /// directories. (Those of files were copied with them.)
+    pub async fn copied_collections(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let root = self.symlinks_dir.join(from);
+        let directories = task::spawn_blocking(move || {
+            let mut directories = vec![PathBuf::new()];
+            let mut index = 0;
+            while let Some(directory) = directories.get(index).cloned() {
+                for child in fs::read_dir(root.join(&directory))? {
+                    let child = child?;
+                    if fs::symlink_metadata(child.path())?.is_dir() {
+                        directories.push(directory.join(child.file_name()));
+                    }
+                }
+                index += 1;
+            }
+            Ok::<_, io::Error>(directories)
+        })
+        .await??;
+        for directory in directories {
+            let (from, to) = (from.join(&directory), to.join(&directory));
+            if tokio::fs::metadata(self.symlinks_dir.join(&to))
+                .await
+                .is_ok_and(|m| m.is_dir())
+            {
+                self.copied(&from, &to).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `inner` (in the symlinks directory of `store`) with dead properties in `store`.
+#[derive(Clone)]
+pub struct PropsFs {
+    inner: Box<dyn DavFileSystem>,
+    store: PropStore,
+}
+
+impl PropsFs {
+    pub fn new(inner: Box<dyn DavFileSystem>, store: PropStore) -> Box<Self> {
+        Box::new(Self { inner, store })
+    }
+}
+
+/// Log a failure to keep dead properties in step with what already happened to files.
+pub(crate) fn log_failure(what: &str, path: impl fmt::Display, result: io::Result<()>) {
+    if let Err(e) = result {
+        log::error!("Dead properties of {path} not {what}: {e}.");
+    }
+}
+
+impl DavFileSystem for PropsFs {
+    fn open<'a>(
+        &'a self,
+        path: &'a DavPath,
+        options: OpenOptions,
+    ) -> FsFuture<'a, Box<dyn DavFile>> {
+        self.inner.open(path, options)
+    }
+    fn read_dir<'a>(
+        &'a self,
+        path: &'a DavPath,
+        meta: ReadDirMeta,
+    ) -> FsFuture<'a, FsStream<Box<dyn DavDirEntry>>> {
+        self.inner.read_dir(path, meta)
+    }
+    fn metadata<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, Box<dyn DavMetaData>> {
+        self.inner.metadata(path)
+    }
+    fn symlink_metadata<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, Box<dyn DavMetaData>> {
+        self.inner.symlink_metadata(path)
+    }
+    fn create_dir<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, ()> {
+        self.inner.create_dir(path)
+    }
+    fn remove_dir<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, ()> {
+        async move {
+            self.inner.remove_dir(path).await?;
+            log_failure(
+                "removed",
+                path,
+                self.store.removed(path.as_rel_ospath()).await,
+            );
+            Ok(())
+        }
+        .boxed()
+    }
+    fn remove_file<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, ()> {
+        async move {
+            self.inner.remove_file(path).await?;
+            log_failure(
+                "removed",
+                path,
+                self.store.removed(path.as_rel_ospath()).await,
+            );
+            Ok(())
+        }
+        .boxed()
+    }
+    fn rename<'a>(&'a self, from: &'a DavPath, to: &'a DavPath) -> FsFuture<'a, ()> {
+        async move {
+            self.inner.rename(from, to).await?;
+            let moved = self
+                .store
+                .moved(from.as_rel_ospath(), to.as_rel_ospath())
+                .await;
+            log_failure("moved", from, moved);
+            Ok(())
+        }
+        .boxed()
+    }
+    fn copy<'a>(&'a self, from: &'a DavPath, to: &'a DavPath) -> FsFuture<'a, ()> {
+        async move {
+            self.inner.copy(from, to).await?;
+            let copied = self
+                .store
+                .copied(from.as_rel_ospath(), to.as_rel_ospath())
+                .await;
+            log_failure("copied", from, copied);
+            Ok(())
+        }
+        .boxed()
+    }
+    fn set_accessed<'a>(&'a self, path: &'a DavPath, time: SystemTime) -> FsFuture<'a, ()> {
+        self.inner.set_accessed(path, time)
+    }
+    fn set_modified<'a>(&'a self, path: &'a DavPath, time: SystemTime) -> FsFuture<'a, ()> {
+        self.inner.set_modified(path, time)
+    }
+    /// Anything in a share has them (but not the symlinks directory itself).
+    fn have_props<'a>(
+        &'a self,
+        path: &'a DavPath,
+    ) -> Pin<Box<dyn Future<Output = bool> + Send + 'a>> {
+        let in_share = !path.as_rel_ospath().as_os_str().is_empty();
+        Box::pin(future::ready(in_share))
+    }
+    fn patch_props<'a>(
+        &'a self,
+        path: &'a DavPath,
+        patch: Vec<(bool, DavProp)>,
+    ) -> FsFuture<'a, Vec<(StatusCode, DavProp)>> {
+        async move {
+            let relative = path.as_rel_ospath();
+            let mut props = self.store.load(relative).await?;
+            let mut statuses = Vec::new();
+            for (set, prop) in patch {
+                let key = key(&prop.namespace, &prop.name);
+                let stored = StoredProp::from(prop);
+                statuses.push((StatusCode::OK, stored.to_dav(false)));
+                if set {
+                    props.insert(key, stored);
+                } else {
+                    // Like dav-server's memfs: Removing a missing property succeeds.
+                    props.remove(&key);
+                }
+            }
+            self.store.save(relative, props).await?;
+            Ok(statuses)
+        }
+        .boxed()
+    }
+    fn get_props<'a>(&'a self, path: &'a DavPath, do_content: bool) -> FsFuture<'a, Vec<DavProp>> {
+        async move {
+            let props = self.store.load(path.as_rel_ospath()).await?;
+            Ok(props.values().map(|prop| prop.to_dav(do_content)).collect())
+        }
+        .boxed()
+    }
+    fn get_prop<'a>(&'a self, path: &'a DavPath, prop: DavProp) -> FsFuture<'a, Vec<u8>> {
+        async move {
+            let props = self.store.load(path.as_rel_ospath()).await?;
+            props
+                .get(&key(&prop.namespace, &prop.name))
+                .and_then(|stored| stored.to_dav(true).xml)
+                .ok_or(FsError::NotFound)
+        }
+        .boxed()
+    }
+    fn get_quota(&self) -> FsFuture<'_, (u64, Option<u64>)> {
+        self.inner.get_quota()
+    }
+}
+
+/// `filter` (a DAV handler of [PropsFs] with `store`, after the URL prefix segment
+/// `prefix_segment`), but also copying dead properties of directories with successful COPY.
+/// (dav-server copies directories by creating them anew, without telling [PropsFs] where from.)
+pub fn with_collection_props<F, R>(
+    prefix_segment: &'static str,
+    store: PropStore,
+    filter: F,
+) -> impl Filter<Extract = (reply::Response,), Error = Rejection> + Clone
+where
+    F: Filter<Extract = (R,), Error = Rejection> + Clone + Send + Sync + 'static,
+    R: Reply,
+{
+    warp::method()
+        .and(warp::path::peek())
+        .and(warp::header::optional::<String>("destination"))
+        .and(filter.map(Reply::into_response))
+        .then(
+            move |method: Method,
+                  tail: Peek,
+                  destination: Option<String>,
+                  response: reply::Response| {
+                let store = store.clone();
+                async move {
+                    if method.as_str() != "COPY" || !response.status().is_success() {
+                        return response;
+                    }
+                    let from = decode_tail(&tail).ok();
+                    let to = destination.and_then(|d| destination_relative(&d, prefix_segment));
+                    if let (Some(from), Some(to)) = (from, to) {
+                        let path = store.symlinks_dir.join(&from);
+                        if tokio::fs::metadata(path).await.is_ok_and(|m| m.is_dir()) {
+                            if let Err(e) = store.copied_collections(&from, &to).await {
+                                log::error!(
+                                    "Dead properties of directories in {} not copied: {e}.",
+                                    from.display()
+                                );
+                            }
+                        }
+                    }
+                    response
+                }
+            },
+        )
+}
@@ -0,0 +1,344 @@
+//! Per-share quota ([ShareMeta::quota_bytes](crate::meta::ShareMeta::quota_bytes)): The total size
+//! of a share's files. It's measured (by walking the share) whenever something is about to be
+//! added, so it also counts what got there by other means. (Of WebDAV, that's PUT, COPY, and MOVE
+//! from another share: See [crate::server::method_policy].)
+//!
+//! PROPFIND reports it (or else the free space of the filesystem) to clients, as RFC 4331
+//! `quota-used-bytes` and `quota-available-bytes` of collections. See [dav_handler].
+
+use crate::error::Error;
+use crate::meta::ShareMeta;
+use crate::server::{decode_tail, share_of};
+use core::time::Duration;
+use dav_server::davpath::DavPath;
+use dav_server::fs::{
+    DavDirEntry, DavFile, DavFileSystem, DavMetaData, DavProp, FsFuture, FsResult, FsStream,
+    OpenOptions, ReadDirMeta,
+};
+use dav_server::DavHandler;
+use futures_util::{FutureExt, StreamExt};
+use http::header::HeaderMap;
+use http::{Method, Response};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime};
+use tokio::task;
+use warp::hyper::Body;
+use warp::path::{FullPath, Peek};
+use warp::{reject::Rejection, reply, Filter};
+
+/// How long [space] keeps what it measured of a share.
+pub const SPACE_CACHE: Duration = Duration::from_secs(30);
+
+/// Total size of the files under `dir`. Symlinks are not followed (nested shares are counted in the
+/// share that they are in). Blocking.
+pub fn usage(dir: &Path) -> io::Result<u64> {
+    let mut total = 0;
+    for child in fs::read_dir(dir)? {
+        let child = child?;
+        let metadata = fs::symlink_metadata(child.path())?;
+        if metadata.is_dir() {
+            total += usage(&child.path())?;
+        } else if metadata.is_file() {
+            total += metadata.len();
+        }
+    }
+    Ok(total)
+}
+
+/// How many bytes can be added to share directory `share_dir` under `quota` (if any), when file
+/// `replaced` (if it exists) goes away at the same time. [None] without a quota.
+pub async fn remaining(
+    share_dir: PathBuf,
+    quota: Option<u64>,
+    replaced: Option<PathBuf>,
+) -> Result<Option<u64>, Error> {
+    let Some(quota) = quota else {
+        return Ok(None);
+    };
+    let used = task::spawn_blocking(move || {
+        let replaced = replaced
+            .and_then(|path| fs::symlink_metadata(path).ok())
+            .filter(|metadata| metadata.is_file())
+            .map_or(0, |metadata| metadata.len());
+        usage(&share_dir).map(|used| used.saturating_sub(replaced))
+    })
+    .await??;
+    Ok(Some(quota.saturating_sub(used)))
+}
+
+/// Whether `length` more bytes fit in what [remaining] returned.
+pub fn check(remaining: Option<u64>, length: u64) -> Result<(), Error> {
+    match remaining {
+        Some(remaining) if length > remaining => Err(Error::QuotaExceeded),
+        _ => Ok(()),
+    }
+}
+
+/// Storage of a share, as PROPFIND reports it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Space {
+    /// Total size of the share's files (see [usage]).
+    pub used: u64,
+    /// What's left of the share's quota, but no more than the filesystem has free.
+    pub available: u64,
+}
+
+/// What [space] measured of a share.
+#[derive(Clone, Copy)]
+struct Measured {
+    at: Instant,
+    /// [usage].
+    used: u64,
+    /// [filesystem_available].
+    free: u64,
+}
+
+/// [Measured] shares, by their names.
+static MEASURED: Mutex<BTreeMap<String, Measured>> = Mutex::new(BTreeMap::new());
+
+/// Free space of the filesystem that `dir` is on, for unprivileged users. Blocking.
+pub fn filesystem_available(dir: &Path) -> io::Result<u64> {
+    let stat = rustix::fs::statvfs(dir)?;
+    Ok(stat.f_bavail.saturating_mul(stat.f_frsize))
+}
+
+/// [Space] of share `name` (in `share_dir`) under `quota` (if any). The share is measured at most
+/// once per [SPACE_CACHE].
+pub async fn space(name: &str, share_dir: PathBuf, quota: Option<u64>) -> Result<Space, Error> {
+    let now = Instant::now();
+    let fresh = |measured: &Measured| now.duration_since(measured.at) < SPACE_CACHE;
+    let cached = MEASURED
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(name)
+        .copied()
+        .filter(fresh);
+    let Measured { used, free, .. } = match cached {
+        Some(measured) => measured,
+        None => {
+            let measured = task::spawn_blocking(move || {
+                Ok::<_, io::Error>(Measured {
+                    at: now,
+                    used: usage(&share_dir)?,
+                    free: filesystem_available(&share_dir)?,
+                })
+            })
+            .await??;
+            let mut all = MEASURED.lock().unwrap_or_else(|e| e.into_inner());
+            all.retain(|_, measured| fresh(measured));
+            all.insert(name.to_owned(), measured);
+            measured
+        }
+    };
+    let available = quota.map_or(free, |quota| quota.saturating_sub(used).min(free));
+    Ok(Space { used, available })
+}
+
+/// Metadata of a directory with [Space::used] as its length. That's what dav-server reports as
+/// `quota-used-bytes` (of anything but its root), and it doesn't use the length of directories
+/// otherwise.
+#[derive(Clone, Debug)]
+struct SpaceMetaData {
+    inner: Box<dyn DavMetaData>,
+    used: u64,
+}
+
+impl DavMetaData for SpaceMetaData {
+    fn len(&self) -> u64 {
+        self.used
+    }
+    fn modified(&self) -> FsResult<SystemTime> {
+        self.inner.modified()
+    }
+    fn is_dir(&self) -> bool {
+        true
+    }
+    fn etag(&self) -> Option<String> {
+        self.inner.etag()
+    }
+    fn is_symlink(&self) -> bool {
+        self.inner.is_symlink()
+    }
+    fn accessed(&self) -> FsResult<SystemTime> {
+        self.inner.accessed()
+    }
+    fn created(&self) -> FsResult<SystemTime> {
+        self.inner.created()
+    }
+    fn status_changed(&self) -> FsResult<SystemTime> {
+        self.inner.status_changed()
+    }
+    fn executable(&self) -> FsResult<bool> {
+        self.inner.executable()
+    }
+}
+
+fn with_space(metadata: Box<dyn DavMetaData>, used: u64) -> Box<dyn DavMetaData> {
+    if metadata.is_dir() {
+        Box::new(SpaceMetaData {
+            inner: metadata,
+            used,
+        })
+    } else {
+        metadata
+    }
+}
+
+struct SpaceDirEntry {
+    inner: Box<dyn DavDirEntry>,
+    used: u64,
+}
+
+impl DavDirEntry for SpaceDirEntry {
+    fn name(&self) -> Vec<u8> {
+        self.inner.name()
+    }
+    fn metadata(&self) -> FsFuture<'_, Box<dyn DavMetaData>> {
+        let used = self.used;
+        self.inner
+            .metadata()
+            .map(move |metadata| Ok(with_space(metadata?, used)))
+            .boxed()
+    }
+    fn is_dir(&self) -> FsFuture<'_, bool> {
+        self.inner.is_dir()
+    }
+    fn is_file(&self) -> FsFuture<'_, bool> {
+        self.inner.is_file()
+    }
+    fn is_symlink(&self) -> FsFuture<'_, bool> {
+        self.inner.is_symlink()
+    }
+}
+
+/// `inner`, with the quota of one share (that all requested paths are in).
+#[derive(Clone)]
+struct SpaceFs {
+    inner: Box<dyn DavFileSystem>,
+    space: Space,
+}
+
+impl DavFileSystem for SpaceFs {
+    fn open<'a>(
+        &'a self,
+        path: &'a DavPath,
+        options: OpenOptions,
+    ) -> FsFuture<'a, Box<dyn DavFile>> {
+        self.inner.open(path, options)
+    }
+    fn read_dir<'a>(
+        &'a self,
+        path: &'a DavPath,
+        meta: ReadDirMeta,
+    ) -> FsFuture<'a, FsStream<Box<dyn DavDirEntry>>> {
+        let used = self.space.used;
+        self.inner
+            .read_dir(path, meta)
+            .map(move |entries| {
+                let entries = entries?.map(move |inner| {
+                    Box::new(SpaceDirEntry { inner, used }) as Box<dyn DavDirEntry>
+                });
+                Ok(Box::pin(entries) as FsStream<_>)
+            })
+            .boxed()
+    }
+    fn metadata<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, Box<dyn DavMetaData>> {
+        let used = self.space.used;
+        self.inner
+            .metadata(path)
+            .map(move |metadata| Ok(with_space(metadata?, used)))
+            .boxed()
+    }
+    fn symlink_metadata<'a>(&'a self, path: &'a DavPath) -> FsFuture<'a, Box<dyn DavMetaData>> {
+        let used = self.space.used;
+        self.inner
+            .symlink_metadata(path)
+            .map(move |metadata| Ok(with_space(metadata?, used)))
+            .boxed()
+    }
+    fn have_props<'a>(
+        &'a self,
+        path: &'a DavPath,
+    ) -> std::pin::Pin<Box<dyn core::future::Future<Output = bool> + Send + 'a>> {
+        self.inner.have_props(path)
+    }
+    fn get_props<'a>(&'a self, path: &'a DavPath, do_content: bool) -> FsFuture<'a, Vec<DavProp>> {
+        self.inner.get_props(path, do_content)
+    }
+    fn get_prop<'a>(&'a self, path: &'a DavPath, prop: DavProp) -> FsFuture<'a, Vec<u8>> {
+        self.inner.get_prop(path, prop)
+    }
+    /// dav-server reports the available bytes as this total less the used ones.
+    fn get_quota(&self) -> FsFuture<'_, (u64, Option<u64>)> {
+        let Space { used, available } = self.space;
+        async move { Ok((used, Some(used.saturating_add(available)))) }.boxed()
+    }
+}
+
+/// Run `handler` (of `filesystem` in `symlinks_dir`, with a strip prefix) like
+/// [dav_server::warp::dav_handler] does, but with PROPFIND in a share reporting the share's
+/// [space]. Put it after the URL prefix segment.
+///
+/// (On files, dav-server reports their own size as used instead.)
+pub fn dav_handler(
+    handler: DavHandler,
+    filesystem: Box<dyn DavFileSystem>,
+    symlinks_dir: impl Into<PathBuf>,
+) -> impl Filter<Extract = (reply::Response,), Error = Rejection> + Clone {
+    let symlinks_dir = symlinks_dir.into();
+    warp::method()
+        .and(warp::path::full())
+        .and(warp::path::peek())
+        .and(warp::header::headers_cloned())
+        .and(warp::body::stream())
+        .then(
+            move |method: Method, full: FullPath, tail: Peek, headers: HeaderMap, body| {
+                let (handler, filesystem) = (handler.clone(), filesystem.clone());
+                let symlinks_dir = symlinks_dir.clone();
+                async move {
+                    let propfind = method.as_str() == "PROPFIND";
+                    let mut request = http::Request::builder().method(method).uri(full.as_str());
+                    for (name, value) in headers.iter() {
+                        request = request.header(name, value);
+                    }
+                    let request = request.body(body).expect("request from a valid one");
+
+                    let space = match propfind {
+                        true => share_space(&symlinks_dir, &tail).await,
+                        false => None,
+                    };
+                    let response = match space {
+                        Some(space) => {
+                            let fs = SpaceFs {
+                                inner: filesystem,
+                                space,
+                            };
+                            let config = DavHandler::builder().filesystem(Box::new(fs));
+                            handler.handle_stream_with(config, request).await
+                        }
+                        None => handler.handle_stream(request).await,
+                    };
+                    let (parts, body) = response.into_parts();
+                    Response::from_parts(parts, Body::wrap_stream(body))
+                }
+            },
+        )
+}
+
+/// [Space] of the share (a directory) that `tail` is in, if any.
+async fn share_space(symlinks_dir: &Path, tail: &Peek) -> Option<Space> {
+    let relative = decode_tail(tail).ok()?;
+    let share = share_of(symlinks_dir, &relative).await?;
+    if !tokio::fs::metadata(&share.dir)
+        .await
+        .is_ok_and(|m| m.is_dir())
+    {
+        return None;
+    }
+    let meta = ShareMeta::load(&share.name).await.ok()?;
+    space(&share.name, share.dir, meta.quota_bytes).await.ok()
+}
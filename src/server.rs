@@ -1,23 +1,57 @@
+use crate::archive::archive;
+use crate::autoindex::{autoindex, INDEX_FILE};
+use crate::chunking::chunked_upload;
+use crate::cleanup;
 use crate::entry;
-use crate::fs::{FileSystem, UnmockFileSystem};
-use crate::{ADMIN, READ, SYMLINKS, SYMLINKS_READ, SYMLINKS_WRITE, WRITE};
+use crate::entry::EntriesMap;
+use crate::error::{self, Error};
+use crate::extract::{extract_upload, ExtractLimits};
+use crate::fs::RealFileSystem;
+use crate::index::{ShareIndex, Watcher};
+use crate::isolation::{
+    hardened, not_on_content_origin, to_content_origin, Isolation, SANDBOX, SITE_SANDBOX,
+};
+use crate::links;
+use crate::logs::{access_log, audited, AccessRecord, AuditRecord, Logs};
+use crate::manager::file_manager;
+use crate::meta::{MethodPolicy, Retention, ShareMeta, SiteConfig};
+use crate::props::{with_collection_props, PropStore, PropsFs};
+use crate::quota;
+use crate::share::{self, FileMode, ShareMode};
+use crate::site::{self, static_site};
+use crate::trash::{ShareTrash, TrashItem};
+use crate::tus::tus;
+use crate::versions::{FileVersions, ShareVersions, Version};
+use crate::{
+    ADD, ADMIN, APPEND, CHUNKING, DROP, FILE, FILE_LINK, FREEZE, LOGS, LOGS_SEGMENT, META, NESTED,
+    POLICY, PROPS, PURGE, READ, READ_DAV, RESTORE, RETENTION, SITE, SYMLINKS, SYMLINKS_APPEND,
+    SYMLINKS_DROP, SYMLINKS_FILE, SYMLINKS_READ, SYMLINKS_WRITE, TRASH, TRASH_SEGMENT, TUS,
+    VERSIONS_SEGMENT, WRITE,
+};
 use askama::Template;
+use core::convert::Infallible;
 use dav_server::{self, fakels::FakeLs, localfs::LocalFs, DavMethod};
 pub use entry::Entry;
+use futures_util::{Stream, StreamExt};
 use http::{uri::Uri, StatusCode};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
 use std::collections::HashMap;
-use std::fmt::Debug;
 use std::fs::{self};
 use std::io;
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
+use tokio::fs as tokio_fs;
+use tokio::io::AsyncWriteExt;
+use tokio::task;
 use warp::http::{self};
-use warp::{redirect, reject::Reject, reject::Rejection, reply, Filter};
+use warp::path::{FullPath, Peek};
+use warp::{redirect, reject::Rejection, reply, Buf, Filter};
 
 use crate::DEFAULT_PORT;
 use crate::DIRS;
 use crate::ENV_DATA_KEY;
 use crate::ENV_PORT;
 use crate::ENV_SALT;
+use dav_server::fs::DavFileSystem;
 use dav_server::DavConfig;
 use dav_server::DavHandler;
 use dav_server::DavMethodSet;
@@ -26,9 +60,14 @@ use std::net::IpAddr;
 use std::net::SocketAddr;
 use warp;
 use warp::http::HeaderValue;
-use warp::reject;
 use warp::Reply;
 
+/// [LocalFs] of `dir_path`, with dead properties (see [crate::props]).
+fn dav_fs(dir_path: impl AsRef<Path>) -> Box<dyn DavFileSystem> {
+    let store = PropStore::new(dir_path.as_ref(), PROPS, true);
+    PropsFs::new(LocalFs::new(dir_path, false, false, false), store)
+}
+
 fn dav_config(
     prefix_segment: impl core::fmt::Display,
     dir_path: impl AsRef<Path>,
@@ -45,27 +84,524 @@ fn dav_config(
     // That is excellent for our need-to-know-based ACL.
     //
     // In GNOME open the WebDAV directory with: nautilus dav://127.0.0.1:4201/subdir-here
+    //
+    // Nested publications (see Entry::Nested) are symlinks directly under `dir_path`, too (just to
+    // deeper targets), so the same strip prefix serves them: `/{prefix_segment}/{name}/...`.
     DavHandler::builder()
-        .filesystem(LocalFs::new(dir_path, false, false, false))
+        .filesystem(dav_fs(dir_path))
         //--- @TODO SYMLINKS_READ to a param
         .locksystem(FakeLs::new())
-        .autoindex(true) //@TODO
-        .indexfile("index.html")
+        // With an index file set, the handler never lists a directory (see crate::autoindex).
+        .indexfile(INDEX_FILE)
         .methods(methods)
         //.strip_prefix("/".to_owned() + prefix_segment)
         .strip_prefix(format!("/{}", prefix_segment))
 }
 
-#[derive(Debug)]
-pub struct Rej<T>(T)
+/// Read-only WebDAV of [SYMLINKS_READ] under `prefix_segment`, with directory listing (see
+/// [autoindex]). Put it after the URL prefix segment (and after [method_policy]).
+fn dav_read(
+    prefix_segment: &'static str,
+    isolation: Isolation,
+) -> impl Filter<Extract = (reply::Response,), Error = Rejection> + Clone {
+    // DavMethodSet::add(&mut self, DavMethod) is ugly. And there is no direct method to
+    // add/merge/union two instances of DavMethodSet. But, for now, the following:
+    let mut read_only = DavMethodSet::HTTP_RO;
+    read_only.add(DavMethod::PropFind);
+
+    let dav_handler = dav_config(prefix_segment, SYMLINKS_READ, read_only).build_handler();
+    autoindex(SYMLINKS_READ)
+        .or(hardened(
+            isolation,
+            SANDBOX,
+            single_file(SYMLINKS_READ)
+                .and(quota::dav_handler(
+                    dav_handler,
+                    dav_fs(SYMLINKS_READ),
+                    SYMLINKS_READ,
+                ))
+                .map(with_disposition),
+        ))
+        .unify()
+}
+
+/// Append-only WebDAV of shares under `symlinks_dir` (by default [SYMLINKS_APPEND]), under
+/// `prefix_segment`. Put it after the URL prefix segment. Like read & write, but nothing that could
+/// remove or replace existing content (see [no_overwrite]).
+pub fn dav_append(
+    prefix_segment: &'static str,
+    symlinks_dir: impl Into<PathBuf>,
+    isolation: Isolation,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let symlinks_dir = symlinks_dir.into();
+    // COPY could overwrite its destination, and PROPPATCH could alter existing (dead) properties.
+    let mut append_only = DavMethodSet::HTTP_RO;
+    append_only.add(DavMethod::PropFind);
+    append_only.add(DavMethod::Put);
+    append_only.add(DavMethod::MkCol);
+    append_only.add(DavMethod::Lock);
+    append_only.add(DavMethod::Unlock);
+
+    let dav_handler = dav_config(prefix_segment, &symlinks_dir, append_only).build_handler();
+    method_policy(symlinks_dir.clone(), META).and(hardened(
+        isolation,
+        SANDBOX,
+        no_overwrite(symlinks_dir).and(dav_server::warp::dav_handler(dav_handler)),
+    ))
+}
+
+/// Write-only WebDAV of drop boxes under `symlinks_dir` (by default [SYMLINKS_DROP]), under
+/// `prefix_segment`. Put it after the URL prefix segment. Uploaders can't list, read nor delete
+/// (their own, or anyone else's) files, so they can't overwrite them either (see [no_overwrite]).
+pub fn dav_drop(
+    prefix_segment: &'static str,
+    symlinks_dir: impl Into<PathBuf>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let symlinks_dir = symlinks_dir.into();
+    // Lock & Unlock are there for clients that lock before PUT.
+    let mut drop_only = DavMethodSet::none();
+    drop_only.add(DavMethod::Options);
+    drop_only.add(DavMethod::Put);
+    drop_only.add(DavMethod::MkCol);
+    drop_only.add(DavMethod::Lock);
+    drop_only.add(DavMethod::Unlock);
+
+    let dav_handler = dav_config(prefix_segment, &symlinks_dir, drop_only).build_handler();
+    method_policy(symlinks_dir.clone(), META)
+        .and(no_overwrite(symlinks_dir))
+        .and(dav_server::warp::dav_handler(dav_handler))
+}
+
+/// The request's `Accept` header, if any (and if valid).
+pub(crate) fn accept_header() -> impl Filter<Extract = (Option<String>,), Error = Infallible> + Clone
+{
+    warp::header::optional::<String>(http::header::ACCEPT.as_str())
+        .or(warp::any().map(|| None))
+        .unify()
+}
+
+/// Reply to any rejection of `filter` with [error::reply], based on the request's `Accept` header.
+/// The result never rejects (even though its type says so, as [Filter::or_else] requires).
+pub fn recover_by_accept<F, R>(
+    filter: F,
+) -> impl Filter<Extract = (Box<dyn Reply>,), Error = Rejection> + Clone
 where
-    T: Debug + Sized + Send + Sync;
+    F: Filter<Extract = (R,), Error = Rejection> + Clone + Send + Sync + 'static,
+    R: Reply + 'static,
+{
+    let result = filter
+        .map(|reply: R| Ok(Box::new(reply) as Box<dyn Reply>))
+        .or_else(|rejection| async move { Ok::<_, Rejection>((Err(rejection),)) });
+
+    accept_header().and(result).map(
+        |accept: Option<String>, result: Result<Box<dyn Reply>, Rejection>| match result {
+            Ok(reply) => reply,
+            Err(rejection) => error::reply(&rejection, accept.as_deref()),
+        },
+    )
+}
+
+/// Require `name` to be usable as a single directory name (a leaf) under [DIRS].
+pub fn check_name(name: &str) -> Result<(), Error> {
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\', '\0']) {
+        Err(Error::InvalidName(name.to_owned()))
+    } else {
+        Ok(())
+    }
+}
+
+/// Percent-decode `tail`, and require it to be relative and without any `.` or `..`.
+///
+/// Filters in front of a DAV handler get the tail through [warp::path::peek], not
+/// [warp::path::tail], because the latter consumes it: Any further filter would get an empty one.
+pub(crate) fn decode_tail(tail: &Peek) -> Result<PathBuf, Error> {
+    let relative = percent_decode_str(tail.as_str())
+        .decode_utf8()
+        .map_err(|_| Error::BadRequest("path is not UTF-8".to_owned()))?;
+    let relative = PathBuf::from(relative.as_ref());
+    if relative
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)))
+    {
+        Ok(relative)
+    } else {
+        Err(Error::BadRequest("path".to_owned()))
+    }
+}
+
+/// Share that a request is for. See [share_of].
+pub(crate) struct ShareTarget {
+    pub(crate) name: String,
+    /// The share's (top level) directory.
+    pub(crate) dir: PathBuf,
+    /// Relative to [ShareTarget::dir]. For a nested publication (see [Entry::Nested]) it starts
+    /// with the published directory.
+    pub(crate) within: PathBuf,
+}
+
+/// The share that `relative` (a decoded path after the URL prefix segment) is in. The share is the
+/// target of the symlink under `symlinks_dir`, named by the first segment of `relative` - or, if
+/// the target is nested inside a share under [DIRS], that share. [None] if there is no such
+/// symlink.
+pub(crate) async fn share_of(symlinks_dir: &Path, relative: &Path) -> Option<ShareTarget> {
+    let mut segments = relative.iter();
+    let link = segments.next()?;
+    let target = tokio_fs::read_link(symlinks_dir.join(link)).await.ok()?;
+    let (dir, nested) = match target.strip_prefix(DIRS) {
+        Ok(under_dirs) => {
+            let mut components = under_dirs.iter();
+            let share = components.next()?;
+            (Path::new(DIRS).join(share), components.collect::<PathBuf>())
+        }
+        // Not under DIRS (like in tests): The target is the share.
+        Err(_) => (target, PathBuf::new()),
+    };
+    let name = dir.file_name()?.to_string_lossy().to_string();
+    Some(ShareTarget {
+        name,
+        dir,
+        within: nested.iter().chain(segments).collect(),
+    })
+}
+
+/// Name of the single-file share (see [Entry::PrimaryFile]) that the request is for, if it is for
+/// one: Its path after the URL prefix segment is just the name of a symlink under `symlinks_dir`
+/// that points to a regular file. Otherwise [None] (and the request passes as-is).
+pub fn single_file(
+    symlinks_dir: impl Into<PathBuf>,
+) -> impl Filter<Extract = (Option<String>,), Error = Infallible> + Clone {
+    let symlinks_dir = symlinks_dir.into();
+    warp::path::peek().then(move |tail: Peek| {
+        let symlinks_dir = symlinks_dir.clone();
+        async move {
+            let relative = decode_tail(&tail).ok()?;
+            if relative.iter().count() != 1 {
+                return None;
+            }
+            let link = symlinks_dir.join(&relative);
+            let target = tokio_fs::read_link(&link).await.ok()?;
+            let is_file = tokio_fs::metadata(&link).await.is_ok_and(|m| m.is_file());
+            is_file.then(|| {
+                target
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+            })?
+        }
+    })
+}
+
+/// Characters to percent-encode in `filename*`: All but RFC 5987 `attr-char`.
+const ATTR_CHAR_ENCODE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'!')
+    .remove(b'#')
+    .remove(b'$')
+    .remove(b'&')
+    .remove(b'+')
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'^')
+    .remove(b'_')
+    .remove(b'`')
+    .remove(b'|')
+    .remove(b'~');
+
+/// `Content-Disposition` that makes browsers download as `file_name`. (The plain `filename` is an
+/// ASCII fallback for old clients.)
+pub fn content_disposition(file_name: &str) -> String {
+    let fallback: String = file_name
+        .chars()
+        .map(|c| match c {
+            ' '..='~' if c != '"' && c != '\\' => c,
+            _ => '_',
+        })
+        .collect();
+    let encoded = utf8_percent_encode(file_name, ATTR_CHAR_ENCODE);
+    format!("attachment; filename=\"{fallback}\"; filename*=UTF-8''{encoded}")
+}
+
+/// Add [content_disposition] to a successful `reply` for single-file share `file_name` (if any).
+fn with_disposition(file_name: Option<String>, reply: impl Reply) -> reply::Response {
+    let mut response = reply.into_response();
+    if let Some(file_name) = file_name.filter(|_| response.status().is_success()) {
+        if let Ok(value) = HeaderValue::from_str(&content_disposition(&file_name)) {
+            response
+                .headers_mut()
+                .insert(http::header::CONTENT_DISPOSITION, value);
+        }
+    }
+    response
+}
+
+/// Enforce the [MethodPolicy] (and the [crate::quota], on PUT, COPY and MOVE) of the share that
+/// the request is for, with metadata in `meta_dir` (the server's is [META]). Put it in front of a
+/// DAV handler, after the URL prefix segment. The share is the target of the symlink (named by the
+/// first segment of the rest of the path) under `symlinks_dir`. If there is no such symlink, the
+/// request passes, and the DAV handler replies with 404.
+pub fn method_policy(
+    symlinks_dir: impl Into<PathBuf>,
+    meta_dir: impl Into<PathBuf>,
+) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    let symlinks_dir = symlinks_dir.into();
+    let meta_dir = meta_dir.into();
+    warp::method()
+        .and(warp::path::full())
+        .and(warp::path::peek())
+        .and(warp::header::optional::<u64>(
+            http::header::CONTENT_LENGTH.as_str(),
+        ))
+        .and(warp::header::optional::<String>("destination"))
+        .and(warp::header::optional::<String>("overwrite"))
+        .and_then(
+            move |method: http::Method,
+                  full: FullPath,
+                  tail: Peek,
+                  content_length: Option<u64>,
+                  destination: Option<String>,
+                  overwrite: Option<String>| {
+                let (symlinks_dir, meta_dir) = (symlinks_dir.clone(), meta_dir.clone());
+                async move {
+                    let relative = decode_tail(&tail)?;
+                    let Some(share) = share_of(&symlinks_dir, &relative).await else {
+                        return Ok::<_, Rejection>(());
+                    };
+                    let meta = ShareMeta::load_in(&meta_dir, &share.name).await?;
+                    meta.policy.check(&method, content_length)?;
+                    if method == http::Method::PUT && meta.quota_bytes.is_some() {
+                        let length = content_length.ok_or(Error::LengthRequired)?;
+                        let replaced = symlinks_dir.join(&relative);
+                        let remaining =
+                            quota::remaining(share.dir.clone(), meta.quota_bytes, Some(replaced))
+                                .await?;
+                        quota::check(remaining, length)?;
+                    }
+                    if let ("COPY" | "MOVE", Some(destination)) = (method.as_str(), destination) {
+                        let prefix_segment = full
+                            .as_str()
+                            .strip_suffix(tail.as_str())
+                            .unwrap_or_default()
+                            .trim_matches('/');
+                        let Some(to) = destination_relative(&destination, prefix_segment) else {
+                            return Ok(());
+                        };
+                        let source = Source {
+                            path: share.dir.join(&share.within),
+                            share: share.name,
+                            moved: method.as_str() == "MOVE",
+                        };
+                        let replaced =
+                            (overwrite.as_deref() != Some("F")).then(|| symlinks_dir.join(&to));
+                        check_added(&symlinks_dir, &meta_dir, &to, source, replaced).await?;
+                    }
+                    Ok(())
+                }
+            },
+        )
+        .untuple_one()
+}
+
+/// What COPY or MOVE adds to the share of its destination.
+struct Source {
+    path: PathBuf,
+    /// Name of the share that [Source::path] is in.
+    share: String,
+    moved: bool,
+}
+
+/// Check the [crate::quota] of the share of `to` (relative to `symlinks_dir`) for `source` copied
+/// (or moved from another share) there, replacing file `replaced` (if any).
+async fn check_added(
+    symlinks_dir: &Path,
+    meta_dir: &Path,
+    to: &Path,
+    source: Source,
+    replaced: Option<PathBuf>,
+) -> Result<(), Error> {
+    let Some(share) = share_of(symlinks_dir, to).await else {
+        return Ok(());
+    };
+    if source.moved && source.share == share.name {
+        return Ok(());
+    }
+    let quota_bytes = ShareMeta::load_in(meta_dir, &share.name).await?.quota_bytes;
+    if quota_bytes.is_none() {
+        return Ok(());
+    }
+    let length = task::spawn_blocking(move || {
+        // Like the quota counts it: Without following symlinks.
+        let metadata = fs::symlink_metadata(&source.path)?;
+        match metadata.is_dir() {
+            true => quota::usage(&source.path),
+            false => Ok(metadata.len()),
+        }
+    })
+    .await?;
+    // A missing source: The DAV handler replies with 404.
+    let Ok(length) = length else {
+        return Ok(());
+    };
+    let remaining = quota::remaining(share.dir, quota_bytes, replaced).await?;
+    quota::check(remaining, length)
+}
 
-unsafe impl<T> Send for Rej<T> where T: Debug + Sized + Send + Sync {}
+/// Decoded path of a `Destination` header (of COPY or MOVE) after `/{prefix_segment}/`. [None] if
+/// it's not under that prefix (then the DAV handler rejects it).
+pub(crate) fn destination_relative(destination: &str, prefix_segment: &str) -> Option<PathBuf> {
+    let uri = destination.parse::<Uri>().ok()?;
+    let tail = uri.path().strip_prefix(&format!("/{prefix_segment}/"))?;
+    let relative = percent_decode_str(tail).decode_utf8().ok()?;
+    let relative = PathBuf::from(relative.as_ref());
+    relative
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)))
+        .then_some(relative)
+}
+
+/// Keep previous versions (see [crate::versions]) of whatever a request is about to overwrite or
+/// delete: The target of PUT and DELETE, and the `Destination` of COPY and MOVE (unless
+/// `Overwrite: F`). Put it in front of the DAV handler of [crate::WRITE], after the URL prefix
+/// segment (and after [method_policy], so that denied requests don't create versions).
+pub fn keep_versions(
+    prefix_segment: &'static str,
+    symlinks_dir: impl Into<PathBuf>,
+) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    let symlinks_dir = symlinks_dir.into();
+    warp::method()
+        .and(warp::path::peek())
+        .and(warp::header::optional::<String>("destination"))
+        .and(warp::header::optional::<String>("overwrite"))
+        .and_then(
+            move |method: http::Method,
+                  tail: Peek,
+                  destination: Option<String>,
+                  overwrite: Option<String>| {
+                let symlinks_dir = symlinks_dir.clone();
+                async move {
+                    let relative = match method.as_str() {
+                        "PUT" | "DELETE" => Some(decode_tail(&tail)?),
+                        "COPY" | "MOVE" if overwrite.as_deref() != Some("F") => {
+                            destination.and_then(|d| destination_relative(&d, prefix_segment))
+                        }
+                        _ => None,
+                    };
+                    let Some(relative) = relative else {
+                        return Ok::<_, Rejection>(());
+                    };
+                    let Some(share) = share_of(&symlinks_dir, &relative).await else {
+                        return Ok(());
+                    };
+                    let meta = ShareMeta::load(&share.name).await?;
+                    task::spawn_blocking(move || {
+                        ShareVersions::of(&share.name)
+                            .with_cap(meta.quota_bytes)
+                            .snapshot(&share.dir, &share.within, meta.versions)
+                    })
+                    .await
+                    .map_err(Error::from)?
+                    .map_err(Error::from)?;
+                    Ok(())
+                }
+            },
+        )
+        .untuple_one()
+}
+
+/// Move the target of a DELETE to the share's [crate::trash]. [None] if the request should go on
+/// to the DAV handler: Not under a share, or its [ShareMeta::trash_days] is 0.
+async fn trash(symlinks_dir: &Path, tail: &Peek) -> Result<Option<StatusCode>, Error> {
+    let relative = decode_tail(tail)?;
+    let Some(share) = share_of(symlinks_dir, &relative).await else {
+        return Ok(None);
+    };
+    // Even without trash: The DAV handler would remove the share's symlink. (For a nested
+    // publication, the trash would take the published directory.)
+    if relative.iter().count() == 1 {
+        return Err(Error::Forbidden("deleting the whole share".to_owned()));
+    }
+    let meta = ShareMeta::load(&share.name).await?;
+    if meta.trash_days == 0 {
+        return Ok(None);
+    }
+    let ShareTarget { name, dir, within } = share;
+    let what = within.display().to_string();
+    let trash = ShareTrash::of(&name).with_cap(meta.quota_bytes);
+    task::spawn_blocking(move || trash.put(&dir, &within))
+        .await?
+        .map_err(|e| Error::from_io(e, what))?;
+    Ok(Some(StatusCode::NO_CONTENT))
+}
 
-unsafe impl<T> Sync for Rej<T> where T: Debug + Sized + Send + Sync {}
+/// Handle DELETE by moving the target to the share's [crate::trash] (see [trash]). Reject anything
+/// else (with [warp::reject::not_found]), so that the request can go on to the DAV handler through
+/// [Filter::or]. Errors are replies (based on the `Accept` header, like [recover_by_accept]),
+/// rather than rejections, because [Filter::or] would pass those on to the DAV handler, too. Put it
+/// in front of the DAV handler of [crate::WRITE], after the URL prefix segment (and after
+/// [method_policy]).
+pub fn trash_delete(
+    symlinks_dir: impl Into<PathBuf>,
+) -> impl Filter<Extract = (Box<dyn Reply>,), Error = Rejection> + Clone {
+    let symlinks_dir = symlinks_dir.into();
+    warp::method()
+        .and(warp::path::peek())
+        .and(accept_header())
+        .and_then(
+            move |method: http::Method, tail: Peek, accept: Option<String>| {
+                let symlinks_dir = symlinks_dir.clone();
+                async move {
+                    if method != http::Method::DELETE {
+                        return Err(warp::reject::not_found());
+                    }
+                    match trash(&symlinks_dir, &tail).await {
+                        Ok(Some(status)) => {
+                            // Extended attributes went to the trash, too.
+                            if let Ok(relative) = decode_tail(&tail) {
+                                let store = PropStore::new(&symlinks_dir, PROPS, true);
+                                if let Err(e) = store.removed(&relative).await {
+                                    let path = relative.display();
+                                    log::error!("Dead properties of {path} not removed: {e}.");
+                                }
+                            }
+                            Ok(Box::new(status) as Box<dyn Reply>)
+                        }
+                        Ok(None) => Err(warp::reject::not_found()),
+                        Err(e) => Ok(error::reply(&warp::reject::custom(e), accept.as_deref())),
+                    }
+                }
+            },
+        )
+}
 
-impl<T> Reject for Rej<T> where T: Debug + Sized + Send + Sync + 'static {}
+/// Reject a PUT of a path that already exists under `symlinks_dir` with
+/// [Error::PreconditionFailed]. Put it in front of a DAV handler of append-only shares, after the
+/// URL prefix segment.
+///
+/// This is a check before the DAV handler's PUT, not an atomic create: Two racing PUTs of the same
+/// new path can both pass, and the latter wins.
+pub fn no_overwrite(
+    symlinks_dir: impl Into<PathBuf>,
+) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    let symlinks_dir = symlinks_dir.into();
+    warp::method()
+        .and(warp::path::peek())
+        .and_then(move |method: http::Method, tail: Peek| {
+            let symlinks_dir = symlinks_dir.clone();
+            async move {
+                if method != http::Method::PUT {
+                    return Ok::<_, Rejection>(());
+                }
+                let relative = decode_tail(&tail)?;
+                // Don't follow the leaf, so that even a (dangling) symlink counts as existing.
+                match tokio_fs::symlink_metadata(symlinks_dir.join(&relative)).await {
+                    Ok(_) => Err(Error::PreconditionFailed(format!(
+                        "{} already exists, and this share doesn't allow overwriting",
+                        relative.iter().skip(1).collect::<PathBuf>().display()
+                    ))
+                    .into()),
+                    Err(_) => Ok(()),
+                }
+            }
+        })
+        .untuple_one()
+}
 
 pub fn redirect_see_other<L>(location: L) -> Result<impl Reply, Rejection>
 where
@@ -90,48 +626,522 @@ where
 /// Directory entries, mapped by their (potentially lossy) names.
 #[derive(Template)]
 #[template(path = "admin_list.html")]
-pub struct AdminListTemplate {
-    pub entries: HashMap<String, Entry>,
+pub struct AdminListTemplate<'a> {
+    pub entries: &'a EntriesMap,
+    /// Mapped by share names. Shares without metadata are missing.
+    pub metas: &'a HashMap<String, ShareMeta>,
 }
 
 // Thanks to https://blog.logrocket.com/template-rendering-in-rust
 pub type WebResult<T> = std::result::Result<T, Rejection>;
 
-pub async fn admin_list() -> WebResult<impl Reply> {
-    let fs: FileSystem = loop {};
-    let entries = fs.get_entries().map_err(|e| reject::custom(Rej(e)))?;
+/// Pass a clone of `index` to handlers.
+pub fn with_index(
+    index: ShareIndex,
+) -> impl Filter<Extract = (ShareIndex,), Error = Infallible> + Clone {
+    warp::any().map(move || index.clone())
+}
 
-    let template = AdminListTemplate { entries };
-    let res = template.render().map_err(|e| reject::custom(Rej(e)))?;
+pub async fn admin_list(index: ShareIndex) -> WebResult<impl Reply> {
+    let metas = ShareMeta::load_all().await?;
+    let res = {
+        let entries = index.read();
+        let template = AdminListTemplate {
+            entries: &entries,
+            metas: &metas,
+        };
+        template.render().map_err(Error::from)?
+    };
     Ok(reply::html(res))
 }
 
-pub async fn admin_add(dir_name: String) -> Result<impl Reply, Rejection> {
-    let dir_result = fs::create_dir(format!("{DIRS}/{dir_name}"));
-    if let Err(e) = dir_result {
-        return Err(reject::custom(Rej(e)));
-    }
+/// Create directory `dir_name`, and publish it according to the optional `mode` query parameter
+/// (see [ShareMode]).
+pub async fn admin_add(
+    dir_name: String,
+    query: HashMap<String, String>,
+    index: ShareIndex,
+) -> Result<impl Reply, Rejection> {
+    check_name(&dir_name)?;
+    let mode = match query.get("mode") {
+        Some(mode) => mode.parse::<ShareMode>()?,
+        None => ShareMode::default(),
+    };
+    share::create(&dir_name, mode).await?;
+    // Don't wait for inotify, so that the redirected-to admin page lists the new directory.
+    index
+        .refresh_async(RealFileSystem {}, dir_name)
+        .await
+        .map_err(Error::from)?;
     //redirect_see_other(format!("/{ADMIN}"))
     Ok(redirect::see_other(
         format!("/{ADMIN}").parse::<Uri>().expect("Admin UR"),
     ))
 }
 
-pub async fn admin_remove_write(dir_name: String) -> Result<impl Reply, Rejection> {
-    let dir_result = fs::create_dir(format!("{DIRS}/{dir_name}"));
-    if let Err(e) = dir_result {
-        return Err(reject::custom(Rej(e)));
+/// Set the [MethodPolicy] of existing share `dir_name` from query parameters `deny`
+/// (comma-separated method names) and `max_put_bytes`, and its [crate::quota] from `quota_bytes`.
+/// Missing or empty parameters mean no restriction.
+pub async fn admin_policy(
+    dir_name: String,
+    query: HashMap<String, String>,
+) -> Result<impl Reply, Rejection> {
+    check_name(&dir_name)?;
+    tokio_fs::metadata(format!("{DIRS}/{dir_name}"))
+        .await
+        .map_err(|e| Error::from_io(e, &dir_name))?;
+
+    let denied_methods = query
+        .get("deny")
+        .map(|deny| {
+            deny.split(',')
+                .map(|method| method.trim().to_lowercase())
+                .filter(|method| !method.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
+    let bytes = |key: &str| match query.get(key).map(|max| max.trim()) {
+        None | Some("") => Ok(None),
+        Some(max) => max
+            .parse::<u64>()
+            .map(Some)
+            .map_err(|_| Error::BadRequest(format!("{key} {max}"))),
+    };
+    let max_put_bytes = bytes("max_put_bytes")?;
+    let quota_bytes = bytes("quota_bytes")?;
+
+    let mut meta = ShareMeta::load(&dir_name).await?;
+    meta.policy = MethodPolicy::new(denied_methods, max_put_bytes)?;
+    meta.quota_bytes = quota_bytes;
+    meta.save(&dir_name).await?;
+    Ok(redirect::see_other(
+        format!("/{ADMIN}").parse::<Uri>().expect("Admin URI"),
+    ))
+}
+
+/// Set the [SiteConfig] of existing share `dir_name` from query parameters `site` (`on` or `off`),
+/// `fallback`, `not_found` (paths within the share) and `max_age` (seconds). Missing or empty
+/// parameters mean none (or the default max. age).
+pub async fn admin_site(
+    dir_name: String,
+    query: HashMap<String, String>,
+) -> Result<impl Reply, Rejection> {
+    check_name(&dir_name)?;
+    let metadata = tokio_fs::metadata(format!("{DIRS}/{dir_name}"))
+        .await
+        .map_err(|e| Error::from_io(e, &dir_name))?;
+    if !metadata.is_dir() {
+        return Err(Error::BadRequest(format!("{dir_name} is not a directory")).into());
+    }
+
+    let param = |key: &str| {
+        query
+            .get(key)
+            .map(|value| value.trim())
+            .filter(|value| !value.is_empty())
+    };
+    let site_path = |key: &str| match param(key) {
+        Some(path) if !site::is_site_path(path) => Err(Error::BadRequest(format!("{key} {path}"))),
+        path => Ok(path.map(str::to_owned)),
+    };
+    let site = match param("site") {
+        Some("off") => None,
+        Some("on") => Some(SiteConfig {
+            fallback: site_path("fallback")?,
+            not_found: site_path("not_found")?,
+            max_age: match param("max_age") {
+                None => SiteConfig::default().max_age,
+                Some(max_age) => max_age
+                    .parse()
+                    .map_err(|_| Error::BadRequest(format!("max_age {max_age}")))?,
+            },
+        }),
+        _ => return Err(Error::BadRequest("site".to_owned()).into()),
+    };
+
+    let mut meta = ShareMeta::load(&dir_name).await?;
+    meta.site = site;
+    meta.save(&dir_name).await?;
+    Ok(redirect::see_other(
+        format!("/{ADMIN}").parse::<Uri>().expect("Admin URI"),
+    ))
+}
+
+/// Previous versions of one share's files.
+#[derive(Template)]
+#[template(path = "admin_versions.html")]
+pub struct AdminVersionsTemplate {
+    pub name: String,
+    pub retention: Retention,
+    pub files: Vec<FileVersions>,
+}
+
+impl AdminVersionsTemplate {
+    fn date(&self, version: &Version) -> String {
+        httpdate::fmt_http_date(version.time())
     }
+}
+
+pub async fn admin_versions(dir_name: String) -> WebResult<impl Reply> {
+    check_name(&dir_name)?;
+    let retention = ShareMeta::load(&dir_name).await?.versions;
+    let name = dir_name.clone();
+    let files = task::spawn_blocking(move || ShareVersions::of(&name).list())
+        .await
+        .map_err(Error::from)?
+        .map_err(Error::from)?;
+    let template = AdminVersionsTemplate {
+        name: dir_name,
+        retention,
+        files,
+    };
+    Ok(reply::html(template.render().map_err(Error::from)?))
+}
+
+fn redirect_to_versions(dir_name: &str) -> impl Reply {
+    let path = percent_encoding::utf8_percent_encode(dir_name, percent_encoding::NON_ALPHANUMERIC);
+    redirect::see_other(
+        format!("/{ADMIN}/{VERSIONS_SEGMENT}/{path}")
+            .parse::<Uri>()
+            .expect("Versions URI"),
+    )
+}
+
+/// Restore version `id` of file `path` (both query parameters) of share `dir_name`.
+pub async fn admin_restore(
+    dir_name: String,
+    query: HashMap<String, String>,
+) -> Result<impl Reply, Rejection> {
+    check_name(&dir_name)?;
+    let relative = query
+        .get("path")
+        .map(PathBuf::from)
+        .filter(|path| path.components().all(|c| matches!(c, Component::Normal(_))))
+        .ok_or_else(|| Error::BadRequest("path".to_owned()))?;
+    let id = query
+        .get("id")
+        .and_then(|id| id.parse::<u128>().ok())
+        .ok_or_else(|| Error::BadRequest("id".to_owned()))?;
+    let meta = ShareMeta::load(&dir_name).await?;
+
+    let name = dir_name.clone();
+    let what = relative.display().to_string();
+    task::spawn_blocking(move || {
+        let share_dir = PathBuf::from(format!("{DIRS}/{name}"));
+        ShareVersions::of(&name).with_cap(meta.quota_bytes).restore(
+            &share_dir,
+            &relative,
+            id,
+            meta.versions,
+        )
+    })
+    .await
+    .map_err(Error::from)?
+    .map_err(|e| Error::from_io(e, what))?;
+    Ok(redirect_to_versions(&dir_name))
+}
+
+/// Set the [Retention] of versions of share `dir_name` from query parameter `retention`: `off`,
+/// `last:<count>` or `days:<days>`.
+pub async fn admin_retention(
+    dir_name: String,
+    query: HashMap<String, String>,
+) -> Result<impl Reply, Rejection> {
+    check_name(&dir_name)?;
+    tokio_fs::metadata(format!("{DIRS}/{dir_name}"))
+        .await
+        .map_err(|e| Error::from_io(e, &dir_name))?;
+    let retention = query
+        .get("retention")
+        .map(|retention| retention.parse::<Retention>())
+        .ok_or_else(|| Error::BadRequest("retention".to_owned()))??;
+
+    let mut meta = ShareMeta::load(&dir_name).await?;
+    meta.versions = retention;
+    meta.save(&dir_name).await?;
+    Ok(redirect_to_versions(&dir_name))
+}
+
+/// Trash of one share.
+#[derive(Template)]
+#[template(path = "admin_trash.html")]
+pub struct AdminTrashTemplate {
+    pub name: String,
+    pub trash_days: u64,
+    pub items: Vec<TrashItem>,
+}
+
+impl AdminTrashTemplate {
+    fn date(&self, item: &TrashItem) -> String {
+        httpdate::fmt_http_date(item.time())
+    }
+}
+
+pub async fn admin_trash(dir_name: String) -> WebResult<impl Reply> {
+    check_name(&dir_name)?;
+    let trash_days = ShareMeta::load(&dir_name).await?.trash_days;
+    let name = dir_name.clone();
+    let items = task::spawn_blocking(move || ShareTrash::of(&name).list())
+        .await
+        .map_err(Error::from)?
+        .map_err(Error::from)?;
+    let template = AdminTrashTemplate {
+        name: dir_name,
+        trash_days,
+        items,
+    };
+    Ok(reply::html(template.render().map_err(Error::from)?))
+}
+
+fn redirect_to_trash(dir_name: &str) -> impl Reply {
+    let path = percent_encoding::utf8_percent_encode(dir_name, percent_encoding::NON_ALPHANUMERIC);
+    redirect::see_other(
+        format!("/{ADMIN}/{TRASH_SEGMENT}/{path}")
+            .parse::<Uri>()
+            .expect("Trash URI"),
+    )
+}
+
+fn trash_id(query: &HashMap<String, String>) -> Result<Option<u128>, Error> {
+    query
+        .get("id")
+        .map(|id| {
+            id.parse::<u128>()
+                .map_err(|_| Error::BadRequest("id".to_owned()))
+        })
+        .transpose()
+}
+
+/// Move item `id` (a query parameter) of the trash of share `dir_name` back.
+pub async fn admin_trash_restore(
+    dir_name: String,
+    query: HashMap<String, String>,
+) -> Result<impl Reply, Rejection> {
+    check_name(&dir_name)?;
+    let id = trash_id(&query)?.ok_or_else(|| Error::BadRequest("id".to_owned()))?;
+    let name = dir_name.clone();
+    task::spawn_blocking(move || {
+        let share_dir = PathBuf::from(format!("{DIRS}/{name}"));
+        ShareTrash::of(&name).restore(&share_dir, id)
+    })
+    .await
+    .map_err(Error::from)?
+    .map_err(|e| Error::from_io(e, format!("trash item {id}")))?;
+    Ok(redirect_to_trash(&dir_name))
+}
+
+/// Remove item `id` (a query parameter) of the trash of share `dir_name` permanently. Without `id`,
+/// empty the whole trash.
+pub async fn admin_trash_purge(
+    dir_name: String,
+    query: HashMap<String, String>,
+) -> Result<impl Reply, Rejection> {
+    check_name(&dir_name)?;
+    let id = trash_id(&query)?;
+    let name = dir_name.clone();
+    task::spawn_blocking(move || {
+        let trash = ShareTrash::of(&name);
+        match id {
+            Some(id) => trash.purge(id),
+            None => trash.purge_all(),
+        }
+    })
+    .await
+    .map_err(Error::from)?
+    .map_err(|e| Error::from_io(e, "trash item"))?;
+    Ok(redirect_to_trash(&dir_name))
+}
+
+/// Set [ShareMeta::trash_days] of share `dir_name` from query parameter `days`.
+pub async fn admin_trash_retention(
+    dir_name: String,
+    query: HashMap<String, String>,
+) -> Result<impl Reply, Rejection> {
+    check_name(&dir_name)?;
+    tokio_fs::metadata(format!("{DIRS}/{dir_name}"))
+        .await
+        .map_err(|e| Error::from_io(e, &dir_name))?;
+    let days = query
+        .get("days")
+        .and_then(|days| days.parse::<u64>().ok())
+        .ok_or_else(|| Error::BadRequest("days".to_owned()))?;
+
+    let mut meta = ShareMeta::load(&dir_name).await?;
+    meta.trash_days = days;
+    meta.save(&dir_name).await?;
+    Ok(redirect_to_trash(&dir_name))
+}
+
+/// Freeze share `dir_name` into new read-only share `as` (a query parameter). See [share::freeze].
+pub async fn admin_freeze(
+    dir_name: String,
+    query: HashMap<String, String>,
+    index: ShareIndex,
+) -> Result<impl Reply, Rejection> {
+    check_name(&dir_name)?;
+    let frozen_name = query
+        .get("as")
+        .ok_or_else(|| Error::BadRequest("as".to_owned()))?
+        .to_owned();
+    check_name(&frozen_name)?;
+
+    share::freeze(&dir_name, &frozen_name).await?;
+    for name in [dir_name, frozen_name] {
+        index
+            .refresh_async(RealFileSystem {}, name)
+            .await
+            .map_err(Error::from)?;
+    }
+    Ok(redirect::see_other(
+        format!("/{ADMIN}").parse::<Uri>().expect("Admin URI"),
+    ))
+}
+
+/// Create single-file share `file_name` from the request body, and publish it according to the
+/// optional `mode` query parameter (see [FileMode]). An empty body creates an empty file.
+/// `file_name` is percent-decoded, so that it can have spaces and non-ASCII characters.
+pub async fn admin_file(
+    file_name: String,
+    query: HashMap<String, String>,
+    body: impl Stream<Item = Result<impl Buf, warp::Error>> + Unpin,
+    index: ShareIndex,
+) -> Result<impl Reply, Rejection> {
+    let file_name = percent_decode_str(&file_name)
+        .decode_utf8()
+        .map_err(|_| Error::InvalidName(file_name.clone()))?
+        .to_string();
+    check_name(&file_name)?;
+    let mode = match query.get("mode") {
+        Some(mode) => mode.parse::<FileMode>()?,
+        None => FileMode::default(),
+    };
+    // Fail before receiving the whole body. (share::create_file checks again.)
+    if tokio_fs::symlink_metadata(format!("{DIRS}/{file_name}"))
+        .await
+        .is_ok()
+    {
+        return Err(Error::AlreadyExists(file_name).into());
+    }
+
+    let staged = share::staged(&file_name);
+    if let Err(e) = receive(body, Path::new(&staged), None).await {
+        let _ = tokio_fs::remove_file(&staged).await;
+        return Err(e.into());
+    }
+    share::create_file(&file_name, mode).await?;
+    index
+        .refresh_async(RealFileSystem {}, file_name)
+        .await
+        .map_err(Error::from)?;
+    Ok(redirect::see_other(
+        format!("/{ADMIN}").parse::<Uri>().expect("Admin URI"),
+    ))
+}
+
+/// Write request `body` (or a part of it) to (new or truncated) file `path`. Fail with
+/// [Error::PayloadTooLarge] once it's over `limit` (if any). The caller removes a partial file.
+pub(crate) async fn receive(
+    body: impl Stream<Item = Result<impl Buf, warp::Error>>,
+    path: &Path,
+    limit: Option<u64>,
+) -> Result<(), Error> {
+    let mut body = Box::pin(body);
+    let mut file = tokio_fs::File::create(path).await?;
+    let mut received = 0u64;
+    while let Some(chunk) = body.next().await {
+        let mut chunk = chunk.map_err(|e| Error::BadRequest(format!("request body: {e}")))?;
+        received += chunk.remaining() as u64;
+        if let Some(limit) = limit.filter(|limit| received > *limit) {
+            return Err(Error::PayloadTooLarge { limit });
+        }
+        while chunk.has_remaining() {
+            let written = file.write(chunk.chunk()).await?;
+            chunk.advance(written);
+        }
+    }
+    file.flush().await?;
+    Ok(())
+}
+
+/// Publish sub-folder `path` (a query parameter, with `/` separators) of share `dir_name` as `as`,
+/// according to `mode` (`read` or `write`). See [share::publish_nested].
+pub async fn admin_nested(
+    dir_name: String,
+    query: HashMap<String, String>,
+    index: ShareIndex,
+) -> Result<impl Reply, Rejection> {
+    check_name(&dir_name)?;
+    let param = |key: &str| {
+        query
+            .get(key)
+            .filter(|value| !value.is_empty())
+            .ok_or_else(|| Error::BadRequest(key.to_owned()))
+    };
+    let path = PathBuf::from(param("path")?.trim_matches('/'));
+    let name = param("as")?.to_owned();
+    check_name(&name)?;
+    let mode = match query.get("mode") {
+        Some(mode) => mode.parse::<ShareMode>()?,
+        None => ShareMode::Read,
+    };
+
+    share::publish_nested(&dir_name, &path, &name, mode).await?;
+    index
+        .refresh_async(RealFileSystem {}, name)
+        .await
+        .map_err(Error::from)?;
+    Ok(redirect::see_other(
+        format!("/{ADMIN}").parse::<Uri>().expect("Admin URI"),
+    ))
+}
+
+/// Most recent records of both logs.
+#[derive(Template)]
+#[template(path = "admin_logs.html")]
+pub struct AdminLogsTemplate {
+    pub access: Vec<AccessRecord>,
+    pub audit: Vec<AuditRecord>,
+}
+
+impl AdminLogsTemplate {
+    fn date(&self, time: std::time::SystemTime) -> String {
+        httpdate::fmt_http_date(time)
+    }
+}
+
+/// How many records of each log [admin_logs] shows.
+const ADMIN_LOGS_LIMIT: usize = 200;
+
+pub async fn admin_logs(logs: Logs) -> WebResult<impl Reply> {
+    let template = AdminLogsTemplate {
+        access: logs.recent_access(ADMIN_LOGS_LIMIT).await?,
+        audit: logs.recent_audit(ADMIN_LOGS_LIMIT).await?,
+    };
+    Ok(reply::html(template.render().map_err(Error::from)?))
+}
+
+pub async fn admin_remove_write(dir_name: String) -> Result<impl Reply, Rejection> {
+    check_name(&dir_name)?;
+    tokio_fs::create_dir(format!("{DIRS}/{dir_name}"))
+        .await
+        .map_err(|e| Error::from_io(e, &dir_name))?;
     // @TODO replace with redirect::see_other(...):
     redirect_see_other(format!("/{ADMIN}"))
 }
 
 pub async fn main() -> io::Result<()> {
+    // Diagnostics, by RUST_LOG: By default errors of dependencies (like dav-server), and ours down
+    // to info. Requests go to [Logs] instead.
+    env_logger::Builder::from_env(
+        env_logger::Env::default().default_filter_or("error,wdav_crypto_rs=info"),
+    )
+    .init();
     let port = env::var(ENV_PORT).unwrap_or(DEFAULT_PORT.to_string());
     let port = port.parse::<u16>().unwrap();
 
     let salt = env::var(ENV_SALT).expect("Requiring SALT env variable.");
     let data_key = env::var(ENV_DATA_KEY).expect("Requiring 'data key', formerly known as 'project key'. It should be passed automatically by Deta on both Deta platform and local `space dev`.");
+    links::init(&data_key, &salt);
 
     let ip: IpAddr = "127.0.0.1".parse().unwrap();
     let addr = SocketAddr::new(ip, port);
@@ -140,15 +1150,53 @@ pub async fn main() -> io::Result<()> {
     fs::create_dir_all(SYMLINKS)?;
     fs::create_dir_all(SYMLINKS_READ)?;
     fs::create_dir_all(SYMLINKS_WRITE)?;
+    fs::create_dir_all(SYMLINKS_DROP)?;
+    fs::create_dir_all(SYMLINKS_APPEND)?;
+    fs::create_dir_all(SYMLINKS_FILE)?;
+    fs::create_dir_all(META)?;
+    fs::create_dir_all(crate::VERSIONS)?;
+    fs::create_dir_all(TRASH)?;
+    fs::create_dir_all(PROPS)?;
+    fs::create_dir_all(crate::STAGING)?;
+    fs::create_dir_all(Path::new(crate::UPLOADS).join(TUS))?;
+    fs::create_dir_all(crate::CHUNKS)?;
+    fs::create_dir_all(LOGS)?;
+    let logs = Logs::new(LOGS).chained(&data_key);
+
+    let isolation = Isolation::from_env().map_err(io::Error::other)?;
+    let extract_limits = ExtractLimits::from_env().map_err(io::Error::other)?;
 
-    let dav_read_filter = {
-        // DavMethodSet::add(&mut self, DavMethod) is ugly. And there is no direct method to
-        // add/merge/union two instances of DavMethodSet. But, for now, the following:
-        let mut read_only = DavMethodSet::HTTP_RO;
-        read_only.add(DavMethod::PropFind);
+    // Both serve SYMLINKS_READ. Shares that are static sites are websites under READ, but plain
+    // WebDAV (like any other read share) under READ_DAV.
+    let dav_read_filter = method_policy(SYMLINKS_READ, META).and(
+        archive(SYMLINKS_READ)
+            .or(hardened(
+                isolation.clone(),
+                SITE_SANDBOX,
+                static_site(READ, SYMLINKS_READ),
+            ))
+            .unify()
+            .or(dav_read(READ, isolation.clone()))
+            .unify(),
+    );
+    let dav_read_dav_filter = method_policy(SYMLINKS_READ, META).and(
+        archive(SYMLINKS_READ)
+            .or(dav_read(READ_DAV, isolation.clone()))
+            .unify(),
+    );
 
-        let dav_handler = dav_config(READ, SYMLINKS_READ, read_only).build_handler();
-        dav_server::warp::dav_handler(dav_handler)
+    // Single-file shares only: Anything else under SYMLINKS_FILE is "not found".
+    let dav_file_filter = {
+        let dav_handler =
+            dav_config(FILE_LINK, SYMLINKS_FILE, DavMethodSet::HTTP_RO).build_handler();
+        let file = single_file(SYMLINKS_FILE)
+            .and_then(|file_name: Option<String>| async move {
+                file_name.ok_or_else(|| Rejection::from(Error::NotFound("file".to_owned())))
+            })
+            .map(Some)
+            .and(dav_server::warp::dav_handler(dav_handler))
+            .map(with_disposition);
+        hardened(isolation.clone(), SANDBOX, file)
     };
 
     let dav_write_filter = {
@@ -161,27 +1209,238 @@ pub async fn main() -> io::Result<()> {
         read_write.add(DavMethod::Put);
 
         let dav_handler = dav_config(WRITE, SYMLINKS_WRITE, read_write).build_handler();
-        dav_server::warp::dav_handler(dav_handler)
+        method_policy(SYMLINKS_WRITE, META).and(
+            archive(SYMLINKS_WRITE)
+                .or(extract_upload(
+                    SYMLINKS_WRITE,
+                    crate::STAGING,
+                    extract_limits,
+                ))
+                .unify()
+                .or(file_manager(
+                    WRITE,
+                    SYMLINKS_WRITE,
+                    crate::STAGING,
+                    PropStore::new(SYMLINKS_WRITE, PROPS, true),
+                )
+                .or(trash_delete(SYMLINKS_WRITE))
+                .unify()
+                .or(hardened(
+                    isolation.clone(),
+                    SANDBOX,
+                    with_collection_props(
+                        WRITE,
+                        PropStore::new(SYMLINKS_WRITE, PROPS, true),
+                        keep_versions(WRITE, SYMLINKS_WRITE).and(quota::dav_handler(
+                            dav_handler,
+                            dav_fs(SYMLINKS_WRITE),
+                            SYMLINKS_WRITE,
+                        )),
+                    ),
+                ))),
+        )
     };
 
+    let index = ShareIndex::new();
+    let watcher = Watcher::start(index.clone()).await?;
+    tokio::spawn(watcher.run());
+
+    tokio::spawn(cleanup::run());
+
     let admin_list = warp::path(ADMIN)
         .and(warp::path::end())
+        .and(with_index(index.clone()))
         .and_then(admin_list);
 
-    // HTTP POST with URL parameters is unusual, but easy to handle & test
-    let admin_add = warp::post()
+    // HTTP POST with URL parameters is unusual, but easy to handle & test.
+    //
+    // Match the path before the method. Otherwise warp would prefer "405 Method Not Allowed" over
+    // "404 Not Found" for any unknown path.
+    let admin_add = warp::path(ADMIN)
+        .and(warp::path(ADD))
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::post())
         .and(warp::body::content_length_limit(1024 * 16))
-        .and(warp::path!("admin" / "add" / String))
+        .and(warp::query::<HashMap<String, String>>())
+        .and(with_index(index.clone()))
         .and_then(admin_add);
 
-    let routes = warp::any().and(
+    let admin_policy = warp::path(ADMIN)
+        .and(warp::path(POLICY))
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(warp::body::content_length_limit(1024 * 16))
+        .and(warp::query::<HashMap<String, String>>())
+        .and_then(admin_policy);
+
+    let admin_site = warp::path(ADMIN)
+        .and(warp::path(SITE))
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(warp::body::content_length_limit(1024 * 16))
+        .and(warp::query::<HashMap<String, String>>())
+        .and_then(admin_site);
+
+    let admin_versions = warp::path(ADMIN)
+        .and(warp::path(VERSIONS_SEGMENT))
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::get())
+        .and_then(admin_versions);
+
+    let admin_restore = warp::path(ADMIN)
+        .and(warp::path(VERSIONS_SEGMENT))
+        .and(warp::path::param::<String>())
+        .and(warp::path(RESTORE))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(warp::body::content_length_limit(1024 * 16))
+        .and(warp::query::<HashMap<String, String>>())
+        .and_then(admin_restore);
+
+    let admin_retention = warp::path(ADMIN)
+        .and(warp::path(VERSIONS_SEGMENT))
+        .and(warp::path::param::<String>())
+        .and(warp::path(RETENTION))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(warp::body::content_length_limit(1024 * 16))
+        .and(warp::query::<HashMap<String, String>>())
+        .and_then(admin_retention);
+
+    let admin_freeze = warp::path(ADMIN)
+        .and(warp::path(FREEZE))
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(warp::body::content_length_limit(1024 * 16))
+        .and(warp::query::<HashMap<String, String>>())
+        .and(with_index(index.clone()))
+        .and_then(admin_freeze);
+
+    let admin_nested = warp::path(ADMIN)
+        .and(warp::path(NESTED))
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(warp::body::content_length_limit(1024 * 16))
+        .and(warp::query::<HashMap<String, String>>())
+        .and(with_index(index.clone()))
+        .and_then(admin_nested);
+
+    // No length limit: The admin uploads the file itself.
+    let admin_file = warp::path(ADMIN)
+        .and(warp::path(FILE))
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(warp::query::<HashMap<String, String>>())
+        .and(warp::body::stream())
+        .and(with_index(index.clone()))
+        .and_then(admin_file);
+
+    let admin_trash = warp::path(ADMIN)
+        .and(warp::path(TRASH_SEGMENT))
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::get())
+        .and_then(admin_trash);
+
+    let admin_logs = warp::path(ADMIN)
+        .and(warp::path(LOGS_SEGMENT))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(warp::any().map({
+            let logs = logs.clone();
+            move || logs.clone()
+        }))
+        .and_then(admin_logs);
+
+    let admin_trash_action = |action: &'static str| {
+        warp::path(ADMIN)
+            .and(warp::path(TRASH_SEGMENT))
+            .and(warp::path::param::<String>())
+            .and(warp::path(action))
+            .and(warp::path::end())
+            .and(warp::post())
+            .and(warp::body::content_length_limit(1024 * 16))
+            .and(warp::query::<HashMap<String, String>>())
+    };
+    let admin_trash_restore = admin_trash_action(RESTORE).and_then(admin_trash_restore);
+    let admin_trash_purge = admin_trash_action(PURGE).and_then(admin_trash_purge);
+    let admin_trash_retention = admin_trash_action(RETENTION).and_then(admin_trash_retention);
+
+    // User content (and the file manager) on the content origin only, if any; /admin never there.
+    let admin = not_on_content_origin(isolation.clone()).and(
         admin_list
-            .or(admin_add)
-            .or(warp::path(READ).and(dav_read_filter))
-            .or(warp::path(WRITE).and(dav_write_filter)),
+            .or(admin_versions)
+            .or(admin_trash)
+            .or(admin_logs)
+            .or(audited(
+                logs.clone(),
+                admin_add
+                    .or(admin_policy)
+                    .or(admin_site)
+                    .or(admin_restore)
+                    .or(admin_retention)
+                    .or(admin_freeze)
+                    .or(admin_file)
+                    .or(admin_nested)
+                    .or(admin_trash_restore)
+                    .or(admin_trash_purge)
+                    .or(admin_trash_retention),
+            )),
+    );
+    let content = |prefix_segment: &'static str| {
+        warp::path(prefix_segment).and(to_content_origin(isolation.clone()))
+    };
+    let routes = warp::any().and(
+        admin
+            .or(content(READ).or(warp::path(READ).and(dav_read_filter)))
+            .or(content(READ_DAV).or(warp::path(READ_DAV).and(dav_read_dav_filter)))
+            .or(content(WRITE).or(warp::path(WRITE).and(dav_write_filter)))
+            .or(content(APPEND).or(warp::path(APPEND).and(dav_append(
+                APPEND,
+                SYMLINKS_APPEND,
+                isolation.clone(),
+            ))))
+            .or(warp::path(DROP).and(dav_drop(DROP, SYMLINKS_DROP)))
+            .or(warp::path(TUS).and(tus(
+                TUS,
+                SYMLINKS_WRITE,
+                Path::new(crate::UPLOADS).join(TUS),
+                crate::VERSIONS,
+                PropStore::new(SYMLINKS_WRITE, PROPS, true),
+            )))
+            .or(warp::path(CHUNKING).and(chunked_upload(
+                CHUNKING,
+                WRITE,
+                SYMLINKS_WRITE,
+                crate::CHUNKS,
+                META,
+                crate::VERSIONS,
+                PropStore::new(SYMLINKS_WRITE, PROPS, true),
+            )))
+            .or(content(FILE_LINK).or(warp::path(FILE_LINK).and(dav_file_filter))),
     );
 
-    println!("listening on {}.", addr);
+    log::info!("listening on {}.", addr);
+    let shares = [
+        (READ, SYMLINKS_READ),
+        (READ_DAV, SYMLINKS_READ),
+        (WRITE, SYMLINKS_WRITE),
+        (APPEND, SYMLINKS_APPEND),
+        (DROP, SYMLINKS_DROP),
+        (FILE_LINK, SYMLINKS_FILE),
+        (TUS, SYMLINKS_WRITE),
+        (CHUNKING, SYMLINKS_WRITE),
+    ]
+    .map(|(prefix_segment, dir)| (prefix_segment, PathBuf::from(dir)));
+    // Boxed, since the type of all routes is big enough already.
+    let routes = access_log(logs, shares.to_vec(), recover_by_accept(routes).boxed());
     warp::serve(routes).run(addr).await;
     Ok(())
 }
@@ -0,0 +1,353 @@
+//! Creating (and publishing) shares from the admin pages.
+
+use crate::entry::SecondaryDir;
+use crate::error::Error;
+use crate::links::symlink_path;
+use crate::meta::ShareMeta;
+use crate::{DIRS, STAGING, SYMLINKS_APPEND, SYMLINKS_DROP, SYMLINKS_READ, SYMLINKS_WRITE};
+use core::fmt;
+use core::str::FromStr;
+use std::fs;
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use tokio::fs as tokio_fs;
+use tokio::task;
+
+/// How a new share gets published, chosen when it's created. It determines which symlinks point to
+/// it (and with that, under which URL prefixes it's served).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ShareMode {
+    /// No symlinks: Not published (yet).
+    #[default]
+    Private,
+    /// [SYMLINKS_READ]
+    Read,
+    /// [SYMLINKS_READ] and [SYMLINKS_WRITE]
+    ReadWrite,
+    /// [SYMLINKS_READ] and [SYMLINKS_APPEND]: Uploads can't be deleted, moved nor
+    /// overwritten.
+    ReadAppend,
+    /// [SYMLINKS_DROP] only.
+    Drop,
+}
+
+impl ShareMode {
+    pub const ALL: [Self; 5] = [
+        Self::Private,
+        Self::Read,
+        Self::ReadWrite,
+        Self::ReadAppend,
+        Self::Drop,
+    ];
+
+    /// Value of the `mode` URL query parameter.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Private => "private",
+            Self::Read => "read",
+            Self::ReadWrite => "write",
+            Self::ReadAppend => "append",
+            Self::Drop => "drop",
+        }
+    }
+
+    /// Symlink directories to publish under.
+    fn symlink_dirs(&self) -> &'static [SecondaryDir] {
+        match self {
+            Self::Private => &[],
+            Self::Read => &[SecondaryDir::Read],
+            Self::ReadWrite => &[SecondaryDir::Read, SecondaryDir::Write],
+            Self::ReadAppend => &[SecondaryDir::Read, SecondaryDir::Append],
+            Self::Drop => &[SecondaryDir::Drop],
+        }
+    }
+}
+
+impl fmt::Display for ShareMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ShareMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|mode| mode.as_str() == s)
+            .ok_or_else(|| Error::BadRequest(format!("unknown share mode {s}")))
+    }
+}
+
+/// Publish directory `path` inside share `share` as nested publication `name` (see
+/// [crate::entry::Entry::Nested]), according to `mode`: [ShareMode::Read] or [ShareMode::ReadWrite]
+/// only. The caller validates `share` and `name`. `path` must be relative, non-empty, and without
+/// `.` or `..`; it must be a directory, and not reached through symlinks (which could lead out of
+/// the share).
+pub async fn publish_nested(
+    share: &str,
+    path: &Path,
+    name: &str,
+    mode: ShareMode,
+) -> Result<(), Error> {
+    if !matches!(mode, ShareMode::Read | ShareMode::ReadWrite) {
+        return Err(Error::BadRequest(format!(
+            "sub-folders can't be published as {mode}"
+        )));
+    }
+    if path.as_os_str().is_empty()
+        || !path
+            .components()
+            .all(|c| matches!(c, std::path::Component::Normal(_)))
+    {
+        return Err(Error::BadRequest("path".to_owned()));
+    }
+    let share_dir = Path::new(DIRS).join(share);
+    let target = share_dir.join(path);
+    let what = format!("{share}/{}", path.display());
+    let canonical = tokio_fs::canonicalize(&target)
+        .await
+        .map_err(|e| Error::from_io(e, &what))?;
+    let canonical_share = tokio_fs::canonicalize(&share_dir)
+        .await
+        .map_err(|e| Error::from_io(e, share))?;
+    if canonical != canonical_share.join(path) || !tokio_fs::metadata(&target).await?.is_dir() {
+        return Err(Error::BadRequest(format!("{what} is not a directory")));
+    }
+    // A top level share of the same name would make the symlinks incorrect.
+    if tokio_fs::symlink_metadata(format!("{DIRS}/{name}"))
+        .await
+        .is_ok()
+    {
+        return Err(Error::AlreadyExists(name.to_owned()));
+    }
+
+    for under in mode.symlink_dirs() {
+        tokio_fs::symlink(&target, symlink_path(*under, name))
+            .await
+            .map_err(|e| Error::from_io(e, name))?;
+    }
+    Ok(())
+}
+
+/// How a new single-file share (see [crate::entry::Entry::PrimaryFile]) gets published.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FileMode {
+    /// No symlinks: Not published (yet).
+    #[default]
+    Private,
+    /// [SYMLINKS_READ]: At `/read/<name>`.
+    Read,
+    /// [crate::SYMLINKS_FILE]: At `/f/<hash>` only (see [crate::links]).
+    Link,
+    /// Both [SYMLINKS_READ] and [crate::SYMLINKS_FILE].
+    ReadAndLink,
+}
+
+impl FileMode {
+    pub const ALL: [Self; 4] = [Self::Private, Self::Read, Self::Link, Self::ReadAndLink];
+
+    /// Value of the `mode` URL query parameter.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Private => "private",
+            Self::Read => "read",
+            Self::Link => "link",
+            Self::ReadAndLink => "read-link",
+        }
+    }
+
+    /// Symlink directories to publish under.
+    fn symlink_dirs(&self) -> &'static [SecondaryDir] {
+        match self {
+            Self::Private => &[],
+            Self::Read => &[SecondaryDir::Read],
+            Self::Link => &[SecondaryDir::File],
+            Self::ReadAndLink => &[SecondaryDir::Read, SecondaryDir::File],
+        }
+    }
+}
+
+impl fmt::Display for FileMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for FileMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|mode| mode.as_str() == s)
+            .ok_or_else(|| Error::BadRequest(format!("unknown file share mode {s}")))
+    }
+}
+
+/// Create directory `name` under [DIRS], and publish it according to `mode`. The caller validates
+/// `name` (see [crate::server::check_name]).
+///
+/// If publishing fails half way, the share stays (incorrectly) half-published, and the admin list
+/// shows it as such.
+pub async fn create(name: &str, mode: ShareMode) -> Result<(), Error> {
+    let target = format!("{DIRS}/{name}");
+    tokio_fs::create_dir(&target)
+        .await
+        .map_err(|e| Error::from_io(e, name))?;
+
+    for under in mode.symlink_dirs() {
+        tokio_fs::symlink(&target, symlink_path(*under, name))
+            .await
+            .map_err(|e| Error::from_io(e, name))?;
+    }
+    Ok(())
+}
+
+/// Where the content of new share `name` is prepared, before it's moved to [DIRS]: A file being
+/// uploaded (see [create_file]), or a directory being copied (see [freeze]).
+pub fn staged(name: &str) -> String {
+    format!("{STAGING}/{name}")
+}
+
+/// Move file [staged] for `name` to [DIRS], and publish it according to `mode`. The caller
+/// validates `name`, and writes the staged file. (Unlike [create], this fails if `name` exists,
+/// rather than replacing it.)
+pub async fn create_file(name: &str, mode: FileMode) -> Result<(), Error> {
+    let target = format!("{DIRS}/{name}");
+    if tokio_fs::symlink_metadata(&target).await.is_ok() {
+        let _ = tokio_fs::remove_file(staged(name)).await;
+        return Err(Error::AlreadyExists(name.to_owned()));
+    }
+    tokio_fs::set_permissions(staged(name), fs::Permissions::from_mode(0o644)).await?;
+    tokio_fs::rename(staged(name), &target)
+        .await
+        .map_err(|e| Error::from_io(e, name))?;
+
+    for under in mode.symlink_dirs() {
+        tokio_fs::symlink(&target, symlink_path(*under, name))
+            .await
+            .map_err(|e| Error::from_io(e, name))?;
+    }
+    Ok(())
+}
+
+/// Copy directory `from` to (new) `to`, read-only: Files get mode `0444`, and directories `0555`.
+/// Symlinks are skipped: They would keep pointing to live (writable) content. Blocking.
+///
+/// [fs::copy] uses `copy_file_range` on Linux, so filesystems that support reflinks (like Btrfs or
+/// XFS) share the data blocks rather than copying them. Either way, later writes to `from` don't
+/// change `to`.
+pub fn copy_read_only(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let target = to.join(entry.file_name());
+        if file_type.is_dir() {
+            copy_read_only(&entry.path(), &target)?;
+        } else if file_type.is_file() {
+            fs::copy(entry.path(), &target)?;
+            fs::set_permissions(&target, fs::Permissions::from_mode(0o444))?;
+        }
+    }
+    fs::set_permissions(to, fs::Permissions::from_mode(0o555))
+}
+
+/// Remove every symlink directly under any of `symlinks_dirs` that points to `dir` or into it,
+/// whatever it's named. Blocking.
+pub fn remove_links_into(symlinks_dirs: &[&Path], dir: &Path) -> io::Result<()> {
+    for symlinks_dir in symlinks_dirs {
+        for entry in fs::read_dir(symlinks_dir)? {
+            let path = entry?.path();
+            if fs::read_link(&path).is_ok_and(|target| target.starts_with(dir)) {
+                match fs::remove_file(&path) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                    _ => {}
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Freeze writable share `name`: Remove all symlinks that let it change (see
+/// [remove_links_into]), and publish a read-only copy of it (see [copy_read_only]) as new share
+/// `frozen_name` under [SYMLINKS_READ]. The caller validates both names. `name` stays readable.
+///
+/// Symlinks under [SYMLINKS_WRITE], [SYMLINKS_APPEND] and [SYMLINKS_DROP] are
+/// removed whatever they're named, so that this doesn't depend on the share's (indexed) state: That
+/// covers legacy or incorrect ones, and write symlinks of publications nested inside the share.
+///
+/// The copy is made under [staged] and then moved to [DIRS], so that a half-made copy is never
+/// published (nor listed).
+pub async fn freeze(name: &str, frozen_name: &str) -> Result<(), Error> {
+    let from = format!("{DIRS}/{name}");
+    let to = format!("{DIRS}/{frozen_name}");
+    if !tokio_fs::metadata(&from)
+        .await
+        .map_err(|e| Error::from_io(e, name))?
+        .is_dir()
+    {
+        return Err(Error::BadRequest(format!("{name} is not a directory")));
+    }
+    if tokio_fs::symlink_metadata(&to).await.is_ok() {
+        return Err(Error::AlreadyExists(frozen_name.to_owned()));
+    }
+
+    let share_dir = from.clone();
+    task::spawn_blocking(move || {
+        remove_links_into(
+            &[
+                Path::new(SYMLINKS_WRITE),
+                Path::new(SYMLINKS_APPEND),
+                Path::new(SYMLINKS_DROP),
+            ],
+            Path::new(&share_dir),
+        )
+    })
+    .await??;
+
+    let staged = staged(frozen_name);
+    let copy = {
+        let (from, staged) = (from.clone(), staged.clone());
+        task::spawn_blocking(move || {
+            // A leftover from an earlier failed freeze.
+            if Path::new(&staged).exists() {
+                make_writable(Path::new(&staged))?;
+                fs::remove_dir_all(&staged)?;
+            }
+            copy_read_only(Path::new(&from), Path::new(&staged))
+        })
+    };
+    copy.await??;
+    // Moving a directory to another parent requires it to be writable (for its `..` entry).
+    tokio_fs::set_permissions(&staged, fs::Permissions::from_mode(0o755)).await?;
+    tokio_fs::rename(&staged, &to)
+        .await
+        .map_err(|e| Error::from_io(e, frozen_name))?;
+    tokio_fs::set_permissions(&to, fs::Permissions::from_mode(0o555)).await?;
+    tokio_fs::symlink(&to, format!("{SYMLINKS_READ}/{frozen_name}"))
+        .await
+        .map_err(|e| Error::from_io(e, frozen_name))?;
+
+    let meta = ShareMeta {
+        frozen_from: Some(name.to_owned()),
+        ..ShareMeta::default()
+    };
+    meta.save(frozen_name).await
+}
+
+/// Undo [copy_read_only]'s permissions (of directories only), so that `dir` can be removed.
+fn make_writable(dir: &Path) -> io::Result<()> {
+    fs::set_permissions(dir, fs::Permissions::from_mode(0o755))?;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            make_writable(&entry.path())?;
+        }
+    }
+    Ok(())
+}
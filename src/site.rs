@@ -0,0 +1,197 @@
+//! Static website hosting of read shares that have a [SiteConfig] (see [ShareMeta::site]): GET and
+//! HEAD under [crate::READ] resolve `index.html` of directories, fall back to the configured file
+//! (for single-page apps) or serve the share's own 404 page, and set `Content-Type` and
+//! `Cache-Control`. Other methods, and shares without a [SiteConfig], go on to the DAV handler. The
+//! plain WebDAV (and directory listing) of every read share stays at [crate::READ_DAV].
+
+use crate::autoindex::INDEX_FILE;
+use crate::error::{self, Error};
+use crate::meta::{ShareMeta, SiteConfig};
+use crate::server::{accept_header, decode_tail, share_of};
+use http::header::{
+    HeaderMap, HeaderValue, CACHE_CONTROL, CONTENT_LENGTH, CONTENT_TYPE, IF_MODIFIED_SINCE,
+    LAST_MODIFIED, LOCATION,
+};
+use http::{Method, Response, StatusCode};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::fs as tokio_fs;
+use tokio_util::io::ReaderStream;
+use warp::hyper::Body;
+use warp::path::Peek;
+use warp::{reject::Rejection, reply, Filter, Reply};
+
+/// Whether `path` (from a [SiteConfig]) stays within the published directory: relative, without
+/// `..`.
+pub fn is_site_path(path: &str) -> bool {
+    !path.is_empty()
+        && Path::new(path)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+}
+
+/// Serve shares that have a [SiteConfig] as static websites. Put it in front of the DAV handler of
+/// [crate::READ], after the URL prefix segment. It rejects (with [warp::reject::not_found]) what
+/// isn't for a static site, so that the request can go on through [Filter::or]. Its errors are
+/// replies (like in [crate::server::trash_delete]).
+pub fn static_site(
+    prefix_segment: &'static str,
+    symlinks_dir: impl Into<PathBuf>,
+) -> impl Filter<Extract = (reply::Response,), Error = Rejection> + Clone {
+    let symlinks_dir = symlinks_dir.into();
+    warp::method()
+        .and(warp::path::peek())
+        .and(warp::header::headers_cloned())
+        .and(accept_header())
+        .and_then(
+            move |method: Method, tail: Peek, headers: HeaderMap, accept: Option<String>| {
+                let symlinks_dir = symlinks_dir.clone();
+                async move {
+                    if !(method == Method::GET || method == Method::HEAD) {
+                        return Err(warp::reject::not_found());
+                    }
+                    let relative = decode_tail(&tail).map_err(|_| warp::reject::not_found())?;
+                    let Some(share) = share_of(&symlinks_dir, &relative).await else {
+                        return Err(warp::reject::not_found());
+                    };
+                    let site = match ShareMeta::load(&share.name).await {
+                        Ok(ShareMeta {
+                            site: Some(site), ..
+                        }) => site,
+                        Ok(_) => return Err(warp::reject::not_found()),
+                        Err(e) => {
+                            let rejection = warp::reject::custom(e);
+                            return Ok(error::reply(&rejection, accept.as_deref()).into_response());
+                        }
+                    };
+
+                    let resolved = resolve(&symlinks_dir, &relative, tail.as_str(), &site).await;
+                    let response = match resolved {
+                        Resolved::AddSlash => {
+                            let location = format!("/{prefix_segment}/{}/", tail.as_str());
+                            Response::builder()
+                                .status(StatusCode::MOVED_PERMANENTLY)
+                                .header(LOCATION, location)
+                                .body(Body::empty())
+                                .map_err(|e| Error::BadRequest(e.to_string()))
+                        }
+                        Resolved::File(path, status) => serve(&path, status, &site, &headers).await,
+                        Resolved::NotFound => Err(Error::NotFound(tail.as_str().to_owned())),
+                    };
+                    Ok(response.unwrap_or_else(|e| {
+                        error::reply(&warp::reject::custom(e), accept.as_deref()).into_response()
+                    }))
+                }
+            },
+        )
+}
+
+/// What a request for a static site gets.
+enum Resolved {
+    /// Redirect a directory path to end with a slash (so that relative links work).
+    AddSlash,
+    /// Serve a file, with the status.
+    File(PathBuf, StatusCode),
+    NotFound,
+}
+
+async fn resolve(symlinks_dir: &Path, relative: &Path, tail: &str, site: &SiteConfig) -> Resolved {
+    let path = symlinks_dir.join(relative);
+    match tokio_fs::metadata(&path).await {
+        Ok(metadata) if metadata.is_file() => return Resolved::File(path, StatusCode::OK),
+        Ok(metadata) if metadata.is_dir() => {
+            if !tail.ends_with('/') {
+                return Resolved::AddSlash;
+            }
+            let index = path.join(INDEX_FILE);
+            if is_file(&index).await {
+                return Resolved::File(index, StatusCode::OK);
+            }
+        }
+        _ => {}
+    }
+
+    // The published directory (the first segment).
+    let Some(root) = relative.iter().next().map(|name| symlinks_dir.join(name)) else {
+        return Resolved::NotFound;
+    };
+    if let Some(fallback) = &site.fallback {
+        let fallback = root.join(fallback);
+        if is_file(&fallback).await {
+            return Resolved::File(fallback, StatusCode::OK);
+        }
+    } else if let Some(not_found) = &site.not_found {
+        let not_found = root.join(not_found);
+        if is_file(&not_found).await {
+            return Resolved::File(not_found, StatusCode::NOT_FOUND);
+        }
+    }
+    Resolved::NotFound
+}
+
+async fn is_file(path: &Path) -> bool {
+    tokio_fs::metadata(path).await.is_ok_and(|m| m.is_file())
+}
+
+/// `Content-Type` of `path`, based on its extension.
+pub fn content_type(path: &Path) -> String {
+    let mime = mime_guess::from_path(path).first_or_octet_stream();
+    if mime.type_() == mime_guess::mime::TEXT {
+        format!("{mime}; charset=utf-8")
+    } else {
+        mime.to_string()
+    }
+}
+
+/// `Cache-Control` of `path`: HTML is revalidated every time (so that changes show up at once),
+/// other files (like scripts, styles and images) are cached for [SiteConfig::max_age].
+pub fn cache_control(path: &Path, site: &SiteConfig) -> String {
+    let mime = mime_guess::from_path(path).first_or_octet_stream();
+    if mime.essence_str() == "text/html" {
+        "no-cache".to_owned()
+    } else {
+        format!("public, max-age={}", site.max_age)
+    }
+}
+
+async fn serve(
+    path: &Path,
+    status: StatusCode,
+    site: &SiteConfig,
+    headers: &HeaderMap,
+) -> Result<reply::Response, Error> {
+    let file = tokio_fs::File::open(path).await?;
+    let metadata = file.metadata().await?;
+    // HTTP dates have whole seconds.
+    let modified = metadata.modified().ok().and_then(|modified| {
+        let secs = modified.duration_since(UNIX_EPOCH).ok()?.as_secs();
+        Some(UNIX_EPOCH + Duration::from_secs(secs))
+    });
+
+    let not_modified = status == StatusCode::OK
+        && modified.is_some_and(|modified| {
+            headers
+                .get(IF_MODIFIED_SINCE)
+                .and_then(|since| since.to_str().ok())
+                .and_then(|since| httpdate::parse_http_date(since).ok())
+                .is_some_and(|since: SystemTime| modified <= since)
+        });
+
+    let mut builder = Response::builder()
+        .header(CACHE_CONTROL, cache_control(path, site))
+        .header(CONTENT_TYPE, content_type(path));
+    if let Some(modified) = modified {
+        builder = builder.header(LAST_MODIFIED, httpdate::fmt_http_date(modified));
+    }
+    let response = if not_modified {
+        builder.status(StatusCode::NOT_MODIFIED).body(Body::empty())
+    } else {
+        // For HEAD, hyper drops the body (but keeps the headers).
+        builder
+            .status(status)
+            .header(CONTENT_LENGTH, HeaderValue::from(metadata.len()))
+            .body(Body::wrap_stream(ReaderStream::new(file)))
+    };
+    response.map_err(|e| Error::Io(io::Error::other(e)))
+}
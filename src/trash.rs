@@ -0,0 +1,225 @@
+//! Per-share trash: DELETE through [crate::WRITE] moves files and directories here instead of
+//! removing them. Like [crate::versions], it lives outside of [crate::DIRS], so WebDAV clients
+//! never see it.
+//!
+//! Layout: `{TRASH}/{share}/{id}/item`, where `id` is the deletion time in nanoseconds since the
+//! Unix epoch, and `item` is the deleted file or directory. Its path relative to the share is in
+//! `{TRASH}/{share}/{id}/path` (rather than in a file name, which couldn't fit a long path).
+//!
+//! Everything here is blocking. Async handlers call it through [tokio::task::spawn_blocking].
+
+use crate::quota::usage;
+use crate::TRASH;
+use core::time::Duration;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Name of the deleted file or directory, in its item's directory.
+const ITEM: &str = "item";
+/// Name of the file (next to [ITEM]) with the path that [ITEM] was deleted from.
+const PATH_FILE: &str = "path";
+
+/// One deleted file or directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrashItem {
+    /// Nanoseconds since the Unix epoch.
+    pub id: u128,
+    /// Relative to the share.
+    pub path: PathBuf,
+    pub is_dir: bool,
+}
+
+impl TrashItem {
+    pub fn time(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_nanos(self.id as u64)
+    }
+}
+
+/// Trash of one share.
+#[derive(Debug, Clone)]
+pub struct ShareTrash {
+    dir: PathBuf,
+    /// Max. total size of all items. See [ShareTrash::with_cap].
+    cap: Option<u64>,
+}
+
+fn now_id() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+}
+
+fn not_found(id: u128) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("No item {id} in the trash"),
+    )
+}
+
+impl ShareTrash {
+    /// Trash of `share` under `root` (instead of [TRASH]).
+    pub fn new(root: impl AsRef<Path>, share: &str) -> Self {
+        Self {
+            dir: root.as_ref().join(share),
+            cap: None,
+        }
+    }
+
+    /// Trash of `share` under [TRASH].
+    pub fn of(share: &str) -> Self {
+        Self::new(TRASH, share)
+    }
+
+    /// Keep the trash within `max_bytes` in total (if any), on top of its expiry: Every
+    /// [ShareTrash::put] then purges the oldest items that don't fit (even the one just put, if
+    /// it's too large by itself). (The trash doesn't count towards the share's
+    /// [quota](crate::quota), so this is what bounds it.)
+    pub fn with_cap(mut self, max_bytes: Option<u64>) -> Self {
+        self.cap = max_bytes;
+        self
+    }
+
+    /// Move `share_dir/relative` to the trash, and return its id. `relative` must not be empty.
+    /// (Renaming requires the trash to be on the same filesystem as the share.)
+    pub fn put(&self, share_dir: &Path, relative: &Path) -> io::Result<u128> {
+        let original = relative
+            .to_str()
+            .filter(|relative| !relative.is_empty())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Can't trash {}", relative.display()),
+                )
+            })?;
+        let path = share_dir.join(relative);
+        // Fail early (before creating anything) if there's nothing to trash.
+        fs::symlink_metadata(&path)?;
+
+        let mut id = now_id();
+        while self.dir.join(id.to_string()).exists() {
+            id += 1;
+        }
+        let item_dir = self.dir.join(id.to_string());
+        fs::create_dir_all(&item_dir)?;
+        let result = fs::write(item_dir.join(PATH_FILE), original)
+            .and_then(|()| fs::rename(&path, item_dir.join(ITEM)));
+        if let Err(e) = result {
+            let _ = fs::remove_dir_all(&item_dir);
+            return Err(e);
+        }
+        self.cap()?;
+        Ok(id)
+    }
+
+    /// Purge the oldest items while all of them take more than the cap (if any).
+    fn cap(&self) -> io::Result<()> {
+        let Some(max_bytes) = self.cap else {
+            return Ok(());
+        };
+        let mut items = Vec::new();
+        for id in self.ids()? {
+            items.push((id, usage(&self.dir.join(id.to_string()))?));
+        }
+        let mut total: u64 = items.iter().map(|(_, size)| size).sum();
+        for (id, size) in items {
+            if total <= max_bytes {
+                break;
+            }
+            self.purge(id)?;
+            total -= size;
+        }
+        Ok(())
+    }
+
+    /// Item `id`: Its path in the trash, and its original path relative to the share.
+    fn item(&self, id: u128) -> io::Result<(PathBuf, PathBuf)> {
+        let item_dir = self.dir.join(id.to_string());
+        let relative =
+            fs::read_to_string(item_dir.join(PATH_FILE)).map_err(|e| match e.kind() {
+                io::ErrorKind::NotFound => not_found(id),
+                _ => e,
+            })?;
+        Ok((item_dir.join(ITEM), PathBuf::from(relative)))
+    }
+
+    /// Everything in the trash, newest first.
+    pub fn list(&self) -> io::Result<Vec<TrashItem>> {
+        let mut items = Vec::new();
+        for id in self.ids()? {
+            let Ok((trashed, path)) = self.item(id) else {
+                continue;
+            };
+            let is_dir = fs::symlink_metadata(trashed)?.is_dir();
+            items.push(TrashItem { id, path, is_dir });
+        }
+        items.reverse();
+        Ok(items)
+    }
+
+    /// Ids of all items, oldest first.
+    fn ids(&self) -> io::Result<Vec<u128>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut ids = Vec::new();
+        for entry in entries {
+            if let Ok(id) = entry?.file_name().to_string_lossy().parse::<u128>() {
+                ids.push(id);
+            }
+        }
+        ids.sort_unstable();
+        Ok(ids)
+    }
+
+    /// Move item `id` back to where it was deleted from (creating its parent directories, if
+    /// needed). Fail with [io::ErrorKind::AlreadyExists] if something else is there now.
+    pub fn restore(&self, share_dir: &Path, id: u128) -> io::Result<PathBuf> {
+        let (trashed, relative) = self.item(id)?;
+        let path = share_dir.join(&relative);
+        if fs::symlink_metadata(&path).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                relative.display().to_string(),
+            ));
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(trashed, path)?;
+        let item_dir = self.dir.join(id.to_string());
+        fs::remove_file(item_dir.join(PATH_FILE))?;
+        fs::remove_dir(item_dir)?;
+        Ok(relative)
+    }
+
+    /// Remove item `id` permanently.
+    pub fn purge(&self, id: u128) -> io::Result<()> {
+        fs::remove_dir_all(self.dir.join(id.to_string())).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => not_found(id),
+            _ => e,
+        })
+    }
+
+    /// Remove everything permanently.
+    pub fn purge_all(&self) -> io::Result<()> {
+        for id in self.ids()? {
+            self.purge(id)?;
+        }
+        Ok(())
+    }
+
+    /// Remove items older than `max_age` permanently. Return how many.
+    pub fn purge_expired(&self, max_age: Duration) -> io::Result<usize> {
+        let oldest = now_id().saturating_sub(max_age.as_nanos());
+        let expired: Vec<u128> = self.ids()?.into_iter().filter(|id| *id < oldest).collect();
+        for id in &expired {
+            self.purge(*id)?;
+        }
+        Ok(expired.len())
+    }
+}
@@ -0,0 +1,597 @@
+//! Resumable uploads into writable shares, by the
+//! [tus 1.0](https://tus.io/protocols/resumable-upload) protocol, with its creation, termination
+//! and expiration extensions. Under [crate::TUS], the first segment is a write hash, like under
+//! [crate::WRITE].
+//!
+//! - `POST /tus/<hash>/` with `Upload-Length`, and with `filename` in `Upload-Metadata` (a path
+//!   relative to the share, with `/` separators, in an existing directory), creates an upload. The
+//!   reply has its URL: `/tus/<hash>/<id>`.
+//! - HEAD of that URL tells `Upload-Offset`, and PATCH appends from there. Whatever an interrupted
+//!   PATCH delivered is kept, so the client resumes after it.
+//! - Once complete, the file is moved into the share. Like PUT through [crate::WRITE], the share's
+//!   method policy (of PUT) and [crate::quota] apply, and a replaced file keeps its version. An
+//!   upload with `Upload-Length: 0` is complete (and moved into the share) right when it's created.
+//! - Unfinished uploads don't reserve any of the quota: Concurrent ones can each be created for all
+//!   that remains. They are checked against the quota again when they complete, so the ones that
+//!   complete last fail then (with `507 Insufficient Storage`), rather than exceed it.
+//! - Every request checks the write hash again, like POST does: Once it's removed (or the share is
+//!   frozen), pending uploads can't go on, nor complete.
+//! - DELETE of the URL discards the upload. Uploads expire [UPLOAD_EXPIRY] after they were created
+//!   (or last appended to), and [crate::cleanup] purges them.
+//!
+//! Unfinished uploads are under [crate::UPLOADS]: The data as `<id>`, and [TusUpload] as
+//! `<id>.json`.
+
+use crate::error::{self, Error};
+use crate::manager::MAX_UPLOAD_BYTES;
+use crate::meta::ShareMeta;
+use crate::props::{log_failure, PropStore};
+use crate::quota;
+use crate::server::{accept_header, decode_tail, share_of, ShareTarget};
+use crate::versions::ShareVersions;
+use base64::Engine;
+use core::time::Duration;
+use futures_util::{Stream, StreamExt};
+use http::header::{HeaderMap, HeaderValue, CACHE_CONTROL, CONTENT_TYPE, LOCATION};
+use http::{Method, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::fs as tokio_fs;
+use tokio::io::AsyncWriteExt;
+use tokio::task;
+use warp::hyper::Body;
+use warp::path::Peek;
+use warp::{reject::Rejection, reply, Buf, Filter, Reply};
+
+/// The only version of the protocol that this supports.
+pub const TUS_VERSION: &str = "1.0.0";
+
+pub const TUS_EXTENSIONS: &str = "creation,termination,expiration";
+
+/// `Content-Type` of PATCH.
+pub const OFFSET_OCTET_STREAM: &str = "application/offset+octet-stream";
+
+/// How long an unfinished upload is kept after it was created, or last appended to.
+pub const UPLOAD_EXPIRY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// State of one upload (besides its offset, which is the size of its data).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TusUpload {
+    /// The write hash (symlink name) that created it. Only requests with the same one can go on,
+    /// and only while it still leads to the same share.
+    pub link: String,
+    /// Name of the share.
+    pub share: String,
+    /// Where the file goes, relative to the share's (top level) directory.
+    pub target: PathBuf,
+    pub length: u64,
+    /// `Upload-Metadata`, as the client sent it.
+    pub metadata: Option<String>,
+    /// Seconds since the Unix epoch.
+    pub expires: u64,
+    /// Moved into the share. Kept (until it expires) to answer HEAD of a client that missed the
+    /// last reply.
+    pub finished: bool,
+}
+
+impl TusUpload {
+    fn expires_at(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.expires)
+    }
+
+    fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires_at() <= now
+    }
+
+    fn extend(&mut self) {
+        self.expires = (SystemTime::now() + UPLOAD_EXPIRY)
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+    }
+}
+
+/// Keys and (base64-decoded) values of an `Upload-Metadata` header. A key may have no value.
+pub fn parse_metadata(header: &str) -> Result<HashMap<String, String>, Error> {
+    let invalid = || Error::BadRequest("Upload-Metadata".to_owned());
+    let mut metadata = HashMap::new();
+    for pair in header
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+    {
+        let (key, value) = pair.split_once(' ').unwrap_or((pair, ""));
+        let value = base64::engine::general_purpose::STANDARD
+            .decode(value.trim())
+            .map_err(|_| invalid())?;
+        metadata.insert(
+            key.to_owned(),
+            String::from_utf8(value).map_err(|_| invalid())?,
+        );
+    }
+    Ok(metadata)
+}
+
+/// `filename` (or, as some clients send it, `name`) from `metadata`, as a relative path without
+/// `.` nor `..`.
+fn target_of(metadata: &HashMap<String, String>) -> Result<PathBuf, Error> {
+    let name = metadata
+        .get("filename")
+        .or_else(|| metadata.get("name"))
+        .ok_or_else(|| Error::BadRequest("filename in Upload-Metadata".to_owned()))?;
+    let path = PathBuf::from(name);
+    if name.is_empty()
+        || !path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return Err(Error::InvalidName(name.to_owned()));
+    }
+    Ok(path)
+}
+
+fn new_id() -> Result<String, Error> {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes).map_err(|e| Error::Io(io::Error::other(e)))?;
+    Ok(bytes.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+fn is_id(id: &str) -> bool {
+    id.len() == 32
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+/// Ids of uploads that a request is working on, so that concurrent PATCHes don't interleave.
+static BUSY: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// An id in [BUSY], until dropped.
+struct Busy(String);
+
+impl Busy {
+    fn take(id: &str) -> Result<Self, Error> {
+        let mut busy = BUSY.lock().unwrap_or_else(PoisonError::into_inner);
+        if !busy.insert(id.to_owned()) {
+            return Err(Error::Conflict("the upload is busy".to_owned()));
+        }
+        Ok(Self(id.to_owned()))
+    }
+}
+
+impl Drop for Busy {
+    fn drop(&mut self) {
+        BUSY.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&self.0);
+    }
+}
+
+/// Where uploads are kept, and where versions of the files that they replace go.
+#[derive(Debug, Clone)]
+struct Uploads {
+    dir: PathBuf,
+    /// Like [crate::VERSIONS].
+    versions_dir: PathBuf,
+}
+
+impl Uploads {
+    fn data(&self, id: &str) -> PathBuf {
+        self.dir.join(id)
+    }
+
+    fn state(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{id}.json"))
+    }
+
+    /// Upload `id`, if `link` (of `share`) created it and it hasn't expired.
+    async fn load(&self, link: &str, share: &ShareTarget, id: &str) -> Result<TusUpload, Error> {
+        let not_found = || Error::NotFound(format!("upload {id}"));
+        if !is_id(id) {
+            return Err(not_found());
+        }
+        let json = tokio_fs::read(self.state(id))
+            .await
+            .map_err(|e| Error::from_io(e, format!("upload {id}")))?;
+        let upload: TusUpload = serde_json::from_slice(&json)
+            .map_err(|e| Error::Io(io::Error::other(format!("upload {id}: {e}"))))?;
+        if upload.link != link || upload.share != share.name {
+            return Err(not_found());
+        }
+        if upload.is_expired(SystemTime::now()) {
+            self.remove(id).await?;
+            return Err(not_found());
+        }
+        Ok(upload)
+    }
+
+    async fn save(&self, id: &str, upload: &TusUpload) -> Result<(), Error> {
+        let json = serde_json::to_vec(upload).map_err(|e| Error::Io(io::Error::other(e)))?;
+        tokio_fs::write(self.state(id), json).await?;
+        Ok(())
+    }
+
+    async fn offset(&self, id: &str, upload: &TusUpload) -> Result<u64, Error> {
+        if upload.finished {
+            return Ok(upload.length);
+        }
+        Ok(tokio_fs::metadata(self.data(id)).await?.len())
+    }
+
+    async fn remove(&self, id: &str) -> Result<(), Error> {
+        for path in [self.data(id), self.state(id)] {
+            match tokio_fs::remove_file(path).await {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Remove expired uploads (and their data) from `dir`. Return how many. Blocking.
+pub fn purge_expired(dir: &Path, now: SystemTime) -> io::Result<usize> {
+    let mut purged = 0;
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|extension| extension != "json") {
+            continue;
+        }
+        let Ok(upload) = serde_json::from_slice::<TusUpload>(&fs::read(&path)?) else {
+            continue;
+        };
+        if upload.is_expired(now) {
+            match fs::remove_file(path.with_extension("")) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+            fs::remove_file(&path)?;
+            purged += 1;
+        }
+    }
+    Ok(purged)
+}
+
+fn header<'h>(headers: &'h HeaderMap, name: &str) -> Option<&'h str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+fn number(headers: &HeaderMap, name: &str) -> Result<u64, Error> {
+    header(headers, name)
+        .and_then(|value| value.trim().parse().ok())
+        .ok_or_else(|| Error::BadRequest(name.to_owned()))
+}
+
+fn expires_header(upload: &TusUpload) -> String {
+    httpdate::fmt_http_date(upload.expires_at())
+}
+
+fn response(status: StatusCode) -> http::response::Builder {
+    Response::builder()
+        .status(status)
+        .header(CACHE_CONTROL, "no-store")
+}
+
+fn build(builder: http::response::Builder) -> Result<reply::Response, Error> {
+    builder
+        .body(Body::empty())
+        .map_err(|e| Error::Io(io::Error::other(e)))
+}
+
+/// Create an upload into `share` (which `link` is for).
+async fn create(
+    prefix_segment: &str,
+    link: &str,
+    share: ShareTarget,
+    headers: &HeaderMap,
+    uploads: &Uploads,
+    props: &PropStore,
+) -> Result<reply::Response, Error> {
+    let length = match number(headers, "upload-length") {
+        Err(_) if headers.contains_key("upload-defer-length") => {
+            return Err(Error::BadRequest(
+                "Upload-Defer-Length is not supported".to_owned(),
+            ))
+        }
+        length => length?,
+    };
+    if length > MAX_UPLOAD_BYTES {
+        return Err(Error::PayloadTooLarge {
+            limit: MAX_UPLOAD_BYTES,
+        });
+    }
+    let metadata = header(headers, "upload-metadata").map(str::to_owned);
+    let target = share.within.join(target_of(&parse_metadata(
+        metadata.as_deref().unwrap_or(""),
+    )?)?);
+
+    let path = share.dir.join(&target);
+    let parent_is_dir = match path.parent() {
+        Some(parent) => tokio_fs::metadata(parent).await.is_ok_and(|m| m.is_dir()),
+        None => false,
+    };
+    if !parent_is_dir {
+        return Err(Error::Conflict("no such directory".to_owned()));
+    }
+    if tokio_fs::symlink_metadata(&path)
+        .await
+        .is_ok_and(|m| !m.is_file())
+    {
+        return Err(Error::AlreadyExists(target.to_string_lossy().to_string()));
+    }
+    let meta = ShareMeta::load(&share.name).await?;
+    meta.policy.check(&Method::PUT, Some(length))?;
+    let remaining = quota::remaining(share.dir.clone(), meta.quota_bytes, Some(path)).await?;
+    quota::check(remaining, length)?;
+
+    let id = new_id()?;
+    let mut upload = TusUpload {
+        link: link.to_owned(),
+        share: share.name.clone(),
+        target,
+        length,
+        metadata,
+        expires: 0,
+        finished: false,
+    };
+    upload.extend();
+    tokio_fs::File::create(uploads.data(&id)).await?;
+    uploads.save(&id, &upload).await?;
+    // No PATCH would ever complete it.
+    if length == 0 {
+        if let Err(e) = finish(uploads, props, &id, &upload, &share).await {
+            uploads.remove(&id).await?;
+            return Err(e);
+        }
+        upload.finished = true;
+        uploads.save(&id, &upload).await?;
+    }
+
+    let link_segment =
+        percent_encoding::utf8_percent_encode(link, percent_encoding::NON_ALPHANUMERIC);
+    build(
+        response(StatusCode::CREATED)
+            .header(LOCATION, format!("/{prefix_segment}/{link_segment}/{id}"))
+            .header("upload-expires", expires_header(&upload)),
+    )
+}
+
+/// Append `body` to file `path`, up to `limit` bytes. What arrived before an error stays.
+async fn append(
+    body: impl Stream<Item = Result<impl Buf, warp::Error>>,
+    path: &Path,
+    limit: u64,
+) -> Result<(), Error> {
+    let mut body = Box::pin(body);
+    let mut file = tokio_fs::OpenOptions::new().append(true).open(path).await?;
+    let mut received = 0u64;
+    let result = async {
+        while let Some(chunk) = body.next().await {
+            let mut chunk = chunk.map_err(|e| Error::BadRequest(format!("request body: {e}")))?;
+            received += chunk.remaining() as u64;
+            if received > limit {
+                return Err(Error::PayloadTooLarge { limit });
+            }
+            while chunk.has_remaining() {
+                let written = file.write(chunk.chunk()).await?;
+                chunk.advance(written);
+            }
+        }
+        Ok(())
+    }
+    .await;
+    file.flush().await?;
+    result
+}
+
+/// Move the complete data of `upload` into `share` (as its write hash leads to it now), with dead
+/// properties in `props`.
+async fn finish(
+    uploads: &Uploads,
+    props: &PropStore,
+    id: &str,
+    upload: &TusUpload,
+    share: &ShareTarget,
+) -> Result<(), Error> {
+    let meta = ShareMeta::load(&share.name).await?;
+    let path = share.dir.join(&upload.target);
+    let remaining =
+        quota::remaining(share.dir.clone(), meta.quota_bytes, Some(path.clone())).await?;
+    quota::check(remaining, upload.length)?;
+    // Through the write hash (unless it leads elsewhere in the share by now).
+    let relative = upload
+        .target
+        .strip_prefix(&share.within)
+        .map(|within| Path::new(&upload.link).join(within));
+
+    let (data, upload) = (uploads.data(id), upload.clone());
+    let (name, share_dir) = (share.name.clone(), share.dir.clone());
+    let versions_dir = uploads.versions_dir.clone();
+    task::spawn_blocking(move || {
+        if fs::symlink_metadata(&path).is_ok_and(|m| !m.is_file()) {
+            return Err(Error::AlreadyExists(
+                upload.target.to_string_lossy().to_string(),
+            ));
+        }
+        ShareVersions::new(versions_dir, &name)
+            .with_cap(meta.quota_bytes)
+            .snapshot(&share_dir, &upload.target, meta.versions)?;
+        fs::rename(data, &path).map_err(|e| Error::from_io(e, "the upload's directory"))
+    })
+    .await??;
+    // A replaced file's extended attributes went with it, and so do its sidecar entries.
+    if let Ok(relative) = relative {
+        log_failure(
+            "removed",
+            relative.display(),
+            props.removed(&relative).await,
+        );
+    }
+    Ok(())
+}
+
+async fn patch(
+    uploads: &Uploads,
+    props: &PropStore,
+    link: &str,
+    share: &ShareTarget,
+    id: &str,
+    headers: &HeaderMap,
+    body: impl Stream<Item = Result<impl Buf, warp::Error>>,
+) -> Result<reply::Response, Error> {
+    let content_type = header(headers, CONTENT_TYPE.as_str()).unwrap_or_default();
+    if content_type != OFFSET_OCTET_STREAM {
+        return Err(Error::UnsupportedMediaType(content_type.to_owned()));
+    }
+    let requested = number(headers, "upload-offset")?;
+    let _busy = Busy::take(id)?;
+    let mut upload = uploads.load(link, share, id).await?;
+    let offset = uploads.offset(id, &upload).await?;
+    if requested != offset {
+        return Err(Error::Conflict(format!("the upload is at offset {offset}")));
+    }
+
+    let mut builder = response(StatusCode::NO_CONTENT);
+    if !upload.finished {
+        let appended = append(body, &uploads.data(id), upload.length - offset).await;
+        upload.extend();
+        uploads.save(id, &upload).await?;
+        appended?;
+        if uploads.offset(id, &upload).await? == upload.length {
+            finish(uploads, props, id, &upload, share).await?;
+            upload.finished = true;
+            uploads.save(id, &upload).await?;
+        } else {
+            builder = builder.header("upload-expires", expires_header(&upload));
+        }
+    }
+    let offset = uploads.offset(id, &upload).await?;
+    build(builder.header("upload-offset", offset))
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn handle(
+    prefix_segment: &str,
+    symlinks_dir: &Path,
+    uploads: &Uploads,
+    props: &PropStore,
+    method: Method,
+    tail: Peek,
+    headers: HeaderMap,
+    body: impl Stream<Item = Result<impl Buf, warp::Error>>,
+) -> Result<reply::Response, Error> {
+    if method == Method::OPTIONS {
+        return build(
+            response(StatusCode::NO_CONTENT)
+                .header("tus-extension", TUS_EXTENSIONS)
+                .header("tus-max-size", MAX_UPLOAD_BYTES),
+        );
+    }
+    if header(&headers, "tus-resumable") != Some(TUS_VERSION) {
+        return Err(Error::PreconditionFailed(format!(
+            "Tus-Resumable {TUS_VERSION}"
+        )));
+    }
+    let relative = decode_tail(&tail)?;
+    let segments: Vec<_> = relative
+        .iter()
+        .map(|segment| segment.to_string_lossy().to_string())
+        .collect();
+    let Some(link) = segments.first() else {
+        return Err(Error::NotFound("upload".to_owned()));
+    };
+
+    // On every request, not only on POST: Once the write hash is removed (or the share is frozen),
+    // pending uploads can't go on either.
+    let share = match share_of(symlinks_dir, Path::new(link)).await {
+        Some(share)
+            if tokio_fs::metadata(symlinks_dir.join(link))
+                .await
+                .is_ok_and(|m| m.is_dir()) =>
+        {
+            share
+        }
+        _ => return Err(Error::NotFound(link.to_owned())),
+    };
+
+    match (&method, &segments[1..]) {
+        (&Method::POST, []) => create(prefix_segment, link, share, &headers, uploads, props).await,
+        (&Method::HEAD, [id]) => {
+            let upload = uploads.load(link, &share, id).await?;
+            let mut builder = response(StatusCode::OK)
+                .header("upload-offset", uploads.offset(id, &upload).await?)
+                .header("upload-length", upload.length);
+            if let Some(metadata) = &upload.metadata {
+                builder = builder.header("upload-metadata", metadata);
+            }
+            if !upload.finished {
+                builder = builder.header("upload-expires", expires_header(&upload));
+            }
+            build(builder)
+        }
+        (&Method::PATCH, [id]) => patch(uploads, props, link, &share, id, &headers, body).await,
+        (&Method::DELETE, [id]) => {
+            let _busy = Busy::take(id)?;
+            uploads.load(link, &share, id).await?;
+            uploads.remove(id).await?;
+            build(response(StatusCode::NO_CONTENT))
+        }
+        (_, [] | [_]) => Err(Error::MethodNotAllowed(method.to_string())),
+        _ => Err(Error::NotFound("upload".to_owned())),
+    }
+}
+
+/// Handle [crate::TUS] (see the module docs), after the URL prefix segment `prefix_segment`. Write
+/// hashes are symlinks under `symlinks_dir`, and uploads are kept in `uploads_dir` (on the same
+/// filesystem as the shares). Versions of files that uploads replace go under `versions_dir` (like
+/// [crate::VERSIONS]), and their dead properties are in `props` (of `symlinks_dir`). Errors are
+/// replies.
+pub fn tus(
+    prefix_segment: &'static str,
+    symlinks_dir: impl Into<PathBuf>,
+    uploads_dir: impl Into<PathBuf>,
+    versions_dir: impl Into<PathBuf>,
+    props: PropStore,
+) -> impl Filter<Extract = (reply::Response,), Error = Rejection> + Clone {
+    let symlinks_dir = symlinks_dir.into();
+    let uploads = Uploads {
+        dir: uploads_dir.into(),
+        versions_dir: versions_dir.into(),
+    };
+    warp::method()
+        .and(warp::path::peek())
+        .and(warp::header::headers_cloned())
+        .and(accept_header())
+        .and(warp::body::stream())
+        .then(
+            move |method: Method, tail: Peek, headers: HeaderMap, accept: Option<String>, body| {
+                let (symlinks_dir, uploads) = (symlinks_dir.clone(), uploads.clone());
+                let props = props.clone();
+                async move {
+                    let handled = handle(
+                        prefix_segment,
+                        &symlinks_dir,
+                        &uploads,
+                        &props,
+                        method,
+                        tail,
+                        headers,
+                        body,
+                    )
+                    .await;
+                    let mut response = handled.unwrap_or_else(|e| {
+                        error::reply(&warp::reject::custom(e), accept.as_deref()).into_response()
+                    });
+                    let headers = response.headers_mut();
+                    headers.insert("tus-resumable", HeaderValue::from_static(TUS_VERSION));
+                    headers.insert("tus-version", HeaderValue::from_static(TUS_VERSION));
+                    response
+                }
+            },
+        )
+}
@@ -0,0 +1,269 @@
+//! Previous versions of files that were overwritten or deleted through [crate::WRITE]. They live
+//! under [VERSIONS], outside of [crate::DIRS], so WebDAV clients (including PROPFIND) never see
+//! them.
+//!
+//! Layout: `{VERSIONS}/{share}/{key}/{id}`, where `key` is the SHA-256 (in hex) of the file's path
+//! relative to the share, and `id` is the snapshot time in nanoseconds since the Unix epoch. A
+//! fixed-length key fits in one segment however long the path is. The path itself is in
+//! `{VERSIONS}/{share}/{key}/path`.
+//!
+//! Everything here is blocking. Async handlers call it through [tokio::task::spawn_blocking].
+
+use crate::chain::hex;
+use crate::meta::Retention;
+use crate::VERSIONS;
+use core::time::Duration;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Name of the file (next to the versions) with the path that they are versions of.
+const PATH_FILE: &str = "path";
+
+/// One previous version of a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Version {
+    /// Nanoseconds since the Unix epoch.
+    pub id: u128,
+    pub size: u64,
+}
+
+impl Version {
+    pub fn time(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_nanos(self.id as u64)
+    }
+}
+
+/// Previous versions of one file, oldest first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileVersions {
+    /// Relative to the share.
+    pub path: PathBuf,
+    pub versions: Vec<Version>,
+}
+
+/// Versions of one share's files.
+#[derive(Debug, Clone)]
+pub struct ShareVersions {
+    dir: PathBuf,
+    /// Max. total size of all versions. See [ShareVersions::with_cap].
+    cap: Option<u64>,
+}
+
+fn key(relative: &Path) -> io::Result<String> {
+    let relative = relative.to_str().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Non-UTF-8 path: {}", relative.display()),
+        )
+    })?;
+    Ok(hex(&Sha256::digest(relative.as_bytes())))
+}
+
+fn now_id() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+}
+
+/// Ids of versions in `file_dir`, oldest first. Any other files are ignored.
+fn ids(file_dir: &Path) -> io::Result<Vec<u128>> {
+    let mut ids = Vec::new();
+    for version in fs::read_dir(file_dir)? {
+        if let Ok(id) = version?.file_name().to_string_lossy().parse::<u128>() {
+            ids.push(id);
+        }
+    }
+    ids.sort_unstable();
+    Ok(ids)
+}
+
+impl ShareVersions {
+    /// Versions of `share` under `root` (instead of [VERSIONS]).
+    pub fn new(root: impl AsRef<Path>, share: &str) -> Self {
+        Self {
+            dir: root.as_ref().join(share),
+            cap: None,
+        }
+    }
+
+    /// Versions of `share` under [VERSIONS].
+    pub fn of(share: &str) -> Self {
+        Self::new(VERSIONS, share)
+    }
+
+    /// Keep the versions within `max_bytes` in total (if any), on top of their [Retention]: Every
+    /// snapshot then removes the oldest versions (of any files) that don't fit. (Versions don't
+    /// count towards the share's [quota](crate::quota), so this is what bounds them.)
+    pub fn with_cap(mut self, max_bytes: Option<u64>) -> Self {
+        self.cap = max_bytes;
+        self
+    }
+
+    fn file_dir(&self, relative: &Path) -> io::Result<PathBuf> {
+        Ok(self.dir.join(key(relative)?))
+    }
+
+    /// Keep a copy of `share_dir/relative`, if it's a file. If it's a directory, keep a copy of
+    /// every file under it. If it doesn't exist, do nothing.
+    pub fn snapshot(
+        &self,
+        share_dir: &Path,
+        relative: &Path,
+        retention: Retention,
+    ) -> io::Result<()> {
+        if retention == Retention::Off {
+            return Ok(());
+        }
+        self.keep(share_dir, relative, retention)?;
+        self.cap()
+    }
+
+    /// [ShareVersions::snapshot], without [ShareVersions::cap]ping.
+    fn keep(&self, share_dir: &Path, relative: &Path, retention: Retention) -> io::Result<()> {
+        let path = share_dir.join(relative);
+        // Don't follow symlinks: They are not user content (and they may point outside).
+        let Ok(metadata) = fs::symlink_metadata(&path) else {
+            return Ok(());
+        };
+        if metadata.is_dir() {
+            for child in fs::read_dir(&path)? {
+                let child = child?;
+                self.keep(share_dir, &relative.join(child.file_name()), retention)?;
+            }
+        } else if metadata.is_file() {
+            let file_dir = self.file_dir(relative)?;
+            fs::create_dir_all(&file_dir)?;
+            let path_file = file_dir.join(PATH_FILE);
+            if !path_file.exists() {
+                fs::write(path_file, relative.as_os_str().as_encoded_bytes())?;
+            }
+            let mut id = now_id();
+            while file_dir.join(id.to_string()).exists() {
+                id += 1;
+            }
+            fs::copy(&path, file_dir.join(id.to_string()))?;
+            self.prune(&file_dir, retention)?;
+        }
+        Ok(())
+    }
+
+    /// Remove versions in `file_dir` that `retention` doesn't keep.
+    fn prune(&self, file_dir: &Path, retention: Retention) -> io::Result<()> {
+        let ids = ids(file_dir)?;
+        let expired: Vec<u128> = match retention {
+            Retention::Off => ids,
+            Retention::KeepLast(count) => ids[..ids.len().saturating_sub(count)].to_vec(),
+            Retention::KeepDays(days) => {
+                let max_age = u128::from(days * SECONDS_PER_DAY) * 1_000_000_000;
+                let oldest = now_id().saturating_sub(max_age);
+                ids.into_iter().filter(|id| *id < oldest).collect()
+            }
+        };
+        for id in expired {
+            fs::remove_file(file_dir.join(id.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// Remove the oldest versions (of any files) while all of them take more than the cap (if
+    /// any). Every [ShareVersions::snapshot] does, too.
+    pub fn cap(&self) -> io::Result<()> {
+        let Some(max_bytes) = self.cap else {
+            return Ok(());
+        };
+        let mut versions = Vec::new();
+        let mut total = 0;
+        for file in self.list()? {
+            let file_dir = self.file_dir(&file.path)?;
+            for Version { id, size } in file.versions {
+                total += size;
+                versions.push((id, file_dir.join(id.to_string()), size));
+            }
+        }
+        versions.sort_unstable();
+        for (_, version, size) in versions {
+            if total <= max_bytes {
+                break;
+            }
+            fs::remove_file(version)?;
+            total -= size;
+        }
+        Ok(())
+    }
+
+    /// All files that have versions, sorted by their paths.
+    pub fn list(&self) -> io::Result<Vec<FileVersions>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut files = Vec::new();
+        for file_dir in entries {
+            let file_dir = file_dir?;
+            if !file_dir.file_type()?.is_dir() {
+                continue;
+            }
+            let Ok(path) = fs::read_to_string(file_dir.path().join(PATH_FILE)) else {
+                continue;
+            };
+            let mut versions = Vec::new();
+            for id in ids(&file_dir.path())? {
+                let size = fs::metadata(file_dir.path().join(id.to_string()))?.len();
+                versions.push(Version { id, size });
+            }
+            if !versions.is_empty() {
+                files.push(FileVersions {
+                    path: PathBuf::from(path),
+                    versions,
+                });
+            }
+        }
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(files)
+    }
+
+    /// Copy version `id` back to `share_dir/relative` (creating its parent directories, if
+    /// needed). The current content (if any) becomes a version first, so restoring can be undone.
+    pub fn restore(
+        &self,
+        share_dir: &Path,
+        relative: &Path,
+        id: u128,
+        retention: Retention,
+    ) -> io::Result<()> {
+        let version = self.file_dir(relative)?.join(id.to_string());
+        if !version.is_file() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No version {id} of {}", relative.display()),
+            ));
+        }
+        let path = share_dir.join(relative);
+        if fs::symlink_metadata(&path).is_ok_and(|m| m.is_dir()) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("Directory {}", relative.display()),
+            ));
+        }
+        // Keep the version that's being restored, even if retention would expire it now.
+        let restored = self.dir.join(format!(".restore-{id}"));
+        fs::copy(&version, &restored)?;
+        let result = self
+            .snapshot(share_dir, relative, retention)
+            .and_then(|()| {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::copy(&restored, &path).map(|_| ())
+            });
+        fs::remove_file(restored)?;
+        result
+    }
+}
@@ -1,3 +0,0 @@
-fn main() {
-    println!("fs_mock_entry_mock completed.");
-}
@@ -1,4 +0,0 @@
-fn main() {
-    println!("fs_mock_entry_real completed.");
-}
-                      
\ No newline at end of file
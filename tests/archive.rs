@@ -0,0 +1,79 @@
+//! [write_archive] against a temporary directory, read back.
+
+mod common;
+
+use common::{cleanup, share_dirs};
+use flate2::read::GzDecoder;
+use std::fs;
+use std::io::{Cursor, Read};
+use std::os::unix::fs::symlink;
+use std::path::Path;
+use wdav_crypto_rs::archive::{write_archive, ArchiveFormat};
+
+/// Fill a share (see [share_dirs]) with files, a sub-directory, and symlinks to a directory and to
+/// a file.
+fn fill(share: &Path) {
+    fs::create_dir(share.join("sub")).unwrap();
+    fs::write(share.join("a.txt"), "a").unwrap();
+    fs::write(share.join("sub/b.txt"), "b").unwrap();
+    symlink(share.join("sub"), share.join("need_to_know")).unwrap();
+    symlink(share.join("a.txt"), share.join("link.txt")).unwrap();
+}
+
+#[test]
+fn zip_without_symlinks() {
+    let symlinks = share_dirs("archive", "zip", fill);
+    let share = symlinks.join("s");
+    let mut zip = Vec::new();
+    write_archive(&share, "top", ArchiveFormat::Zip, &mut zip).unwrap();
+
+    let mut zip = zip::ZipArchive::new(Cursor::new(zip)).unwrap();
+    let mut names: Vec<_> = zip.file_names().map(str::to_owned).collect();
+    names.sort();
+    assert_eq!(names, ["top/", "top/a.txt", "top/sub/", "top/sub/b.txt"]);
+    let mut content = String::new();
+    zip.by_name("top/sub/b.txt")
+        .unwrap()
+        .read_to_string(&mut content)
+        .unwrap();
+    assert_eq!(content, "b");
+    cleanup(&symlinks);
+}
+
+#[test]
+fn tar_gz_without_symlinks() {
+    let symlinks = share_dirs("archive", "tar", fill);
+    let share = symlinks.join("s");
+    let mut tar_gz = Vec::new();
+    write_archive(&share, "top", ArchiveFormat::TarGz, &mut tar_gz).unwrap();
+
+    let mut tar = tar::Archive::new(GzDecoder::new(Cursor::new(tar_gz)));
+    let mut files = Vec::new();
+    for entry in tar.entries().unwrap() {
+        let mut entry = entry.unwrap();
+        let path = entry.path().unwrap().display().to_string();
+        let mut content = String::new();
+        entry.read_to_string(&mut content).unwrap();
+        files.push((path, content));
+    }
+    assert_eq!(
+        files,
+        [
+            ("top".to_owned(), String::new()),
+            ("top/a.txt".to_owned(), "a".to_owned()),
+            ("top/sub".to_owned(), String::new()),
+            ("top/sub/b.txt".to_owned(), "b".to_owned()),
+        ]
+    );
+    cleanup(&symlinks);
+}
+
+#[test]
+fn formats() {
+    assert_eq!("zip".parse::<ArchiveFormat>().unwrap(), ArchiveFormat::Zip);
+    assert_eq!(
+        "tar.gz".parse::<ArchiveFormat>().unwrap(),
+        ArchiveFormat::TarGz
+    );
+    assert!("rar".parse::<ArchiveFormat>().is_err());
+}
@@ -0,0 +1,51 @@
+//! [autoindex] against a temporary directory.
+
+mod common;
+
+use common::{cleanup, share_dirs};
+use std::fs;
+use std::os::unix::fs::symlink;
+use std::path::PathBuf;
+use wdav_crypto_rs::autoindex::autoindex;
+
+/// [share_dirs] with a file, a sub-directory with `index.html`, and a symlinked directory.
+fn dirs(test: &str) -> PathBuf {
+    share_dirs("autoindex", test, |share| {
+        fs::create_dir_all(share.join("site")).unwrap();
+        fs::write(share.join("site/index.html"), "<p>site</p>").unwrap();
+        fs::write(share.join("a.txt"), "abc").unwrap();
+        symlink(share.join("site"), share.join("need_to_know")).unwrap();
+    })
+}
+
+#[tokio::test]
+async fn list_without_symlinks() {
+    let symlinks = dirs("list");
+    let filter = autoindex(symlinks.clone());
+
+    let response = warp::test::request().path("/s/").reply(&filter).await;
+    assert_eq!(response.status(), 200);
+    let html = String::from_utf8_lossy(response.body());
+    assert!(html.contains("a.txt") && html.contains("3 bytes") && html.contains("site/"));
+    assert!(!html.contains("need_to_know"));
+    cleanup(&symlinks);
+}
+
+#[tokio::test]
+async fn leave_the_rest_to_dav() {
+    let symlinks = dirs("rest");
+    let filter = autoindex(symlinks.clone());
+
+    // index.html, no trailing slash, files, the list of shares, and methods other than GET & HEAD.
+    for path in ["/s/site/", "/s", "/s/a.txt", "/", "/missing/"] {
+        let matched = warp::test::request().path(path).matches(&filter).await;
+        assert!(!matched, "{path}");
+    }
+    let matched = warp::test::request()
+        .method("PROPFIND")
+        .path("/s/")
+        .matches(&filter)
+        .await;
+    assert!(!matched);
+    cleanup(&symlinks);
+}
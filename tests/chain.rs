@@ -0,0 +1,166 @@
+//! [WriteChain] and [verify] against temporary directories.
+
+use std::fs;
+use std::path::PathBuf;
+use wdav_crypto_rs::chain::{verify, ChainError, Verified, WriteChain, CHECKPOINT_EVERY};
+use wdav_crypto_rs::logs::{AccessRecord, Logs};
+
+const KEY: &[u8] = b"data key";
+
+/// A fresh, empty directory for the logs.
+fn dir(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("wdav_chain_{test}_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn record(method: &str, path: &str) -> AccessRecord {
+    AccessRecord {
+        time: 1_700_000_000_000,
+        client: Some("127.0.0.1".to_owned()),
+        forwarded_for: None,
+        method: method.to_owned(),
+        prefix: "write".to_owned(),
+        share: Some("evidence".to_owned()),
+        path: Some(path.to_owned()),
+        status: 201,
+        bytes_received: Some(3),
+        bytes_sent: None,
+        duration_ms: 1,
+    }
+}
+
+fn lines(chain: &WriteChain) -> Vec<String> {
+    fs::read_to_string(chain.log_path())
+        .unwrap()
+        .lines()
+        .map(str::to_owned)
+        .collect()
+}
+
+fn write_lines(chain: &WriteChain, lines: &[String]) {
+    fs::write(chain.log_path(), lines.join("\n") + "\n").unwrap();
+}
+
+#[test]
+fn detect_changes() {
+    let dir = dir("changes");
+    assert_eq!(
+        verify(&dir, KEY).unwrap(),
+        Verified {
+            records: 0,
+            checkpoints: 0,
+            after: None
+        }
+    );
+    let chain = WriteChain::new(&dir, KEY);
+    for i in 0..150 {
+        chain.append(record("PUT", &format!("{i}.txt"))).unwrap();
+    }
+    // Another (like after a restart) goes on with the same chain.
+    let chain = WriteChain::new(&dir, KEY);
+    chain.append(record("DELETE", "0.txt")).unwrap();
+    let verified = verify(&dir, KEY).unwrap();
+    assert_eq!(verified.checkpoints, 1);
+    assert_eq!(verified.records, 151 + 1);
+    let intact = lines(&chain);
+    assert!(intact[CHECKPOINT_EVERY as usize - 1].contains("\"checkpoint\""));
+
+    let mut changed = intact.clone();
+    changed[10] = changed[10].replace("\"status\":201", "\"status\":403");
+    write_lines(&chain, &changed);
+    assert!(matches!(
+        verify(&dir, KEY),
+        Err(ChainError::Broken { line: 12, .. })
+    ));
+
+    let mut removed = intact.clone();
+    removed.remove(20);
+    write_lines(&chain, &removed);
+    assert!(matches!(
+        verify(&dir, KEY),
+        Err(ChainError::Broken { line: 21, .. })
+    ));
+
+    // The last line has nothing after it, but the head.
+    let mut changed_last = intact.clone();
+    let last = changed_last.len() - 1;
+    changed_last[last] = changed_last[last].replace("0.txt", "1.txt");
+    write_lines(&chain, &changed_last);
+    assert!(matches!(verify(&dir, KEY), Err(ChainError::Head(_))));
+
+    write_lines(&chain, &intact[..140]);
+    assert!(matches!(verify(&dir, KEY), Err(ChainError::Head(_))));
+    fs::remove_file(chain.log_path()).unwrap();
+    assert!(matches!(verify(&dir, KEY), Err(ChainError::Head(_))));
+
+    write_lines(&chain, &intact);
+    assert!(verify(&dir, KEY).is_ok());
+    assert!(matches!(
+        verify(&dir, b"other key"),
+        Err(ChainError::Broken { line: 100, .. })
+    ));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+/// A log that doesn't resume (after a crash mid-line, or between a line and its head) is set aside,
+/// and the next record starts a new segment that verifies.
+#[test]
+fn new_segment() {
+    let dir = dir("segment");
+    let chain = WriteChain::new(&dir, KEY);
+    for i in 0..3 {
+        chain.append(record("PUT", &format!("{i}.txt"))).unwrap();
+    }
+    let content = fs::read_to_string(chain.log_path()).unwrap();
+    let partial = &content[..content.len() - 10];
+    fs::write(chain.log_path(), partial).unwrap();
+
+    // Like after a restart.
+    let chain = WriteChain::new(&dir, KEY);
+    chain.append(record("PUT", "3.txt")).unwrap();
+    let verified = verify(&dir, KEY).unwrap();
+    assert_eq!(verified.records, 2);
+    let after = verified.after.unwrap();
+    assert_eq!(fs::read_to_string(dir.join(&after)).unwrap(), partial);
+    assert!(lines(&chain)[0].contains("the last line is partial"));
+
+    // A line that the head doesn't sign yet.
+    let head = fs::read(chain.head_path()).unwrap();
+    chain.append(record("PUT", "4.txt")).unwrap();
+    fs::write(chain.head_path(), head).unwrap();
+    let chain = WriteChain::new(&dir, KEY);
+    chain.append(record("PUT", "5.txt")).unwrap();
+    let verified = verify(&dir, KEY).unwrap();
+    assert_eq!(verified.records, 2);
+    assert_ne!(verified.after.unwrap(), after);
+    assert!(lines(&chain)[0].contains("the head doesn't sign the last line"));
+
+    // Segments are signed, too.
+    assert!(matches!(
+        verify(&dir, b"other key"),
+        Err(ChainError::Broken { line: 1, .. })
+    ));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn only_writes() {
+    let dir = dir("writes");
+    let logs = Logs::new(&dir).chained(KEY);
+    logs.access(record("GET", "a.txt")).await;
+    logs.access(record("PROPFIND", "")).await;
+    logs.access(record("PUT", "a.txt")).await;
+    logs.access(record("MOVE", "a.txt")).await;
+
+    let chain = WriteChain::new(&dir, KEY);
+    let lines = lines(&chain);
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].contains("\"PUT\""), "{}", lines[0]);
+    assert_eq!(verify(&dir, KEY).unwrap().records, 2);
+    // Everything is in the access log, too.
+    let access = fs::read_to_string(logs.access_path()).unwrap();
+    assert_eq!(access.lines().count(), 4);
+    fs::remove_dir_all(dir).unwrap();
+}
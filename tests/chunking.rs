@@ -0,0 +1,265 @@
+//! [chunked_upload] and [purge_expired] against temporary directories.
+
+mod common;
+
+use common::{cleanup, color_props, share_dirs};
+use std::fs;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use warp::Filter;
+use wdav_crypto_rs::chunking::{chunked_upload, purge_expired, MAX_UPLOAD_FOLDERS};
+use wdav_crypto_rs::meta::ShareMeta;
+use wdav_crypto_rs::props::PropStore;
+
+/// [share_dirs] with a `sub` directory in the share, `symlinks/other` pointing to another share,
+/// and an empty `uploads` directory.
+fn dirs(test: &str) -> PathBuf {
+    let symlinks = share_dirs("chunking", test, |share| {
+        fs::create_dir(share.join("sub")).unwrap()
+    });
+    let other = symlinks
+        .with_file_name("dirs")
+        .join(format!("chunking_{test}_other"));
+    fs::create_dir(&other).unwrap();
+    symlink(&other, symlinks.join("other")).unwrap();
+    fs::create_dir(symlinks.with_file_name("uploads")).unwrap();
+    symlinks
+}
+
+/// [chunked_upload] with the directories of [dirs], and versions in a `versions` directory beside
+/// them.
+fn filter(
+    symlinks: &Path,
+) -> impl Filter<Extract = (warp::reply::Response,), Error = warp::Rejection> + Clone {
+    warp::path("uploads").and(chunked_upload(
+        "uploads",
+        "write",
+        symlinks.to_owned(),
+        symlinks.with_file_name("uploads"),
+        symlinks.with_file_name("meta"),
+        symlinks.with_file_name("versions"),
+        props(symlinks),
+    ))
+}
+
+fn props(symlinks: &Path) -> PropStore {
+    PropStore::new(symlinks, symlinks.with_file_name("props"), false)
+}
+
+fn request(method: &str, path: &str) -> warp::test::RequestBuilder {
+    warp::test::request().method(method).path(path)
+}
+
+#[tokio::test]
+async fn assemble_in_order() {
+    let symlinks = dirs("assemble");
+    let filter = filter(&symlinks);
+
+    let response = request("MKCOL", "/uploads/s/t1").reply(&filter).await;
+    assert_eq!(response.status(), 201);
+    assert_eq!(
+        request("MKCOL", "/uploads/s/t1")
+            .reply(&filter)
+            .await
+            .status(),
+        405
+    );
+    for (number, body) in [("2", "def"), ("1", "abc"), ("10", "ghij")] {
+        let path = format!("/uploads/s/t1/{number}");
+        let response = request("PUT", &path).body(body).reply(&filter).await;
+        assert_eq!(response.status(), 201, "{number}");
+    }
+    assert_eq!(
+        request("PUT", "/uploads/s/t1/01")
+            .body("x")
+            .reply(&filter)
+            .await
+            .status(),
+        400
+    );
+
+    let response = request("PROPFIND", "/uploads/s/t1")
+        .header("depth", "1")
+        .reply(&filter)
+        .await;
+    assert_eq!(response.status(), 207);
+    let xml = String::from_utf8_lossy(response.body());
+    assert!(xml.contains("<d:href>/uploads/s/t1/10</d:href>"), "{xml}");
+    assert!(
+        xml.contains("<d:getcontentlength>4</d:getcontentlength>"),
+        "{xml}"
+    );
+
+    let response = request("MOVE", "/uploads/s/t1/.file")
+        .header("destination", "/write/s/sub/big.bin")
+        .header("oc-total-length", "9")
+        .reply(&filter)
+        .await;
+    assert_eq!(response.status(), 400);
+
+    let response = request("MOVE", "/uploads/s/t1/.file")
+        .header("destination", "/write/s/sub/big.bin")
+        .header("oc-total-length", "10")
+        .reply(&filter)
+        .await;
+    assert_eq!(response.status(), 201);
+    let share = symlinks.join("s");
+    assert_eq!(
+        fs::read_to_string(share.join("sub/big.bin")).unwrap(),
+        "abcdefghij"
+    );
+    // The folder is gone.
+    assert_eq!(
+        fs::read_dir(symlinks.with_file_name("uploads").join("s"))
+            .unwrap()
+            .count(),
+        0
+    );
+    assert_eq!(
+        request("PROPFIND", "/uploads/s/t1")
+            .reply(&filter)
+            .await
+            .status(),
+        404
+    );
+    cleanup(&symlinks);
+}
+
+#[tokio::test]
+async fn replace_drops_dead_properties() {
+    let symlinks = dirs("replace");
+    let filter = filter(&symlinks);
+    fs::write(symlinks.join("s/old.bin"), "old").unwrap();
+    let old = Path::new("s/old.bin");
+    props(&symlinks)
+        .save(old, color_props("red"))
+        .await
+        .unwrap();
+
+    request("MKCOL", "/uploads/s/t1").reply(&filter).await;
+    request("PUT", "/uploads/s/t1/1")
+        .body("new")
+        .reply(&filter)
+        .await;
+    let response = request("MOVE", "/uploads/s/t1/.file")
+        .header("destination", "/write/s/old.bin")
+        .reply(&filter)
+        .await;
+    assert_eq!(response.status(), 204);
+    assert!(props(&symlinks).load(old).await.unwrap().is_empty());
+
+    // The replaced file's version.
+    let versions = symlinks
+        .with_file_name("versions")
+        .join("chunking_replace_share");
+    assert_eq!(fs::read_dir(versions).unwrap().count(), 1);
+    cleanup(&symlinks);
+}
+
+#[tokio::test]
+async fn reject_and_delete() {
+    let symlinks = dirs("reject");
+    let filter = filter(&symlinks);
+
+    assert_eq!(
+        request("MKCOL", "/uploads/missing/t1")
+            .reply(&filter)
+            .await
+            .status(),
+        404
+    );
+    assert_eq!(
+        request("MKCOL", "/uploads/s/.t1")
+            .reply(&filter)
+            .await
+            .status(),
+        400
+    );
+    request("MKCOL", "/uploads/s/t1").reply(&filter).await;
+    request("PUT", "/uploads/s/t1/1")
+        .body("abc")
+        .reply(&filter)
+        .await;
+
+    for (destination, status) in [
+        ("/write/other/a.bin", 403),
+        ("/write/s", 403),
+        ("/read/s/a.bin", 400),
+        ("/write/s/missing/a.bin", 409),
+    ] {
+        let response = request("MOVE", "/uploads/s/t1/.file")
+            .header("destination", destination)
+            .reply(&filter)
+            .await;
+        assert_eq!(response.status(), status, "{destination}");
+    }
+
+    let response = request("DELETE", "/uploads/s/t1").reply(&filter).await;
+    assert_eq!(response.status(), 204);
+    assert_eq!(
+        fs::read_dir(symlinks.with_file_name("uploads").join("s"))
+            .unwrap()
+            .count(),
+        0
+    );
+    assert_eq!(
+        request("PUT", "/uploads/s/t1/1")
+            .body("abc")
+            .reply(&filter)
+            .await
+            .status(),
+        404
+    );
+    cleanup(&symlinks);
+}
+
+#[tokio::test]
+async fn purge_expired_folders() {
+    let symlinks = dirs("purge");
+    let filter = filter(&symlinks);
+    request("MKCOL", "/uploads/s/t1").reply(&filter).await;
+    let uploads = symlinks.with_file_name("uploads");
+
+    assert_eq!(purge_expired(&uploads, SystemTime::now()).unwrap(), 0);
+    let later = SystemTime::now() + Duration::from_secs(2 * 24 * 60 * 60);
+    assert_eq!(purge_expired(&uploads, later).unwrap(), 1);
+    assert_eq!(fs::read_dir(uploads.join("s")).unwrap().count(), 0);
+    cleanup(&symlinks);
+}
+
+/// Chunks count together with what the share has already.
+#[tokio::test]
+async fn quota_and_folder_limits() {
+    let symlinks = dirs("quota");
+    let filter = filter(&symlinks);
+    fs::write(symlinks.join("s/a.txt"), [b'a'; 30]).unwrap();
+    let meta = ShareMeta {
+        quota_bytes: Some(40),
+        ..ShareMeta::default()
+    };
+    let meta_dir = symlinks.with_file_name("meta");
+    meta.save_in(&meta_dir, "chunking_quota_share")
+        .await
+        .unwrap();
+
+    request("MKCOL", "/uploads/s/t1").reply(&filter).await;
+    let put = |number: &str, size: usize| {
+        request("PUT", &format!("/uploads/s/t1/{number}")).body(vec![b'x'; size])
+    };
+    assert_eq!(put("1", 8).reply(&filter).await.status(), 201);
+    assert_eq!(put("2", 3).reply(&filter).await.status(), 507);
+    assert_eq!(put("2", 2).reply(&filter).await.status(), 201);
+
+    for i in 2..=MAX_UPLOAD_FOLDERS {
+        let path = format!("/uploads/s/t{i}");
+        assert_eq!(request("MKCOL", &path).reply(&filter).await.status(), 201);
+    }
+    let response = request("MKCOL", "/uploads/s/too_many").reply(&filter).await;
+    assert_eq!(response.status(), 409);
+    // Per write hash.
+    let response = request("MKCOL", "/uploads/other/t1").reply(&filter).await;
+    assert_eq!(response.status(), 201);
+
+    cleanup(&symlinks);
+}
@@ -0,0 +1,97 @@
+//! Fixtures shared by the integration tests. Each test crate uses only some of them.
+#![allow(dead_code)]
+
+use dav_server::fs::DavProp;
+use std::fs;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+use wdav_crypto_rs::entry::SecondaryDir;
+use wdav_crypto_rs::fs::FakeFileSystem;
+use wdav_crypto_rs::links::{self, link_name, symlink_path};
+use wdav_crypto_rs::props::{Props, StoredProp};
+use wdav_crypto_rs::{
+    DIRS, SYMLINKS, SYMLINKS_APPEND, SYMLINKS_DROP, SYMLINKS_FILE, SYMLINKS_READ, SYMLINKS_WRITE,
+    TMP, TRASH, VERSIONS,
+};
+
+/// Like the server does at startup, but with a fixed data key and salt.
+pub fn init_links() {
+    links::init("test data key", "test salt");
+}
+
+/// Empty layout: only the top level directories. (It calls [init_links].)
+pub fn layout() -> FakeFileSystem {
+    init_links();
+    FakeFileSystem::new()
+        .with_dir("/")
+        .with_dir(TMP)
+        .with_dir(DIRS)
+        .with_dir(SYMLINKS)
+        .with_dir(SYMLINKS_READ)
+        .with_dir(SYMLINKS_WRITE)
+        .with_dir(SYMLINKS_APPEND)
+        .with_dir(SYMLINKS_DROP)
+        .with_dir(SYMLINKS_FILE)
+}
+
+pub fn dir(name: &str) -> String {
+    format!("{DIRS}/{name}")
+}
+pub fn read(name: &str) -> String {
+    format!("{SYMLINKS_READ}/{name}")
+}
+/// The symlinks below are named by [link_name], like the server names them.
+pub fn write(name: &str) -> String {
+    symlink_path(SecondaryDir::Write, name)
+}
+pub fn append(name: &str) -> String {
+    symlink_path(SecondaryDir::Append, name)
+}
+pub fn drop(name: &str) -> String {
+    symlink_path(SecondaryDir::Drop, name)
+}
+pub fn file_link(name: &str) -> String {
+    symlink_path(SecondaryDir::File, name)
+}
+/// [link_name] under `under` of `name`.
+pub fn hashed(under: SecondaryDir, name: &str) -> String {
+    link_name(under, name)
+}
+
+/// A fresh share `dirs/{module}_{test}_share`, filled by `fill`, `symlinks/s` pointing to it, and
+/// empty `staging`, `meta` and `props` directories, under a per-test temporary directory. Return the
+/// `symlinks` directory. (Versions and trash of the server are under global directories, so share
+/// names are unique per test.)
+pub fn share_dirs(module: &str, test: &str, fill: impl FnOnce(&Path)) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("wdav_{module}_{test}_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    let share = root.join(format!("dirs/{module}_{test}_share"));
+    fs::create_dir_all(&share).unwrap();
+    fill(&share);
+    fs::create_dir_all(root.join("symlinks")).unwrap();
+    fs::create_dir_all(root.join("staging")).unwrap();
+    fs::create_dir_all(root.join("meta")).unwrap();
+    fs::create_dir_all(root.join("props")).unwrap();
+    symlink(&share, root.join("symlinks/s")).unwrap();
+    root.join("symlinks")
+}
+
+/// Dead properties with one property, `{urn:test}color`.
+pub fn color_props(color: &str) -> Props {
+    let prop = DavProp {
+        name: "color".to_owned(),
+        prefix: Some("x".to_owned()),
+        namespace: Some("urn:test".to_owned()),
+        xml: Some(format!(r#"<x:color xmlns:x="urn:test">{color}</x:color>"#).into_bytes()),
+    };
+    Props::from([("{urn:test}color".to_owned(), StoredProp::from(prop))])
+}
+
+/// Remove what [share_dirs] created, and the share's versions and trash (if any).
+pub fn cleanup(symlinks: &Path) {
+    let share = fs::canonicalize(symlinks.join("s")).unwrap();
+    let share = share.file_name().unwrap();
+    let _ = fs::remove_dir_all(Path::new(VERSIONS).join(share));
+    let _ = fs::remove_dir_all(Path::new(TRASH).join(share));
+    fs::remove_dir_all(symlinks.parent().unwrap()).unwrap();
+}
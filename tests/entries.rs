@@ -0,0 +1,688 @@
+//! [Entry] classification of every state, against [FakeFileSystem].
+
+mod common;
+
+use common::{append, dir, drop, file_link, hashed, layout, read, write};
+use wdav_crypto_rs::entry::{
+    Entry, EntryError, ReadAndOrWriteIncorrectKind, SecondaryDir, SecondaryIncorrectKind,
+};
+use wdav_crypto_rs::fs::{get_entries, get_entry, FakeFileSystem, FileSystem};
+use wdav_crypto_rs::{DIRS, SYMLINKS_DROP, SYMLINKS_READ, SYMLINKS_WRITE};
+
+/// Classify, and return the only entry.
+fn single_entry(fs: &impl FileSystem) -> Entry {
+    let entries = get_entries(fs).unwrap();
+    assert_eq!(entries.len(), 1, "{entries:?}");
+    entries.into_values().next().unwrap()
+}
+
+#[test]
+fn empty() {
+    assert!(get_entries(&layout()).unwrap().is_empty());
+}
+
+#[test]
+fn missing_top_level_dir_is_error() {
+    let fs = FakeFileSystem::new().with_dir(DIRS).with_dir(SYMLINKS_READ);
+    assert!(get_entries(&fs).is_err());
+}
+
+#[test]
+fn primary_only() {
+    let fs = layout().with_dir(dir("a"));
+    let entry = single_entry(&fs);
+    assert!(matches!(&entry, Entry::PrimaryOnly { name } if name == "a"));
+    assert!(!entry.is_readable());
+}
+
+#[test]
+fn primary_file_unpublished() {
+    let fs = layout().with_file(dir("a"));
+    let entry = single_entry(&fs);
+    assert!(matches!(
+        &entry,
+        Entry::PrimaryFile { name, read: false, link_name: None } if name == "a"
+    ));
+    assert!(entry.is_file());
+    assert!(!entry.is_ok_and_complete());
+}
+
+#[test]
+fn primary_non_dir() {
+    let fs = layout().with_symlink(dir("a"), "/nowhere");
+    assert!(matches!(single_entry(&fs), Entry::PrimaryNonDir { name, .. } if name == "a"));
+}
+
+#[test]
+fn primary_and_read_only() {
+    let fs = layout()
+        .with_dir(dir("a"))
+        .with_symlink(read("a"), dir("a"));
+    let entry = single_entry(&fs);
+    assert!(matches!(&entry, Entry::PrimaryAndReadOnly { name } if name == "a"));
+    assert!(entry.is_readable());
+    assert!(!entry.is_writable());
+}
+
+#[test]
+fn primary_and_read_write() {
+    let fs = layout()
+        .with_dir(dir("a"))
+        .with_symlink(read("a"), dir("a"))
+        .with_symlink(write("a"), dir("a"));
+    let entry = single_entry(&fs);
+    assert!(entry.is_ok_and_complete());
+    assert!(entry.is_writable());
+    assert_eq!(entry.write_name(), Some(&*hashed(SecondaryDir::Write, "a")));
+}
+
+#[test]
+fn primary_and_read_different_symlink() {
+    let fs = layout()
+        .with_dir(dir("a"))
+        .with_dir(dir("b"))
+        .with_symlink(read("a"), dir("b"));
+    let entries = get_entries(&fs).unwrap();
+    assert!(matches!(
+        &entries["a"],
+        Entry::PrimaryAndReadAndOrWriteIncorrect {
+            kind: ReadAndOrWriteIncorrectKind::PrimaryAndReadIncorrect {
+                read: SecondaryIncorrectKind::OrphanOrDifferentSymlink {
+                    is_orphan: false,
+                    ..
+                },
+                write: None,
+            },
+            ..
+        }
+    ));
+    assert!(matches!(&entries["b"], Entry::PrimaryOnly { .. }));
+}
+
+#[test]
+fn primary_and_read_orphan_symlink() {
+    let fs = layout()
+        .with_dir(dir("a"))
+        .with_symlink(read("a"), dir("gone"));
+    assert!(matches!(
+        single_entry(&fs),
+        Entry::PrimaryAndReadAndOrWriteIncorrect {
+            kind: ReadAndOrWriteIncorrectKind::PrimaryAndReadIncorrect {
+                read: SecondaryIncorrectKind::OrphanOrDifferentSymlink { is_orphan: true, target },
+                ..
+            },
+            ..
+        } if target == dir("gone")
+    ));
+}
+
+#[test]
+fn primary_and_read_non_symlink() {
+    let fs = layout().with_dir(dir("a")).with_dir(read("a"));
+    assert!(matches!(
+        single_entry(&fs),
+        Entry::PrimaryAndReadAndOrWriteIncorrect {
+            kind: ReadAndOrWriteIncorrectKind::PrimaryAndReadIncorrect {
+                read: SecondaryIncorrectKind::NonSymlink { is_dir: true },
+                ..
+            },
+            ..
+        }
+    ));
+}
+
+#[test]
+fn primary_and_read_ok_but_write_orphan() {
+    let fs = layout()
+        .with_dir(dir("a"))
+        .with_symlink(read("a"), dir("a"))
+        .with_symlink(write("a"), dir("gone"));
+    assert!(matches!(
+        single_entry(&fs),
+        Entry::PrimaryAndReadAndOrWriteIncorrect {
+            kind: ReadAndOrWriteIncorrectKind::PrimaryAndReadOkButWriteIncorrect {
+                write: SecondaryIncorrectKind::OrphanOrDifferentSymlink {
+                    is_orphan: true,
+                    ..
+                },
+                ..
+            },
+            ..
+        }
+    ));
+}
+
+#[test]
+fn primary_and_read_ok_but_write_non_symlink() {
+    let fs = layout()
+        .with_dir(dir("a"))
+        .with_symlink(read("a"), dir("a"))
+        .with_file(write("a"));
+    assert!(matches!(
+        single_entry(&fs),
+        Entry::PrimaryAndReadAndOrWriteIncorrect {
+            kind: ReadAndOrWriteIncorrectKind::PrimaryAndReadOkButWriteIncorrect {
+                write: SecondaryIncorrectKind::NonSymlink { is_dir: false },
+                ..
+            },
+            ..
+        }
+    ));
+}
+
+#[test]
+fn primary_and_write_only() {
+    let fs = layout()
+        .with_dir(dir("a"))
+        .with_symlink(write("a"), dir("a"));
+    let entry = single_entry(&fs);
+    assert!(matches!(
+        &entry,
+        Entry::PrimaryAndReadAndOrWriteIncorrect {
+            kind: ReadAndOrWriteIncorrectKind::PrimaryAndWriteOnly { write_name },
+            ..
+        } if *write_name == hashed(SecondaryDir::Write, "a")
+    ));
+    assert!(!entry.is_writable());
+}
+
+#[test]
+fn primary_and_write_only_and_incorrect() {
+    let fs = layout().with_dir(dir("a")).with_dir(write("a"));
+    assert!(matches!(
+        single_entry(&fs),
+        Entry::PrimaryAndReadAndOrWriteIncorrect {
+            kind: ReadAndOrWriteIncorrectKind::PrimaryAndWriteOnlyAndIncorrect {
+                write: SecondaryIncorrectKind::NonSymlink { is_dir: true },
+                ..
+            },
+            ..
+        }
+    ));
+}
+
+#[test]
+fn secondary_read_orphan() {
+    let fs = layout().with_symlink(read("a"), dir("a"));
+    assert!(matches!(
+        single_entry(&fs),
+        Entry::SecondaryIncorrect {
+            under: SecondaryDir::Read,
+            kind: SecondaryIncorrectKind::OrphanOrDifferentSymlink {
+                is_orphan: true,
+                ..
+            },
+            ..
+        }
+    ));
+}
+
+#[test]
+fn secondary_write_non_symlink() {
+    let fs = layout().with_file(write("a"));
+    assert!(matches!(
+        single_entry(&fs),
+        Entry::SecondaryIncorrect {
+            under: SecondaryDir::Write,
+            kind: SecondaryIncorrectKind::NonSymlink { is_dir: false },
+            ..
+        }
+    ));
+}
+
+#[test]
+fn primary_and_read_incorrect_and_write() {
+    let fs = layout()
+        .with_dir(dir("a"))
+        .with_file(read("a"))
+        .with_symlink(write("a"), dir("a"));
+    assert!(matches!(
+        single_entry(&fs),
+        Entry::PrimaryAndReadAndOrWriteIncorrect {
+            kind: ReadAndOrWriteIncorrectKind::PrimaryAndReadIncorrect {
+                read: SecondaryIncorrectKind::NonSymlink { is_dir: false },
+                write: Some((_, Ok(()))),
+            },
+            ..
+        }
+    ));
+}
+
+#[test]
+fn secondaries_read_and_write_orphans() {
+    let fs = layout()
+        .with_symlink(read("a"), dir("a"))
+        .with_symlink(write("a"), dir("a"));
+    let entry = single_entry(&fs);
+    assert!(matches!(
+        &entry,
+        Entry::SecondariesIncorrect {
+            read: Some(SecondaryIncorrectKind::OrphanOrDifferentSymlink {
+                is_orphan: true,
+                ..
+            }),
+            write: Some(SecondaryIncorrectKind::OrphanOrDifferentSymlink {
+                is_orphan: true,
+                ..
+            }),
+            drop: None,
+            ..
+        }
+    ));
+    assert_eq!(entry.write_name(), None);
+}
+
+#[test]
+fn primary_non_dir_ignores_symlinks() {
+    let fs = layout()
+        .with_symlink(dir("a"), "/nowhere")
+        .with_symlink(read("a"), dir("a"))
+        .with_symlink(write("a"), dir("a"))
+        .with_symlink(file_link("a"), dir("a"));
+    assert!(matches!(single_entry(&fs), Entry::PrimaryNonDir { .. }));
+}
+
+#[test]
+fn primary_file_read_and_link() {
+    let fs = layout()
+        .with_file(dir("a"))
+        .with_symlink(read("a"), dir("a"))
+        .with_symlink(file_link("a"), dir("a"));
+    let entry = single_entry(&fs);
+    assert!(matches!(&entry, Entry::PrimaryFile { read: true, .. }));
+    assert!(entry.is_ok_and_complete());
+    assert!(entry.is_readable());
+    assert!(!entry.is_writable());
+    assert_eq!(entry.link_name(), Some(&*hashed(SecondaryDir::File, "a")));
+}
+
+#[test]
+fn primary_file_link_only() {
+    let fs = layout()
+        .with_file(dir("a"))
+        .with_symlink(file_link("a"), dir("a"));
+    let entry = single_entry(&fs);
+    assert!(entry.is_ok_and_complete());
+    assert!(!entry.is_readable());
+    assert_eq!(entry.link_name(), Some(&*hashed(SecondaryDir::File, "a")));
+}
+
+/// Files can't be written to (nor appended to, nor dropped into).
+#[test]
+fn primary_file_and_write() {
+    let fs = layout()
+        .with_file(dir("a"))
+        .with_symlink(read("a"), dir("a"))
+        .with_symlink(write("a"), dir("a"));
+    let entry = single_entry(&fs);
+    assert!(matches!(
+        &entry,
+        Entry::PrimaryFileIncorrect { read: true, incorrect, .. }
+            if matches!(incorrect[..], [(SecondaryDir::Write, Ok(()))])
+    ));
+    assert!(!entry.is_ok_and_complete());
+    assert!(!entry.is_readable());
+    assert!(entry.is_file());
+}
+
+#[test]
+fn primary_file_and_link_orphan() {
+    let fs = layout()
+        .with_file(dir("a"))
+        .with_symlink(file_link("a"), dir("b"));
+    assert!(matches!(
+        single_entry(&fs),
+        Entry::PrimaryFileIncorrect { link_name: None, incorrect, .. }
+            if matches!(
+                incorrect[..],
+                [(SecondaryDir::File, Err(SecondaryIncorrectKind::OrphanOrDifferentSymlink { is_orphan: true, .. }))]
+            )
+    ));
+}
+
+/// File links are for single files only.
+#[test]
+fn primary_dir_and_file_link() {
+    let fs = layout()
+        .with_dir(dir("a"))
+        .with_symlink(read("a"), dir("a"))
+        .with_symlink(file_link("a"), dir("a"));
+    let entry = single_entry(&fs);
+    assert!(matches!(
+        &entry,
+        Entry::FileLinkIncorrect { link: Ok(()), also, .. }
+            if matches!(**also, Entry::PrimaryAndReadOnly { .. })
+    ));
+    assert!(!entry.is_readable());
+}
+
+/// A symlink with the plain share name (from before links were hashed) besides the hashed one.
+#[test]
+fn duplicate_write_links() {
+    let fs = layout()
+        .with_dir(dir("a"))
+        .with_symlink(read("a"), dir("a"))
+        .with_symlink(write("a"), dir("a"))
+        .with_symlink(format!("{SYMLINKS_WRITE}/a"), dir("a"));
+    let is_duplicate = |entry: &Entry| {
+        matches!(
+            entry,
+            Entry::DuplicateLinksIncorrect { under: SecondaryDir::Write, link_names, also, .. }
+                if link_names == &["a"]
+                    && also.write_name() == Some(&*hashed(SecondaryDir::Write, "a"))
+        )
+    };
+    let entry = single_entry(&fs);
+    assert!(is_duplicate(&entry), "{entry:?}");
+    assert!(!entry.is_ok_and_complete());
+    assert!(!entry.is_writable());
+    assert!(is_duplicate(&get_entry(&fs, "a").unwrap().unwrap()));
+}
+
+/// Later symlinks still classify what [Entry::DuplicateLinksIncorrect] wraps.
+#[test]
+fn duplicate_drop_links_and_file_link() {
+    let fs = layout()
+        .with_dir(dir("a"))
+        .with_symlink(drop("a"), dir("a"))
+        .with_symlink(format!("{SYMLINKS_DROP}/a"), dir("a"))
+        .with_symlink(file_link("a"), dir("a"));
+    for entry in [single_entry(&fs), get_entry(&fs, "a").unwrap().unwrap()] {
+        assert!(
+            matches!(
+                &entry,
+                Entry::DuplicateLinksIncorrect { under: SecondaryDir::Drop, also, .. }
+                    if matches!(**also, Entry::FileLinkIncorrect { .. })
+            ),
+            "{entry:?}"
+        );
+    }
+}
+
+#[test]
+fn secondary_file_link_orphan() {
+    let fs = layout().with_symlink(file_link("a"), dir("a"));
+    assert!(matches!(
+        single_entry(&fs),
+        Entry::SecondaryIncorrect {
+            under: SecondaryDir::File,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn writable_symlink_twice_is_error() {
+    let fs = layout()
+        .with_dir(dir("a"))
+        .with_symlink(read("a"), dir("a"))
+        .with_symlink(write("a"), dir("a"));
+    let entry = single_entry(&fs);
+    assert!(matches!(
+        entry.and_writable_symlink(&fs, write("a").into()),
+        Err(EntryError::UnexpectedVariant { .. })
+    ));
+}
+
+#[test]
+fn primary_and_drop_only() {
+    let fs = layout()
+        .with_dir(dir("a"))
+        .with_symlink(drop("a"), dir("a"));
+    let entry = single_entry(&fs);
+    assert!(entry.is_ok_and_complete());
+    assert!(entry.is_droppable());
+    assert!(!entry.is_readable());
+    assert!(!entry.is_writable());
+    assert_eq!(entry.drop_name(), Some(&*hashed(SecondaryDir::Drop, "a")));
+}
+
+#[test]
+fn primary_and_drop_different_symlink() {
+    let fs = layout()
+        .with_dir(dir("a"))
+        .with_dir(dir("b"))
+        .with_symlink(drop("a"), dir("b"));
+    let entries = get_entries(&fs).unwrap();
+    assert!(matches!(
+        &entries["a"],
+        Entry::PrimaryAndDropIncorrect {
+            drop: Err(SecondaryIncorrectKind::OrphanOrDifferentSymlink {
+                is_orphan: false,
+                ..
+            }),
+            also: None,
+            ..
+        }
+    ));
+}
+
+/// A drop box must not be readable, otherwise uploaders could see each other's files.
+#[test]
+fn primary_and_read_and_drop() {
+    let fs = layout()
+        .with_dir(dir("a"))
+        .with_symlink(read("a"), dir("a"))
+        .with_symlink(drop("a"), dir("a"));
+    let entry = single_entry(&fs);
+    assert!(matches!(
+        &entry,
+        Entry::PrimaryAndDropIncorrect {
+            drop: Ok(()),
+            also: Some(also),
+            ..
+        } if matches!(**also, Entry::PrimaryAndReadOnly { .. })
+    ));
+    assert!(!entry.is_ok_and_complete());
+    assert!(!entry.is_readable());
+}
+
+#[test]
+fn secondary_drop_orphan() {
+    let fs = layout().with_symlink(drop("a"), dir("a"));
+    assert!(matches!(
+        single_entry(&fs),
+        Entry::SecondaryIncorrect {
+            under: SecondaryDir::Drop,
+            kind: SecondaryIncorrectKind::OrphanOrDifferentSymlink {
+                is_orphan: true,
+                ..
+            },
+            ..
+        }
+    ));
+}
+
+#[test]
+fn secondaries_write_and_drop() {
+    let fs = layout()
+        .with_file(write("a"))
+        .with_symlink(drop("a"), dir("a"));
+    // Without a primary or a readable symlink, nothing tells that both are for "a": Each is an
+    // entry of its own, by its hashed name.
+    let entries = get_entries(&fs).unwrap();
+    assert_eq!(entries.len(), 2);
+    assert!(matches!(
+        &entries[&hashed(SecondaryDir::Write, "a")],
+        Entry::SecondaryIncorrect {
+            under: SecondaryDir::Write,
+            kind: SecondaryIncorrectKind::NonSymlink { is_dir: false },
+            ..
+        }
+    ));
+    assert!(matches!(
+        &entries[&hashed(SecondaryDir::Drop, "a")],
+        Entry::SecondaryIncorrect {
+            under: SecondaryDir::Drop,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn primary_and_read_append() {
+    let fs = layout()
+        .with_dir(dir("a"))
+        .with_symlink(read("a"), dir("a"))
+        .with_symlink(append("a"), dir("a"));
+    let entry = single_entry(&fs);
+    assert!(entry.is_ok_and_complete());
+    assert!(entry.is_readable());
+    assert!(entry.is_appendable());
+    assert!(!entry.is_writable());
+    assert_eq!(
+        entry.append_name(),
+        Some(&*hashed(SecondaryDir::Append, "a"))
+    );
+    assert_eq!(entry.write_name(), None);
+}
+
+/// Append-only must not be combined with full write access.
+#[test]
+fn primary_and_read_write_and_append() {
+    let fs = layout()
+        .with_dir(dir("a"))
+        .with_symlink(read("a"), dir("a"))
+        .with_symlink(write("a"), dir("a"))
+        .with_symlink(append("a"), dir("a"));
+    let entry = single_entry(&fs);
+    assert!(matches!(
+        &entry,
+        Entry::PrimaryAndAppendIncorrect {
+            append: Ok(()),
+            also,
+            ..
+        } if matches!(**also, Entry::PrimaryAndReadWrite { .. })
+    ));
+    assert!(!entry.is_ok_and_complete());
+    assert!(!entry.is_writable());
+}
+
+#[test]
+fn primary_and_append_only() {
+    let fs = layout()
+        .with_dir(dir("a"))
+        .with_symlink(append("a"), dir("a"));
+    let entry = single_entry(&fs);
+    assert!(matches!(
+        &entry,
+        Entry::PrimaryAndAppendIncorrect { also, .. } if matches!(**also, Entry::PrimaryOnly { .. })
+    ));
+    assert!(!entry.is_appendable());
+}
+
+#[test]
+fn secondaries_read_and_append() {
+    let fs = layout()
+        .with_symlink(read("a"), dir("a"))
+        .with_file(append("a"));
+    assert!(matches!(
+        single_entry(&fs),
+        Entry::SecondariesIncorrect {
+            read: Some(_),
+            write: None,
+            append: Some(SecondaryIncorrectKind::NonSymlink { is_dir: false }),
+            drop: None,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn relative_symlink_target_resolves() {
+    let fs = layout()
+        .with_dir(dir("b"))
+        .with_symlink(read("a"), "../../wdav_dirs/b");
+    assert!(fs.exists(read("a").as_ref()));
+    assert!(fs.is_dir(read("a").as_ref()));
+}
+
+#[test]
+fn nested_read_write() {
+    let fs = layout()
+        .with_dir(dir("p"))
+        .with_dir(dir("p/in"))
+        .with_symlink(read("n"), dir("p/in"))
+        .with_symlink(write("n"), dir("p/in"));
+    let entries = get_entries(&fs).unwrap();
+    assert_eq!(entries.len(), 2);
+    let entry = &entries["n"];
+    assert!(matches!(entry, Entry::Nested { path, read: true, .. } if path.as_os_str() == "p/in"));
+    assert!(entry.is_ok_and_complete());
+    assert!(entry.is_readable());
+    assert_eq!(entry.write_name(), Some(&*hashed(SecondaryDir::Write, "n")));
+    assert!(matches!(entries["p"], Entry::PrimaryOnly { .. }));
+}
+
+#[test]
+fn nested_write_only() {
+    let fs = layout()
+        .with_dir(dir("p"))
+        .with_dir(dir("p/in"))
+        .with_symlink(write("n"), dir("p/in"));
+    let entries = get_entries(&fs).unwrap();
+    // Without a readable symlink, nothing tells its name.
+    let entry = &entries[&hashed(SecondaryDir::Write, "n")];
+    assert!(matches!(
+        entry,
+        Entry::Nested {
+            read: false,
+            write_name: Some(_),
+            ..
+        }
+    ));
+    assert!(!entry.is_ok_and_complete());
+    assert_eq!(entry.write_name(), None);
+}
+
+#[test]
+fn nested_read_and_write_elsewhere() {
+    let fs = layout()
+        .with_dir(dir("p"))
+        .with_dir(dir("p/in"))
+        .with_dir(dir("p/other"))
+        .with_symlink(read("n"), dir("p/in"))
+        .with_symlink(write("n"), dir("p/other"));
+    let entries = get_entries(&fs).unwrap();
+    assert!(matches!(
+        &entries["n"],
+        Entry::NestedIncorrect { read: true, write_name: None, incorrect, .. }
+            if matches!(incorrect[..], [(SecondaryDir::Write, Err(_))])
+    ));
+}
+
+/// Nested publications are readable or writable, but not append-only.
+#[test]
+fn nested_and_append() {
+    let fs = layout()
+        .with_dir(dir("p"))
+        .with_dir(dir("p/in"))
+        .with_symlink(read("n"), dir("p/in"))
+        .with_symlink(append("n"), dir("p/in"));
+    let entries = get_entries(&fs).unwrap();
+    assert!(matches!(
+        &entries["n"],
+        Entry::NestedIncorrect { incorrect, .. }
+            if matches!(incorrect[..], [(SecondaryDir::Append, Ok(()))])
+    ));
+    assert!(!entries["n"].is_readable());
+}
+
+#[test]
+fn nested_missing_dir_is_incorrect() {
+    let fs = layout()
+        .with_dir(dir("p"))
+        .with_symlink(read("n"), dir("p/in"));
+    let entries = get_entries(&fs).unwrap();
+    assert!(matches!(
+        &entries["n"],
+        Entry::SecondaryIncorrect {
+            under: SecondaryDir::Read,
+            kind: SecondaryIncorrectKind::OrphanOrDifferentSymlink {
+                is_orphan: true,
+                ..
+            },
+            ..
+        }
+    ));
+}
@@ -0,0 +1,188 @@
+//! Mapping of [Error] and warp's rejections to status codes and bodies.
+
+use http::StatusCode;
+use warp::Filter;
+use wdav_crypto_rs::error::Error;
+use wdav_crypto_rs::isolation::Isolation;
+use wdav_crypto_rs::server::{
+    check_name, dav_append, dav_drop, method_policy, no_overwrite, recover_by_accept,
+};
+
+fn routes() -> impl Filter<Extract = (Box<dyn warp::Reply>,), Error = warp::Rejection> + Clone {
+    let exists = warp::path("exists").and_then(|| async {
+        Err::<String, _>(warp::reject::custom(Error::AlreadyExists("x".to_owned())))
+    });
+    let quota = warp::path("quota")
+        .and_then(|| async { Err::<String, _>(warp::reject::custom(Error::QuotaExceeded)) });
+    let post = warp::path("post").and(warp::post()).map(|| "posted");
+    recover_by_accept(exists.or(quota).or(post))
+}
+
+#[tokio::test]
+async fn already_exists_is_conflict_in_html() {
+    let res = warp::test::request()
+        .path("/exists")
+        .header("accept", "text/html")
+        .reply(&routes())
+        .await;
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+    assert_eq!(res.headers()["content-type"], "text/html; charset=utf-8");
+    assert!(String::from_utf8_lossy(res.body()).contains("Already exists: x."));
+}
+
+#[tokio::test]
+async fn quota_exceeded_in_json() {
+    let res = warp::test::request()
+        .path("/quota")
+        .header("accept", "application/json")
+        .reply(&routes())
+        .await;
+    assert_eq!(res.status(), StatusCode::INSUFFICIENT_STORAGE);
+    let body: serde_json::Value = serde_json::from_slice(res.body()).unwrap();
+    assert_eq!(body["status"], 507);
+    assert_eq!(body["error"], "Quota exceeded.");
+}
+
+#[tokio::test]
+async fn unknown_path_is_not_found() {
+    let res = warp::test::request()
+        .path("/nothing/here")
+        .reply(&routes())
+        .await;
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn wrong_method_is_method_not_allowed() {
+    let res = warp::test::request()
+        .method("GET")
+        .path("/post")
+        .reply(&routes())
+        .await;
+    assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+}
+
+#[tokio::test]
+async fn success_passes_through() {
+    let res = warp::test::request()
+        .method("POST")
+        .path("/post")
+        .reply(&routes())
+        .await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.body(), "posted");
+}
+
+#[test]
+fn names() {
+    assert!(check_name("project").is_ok());
+    assert!(check_name("my project.v2").is_ok());
+    for name in ["", ".", "..", "a/b", "a\\b", "a\0b"] {
+        assert!(
+            matches!(check_name(name), Err(Error::InvalidName(_))),
+            "{name:?}"
+        );
+    }
+}
+
+/// A fresh directory with `hash/existing` in it (like one symlink directory with one share).
+fn share_with_existing_file(test: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("wdav_test_{test}_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("hash")).unwrap();
+    std::fs::write(dir.join("hash/existing"), "old").unwrap();
+    dir
+}
+
+#[tokio::test]
+async fn no_overwrite_rejects_put_of_existing() {
+    let dir = share_with_existing_file("no_overwrite");
+    // Behind another filter that looks at the path (as in the server), too.
+    let filter = recover_by_accept(
+        method_policy(dir.clone(), dir.join("meta"))
+            .and(no_overwrite(dir.clone()))
+            .map(|| "passed"),
+    );
+
+    let put = |path: &'static str| warp::test::request().method("PUT").path(path);
+    assert_eq!(
+        put("/hash/existing").reply(&filter).await.status(),
+        StatusCode::PRECONDITION_FAILED
+    );
+    assert_eq!(
+        put("/hash/new").reply(&filter).await.status(),
+        StatusCode::OK
+    );
+    assert_eq!(
+        put("/hash/%2E%2E/hash/existing")
+            .reply(&filter)
+            .await
+            .status(),
+        StatusCode::BAD_REQUEST
+    );
+    let get = warp::test::request().path("/hash/existing");
+    assert_eq!(get.reply(&filter).await.status(), StatusCode::OK);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn drop_box_rejects_put_of_existing() {
+    let dir = share_with_existing_file("drop_overwrite");
+    let filter = recover_by_accept(warp::path("drop").and(dav_drop("drop", dir.clone())));
+
+    let put = |path: &'static str| warp::test::request().method("PUT").path(path).body("new");
+    assert_eq!(
+        put("/drop/hash/existing").reply(&filter).await.status(),
+        StatusCode::PRECONDITION_FAILED
+    );
+    assert_eq!(
+        std::fs::read_to_string(dir.join("hash/existing")).unwrap(),
+        "old"
+    );
+    assert_eq!(
+        put("/drop/hash/new").reply(&filter).await.status(),
+        StatusCode::CREATED
+    );
+    assert_eq!(
+        std::fs::read_to_string(dir.join("hash/new")).unwrap(),
+        "new"
+    );
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn append_only_creates_new_files_only() {
+    let dir = share_with_existing_file("append_overwrite");
+    let filter = recover_by_accept(warp::path("append").and(dav_append(
+        "append",
+        dir.clone(),
+        Isolation::default(),
+    )));
+
+    let put = |path: &'static str| warp::test::request().method("PUT").path(path).body("new");
+    assert_eq!(
+        put("/append/hash/existing").reply(&filter).await.status(),
+        StatusCode::PRECONDITION_FAILED
+    );
+    // Behind the method policy, which looks at the path, too.
+    assert_eq!(
+        put("/append/hash/new").reply(&filter).await.status(),
+        StatusCode::CREATED
+    );
+    assert_eq!(
+        std::fs::read_to_string(dir.join("hash/new")).unwrap(),
+        "new"
+    );
+    let delete = warp::test::request()
+        .method("DELETE")
+        .path("/append/hash/existing");
+    assert_eq!(
+        delete.reply(&filter).await.status(),
+        StatusCode::METHOD_NOT_ALLOWED
+    );
+    assert!(dir.join("hash/existing").exists());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,201 @@
+//! [extract], [merge] and [extract_upload] against temporary directories.
+
+use std::fs;
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+use wdav_crypto_rs::archive::ArchiveFormat;
+use wdav_crypto_rs::error::Error;
+use wdav_crypto_rs::extract::{extract, extract_upload, merge, ExtractLimits};
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+/// A fresh per-test temporary directory, with an empty `unpacked` directory in it.
+fn root(test: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("wdav_extract_{test}_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("unpacked")).unwrap();
+    root
+}
+
+/// A zip archive (as `root/archive.zip`) with entries `names`: Directories end with `/`, symlinks
+/// with `@`, and files contain their own names.
+fn zip_with(root: &Path, names: &[&str]) -> PathBuf {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default();
+    for name in names {
+        if name.ends_with('/') {
+            zip.add_directory(*name, options).unwrap();
+        } else if let Some(link) = name.strip_suffix('@') {
+            zip.add_symlink(link, "/etc/passwd", options).unwrap();
+        } else {
+            zip.start_file(*name, options).unwrap();
+            zip.write_all(name.as_bytes()).unwrap();
+        }
+    }
+    let path = root.join("archive.zip");
+    fs::write(&path, zip.finish().unwrap().into_inner()).unwrap();
+    path
+}
+
+/// A tar archive (as `root/archive.tar`) with files named `names` (taken as-is, even if unsafe),
+/// each with `size` bytes.
+fn tar_with(root: &Path, names: &[&str], size: usize) -> PathBuf {
+    let mut tar = tar::Builder::new(Vec::new());
+    for name in names {
+        let mut header = tar::Header::new_gnu();
+        header.as_gnu_mut().unwrap().name[..name.len()].copy_from_slice(name.as_bytes());
+        header.set_size(size as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        tar.append(&header, &vec![b'x'; size][..]).unwrap();
+    }
+    let path = root.join("archive.tar");
+    fs::write(&path, tar.into_inner().unwrap()).unwrap();
+    path
+}
+
+#[test]
+fn extract_zip_without_symlinks() {
+    let root = root("zip");
+    let archive = zip_with(
+        &root,
+        &["top/", "top/a.txt", "./top/sub/b.txt", "top/link@"],
+    );
+    let unpacked = root.join("unpacked");
+
+    let extracted = extract(&archive, ArchiveFormat::Zip, &unpacked, &Default::default()).unwrap();
+    assert_eq!((extracted.files, extracted.directories), (2, 1));
+    assert_eq!(extracted.skipped, ["top/link"]);
+    assert_eq!(
+        fs::read(unpacked.join("top/sub/b.txt")).unwrap(),
+        b"./top/sub/b.txt"
+    );
+    assert!(!unpacked.join("top/link").exists());
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn reject_unsafe_paths() {
+    let root = root("unsafe");
+    let unpacked = root.join("unpacked");
+    let zip = zip_with(&root, &["ok.txt", "../evil.txt"]);
+    let tar = tar_with(&root, &["/etc/evil"], 1);
+
+    for (archive, format) in [(zip, ArchiveFormat::Zip), (tar, ArchiveFormat::Tar)] {
+        let result = extract(&archive, format, &unpacked, &Default::default());
+        assert!(matches!(result, Err(Error::BadRequest(_))), "{format:?}");
+    }
+    assert!(!root.join("evil.txt").exists());
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn limits() {
+    let root = root("limits");
+    let unpacked = root.join("unpacked");
+    let archive = tar_with(&root, &["a", "b", "c"], 10);
+    let limits = |max_entries, max_bytes, quota| ExtractLimits {
+        max_entries,
+        max_bytes,
+        quota,
+    };
+
+    let extract = |limits: ExtractLimits| {
+        extract(&archive, ArchiveFormat::Tar, &unpacked, &limits).map(|e| e.bytes)
+    };
+    assert_eq!(extract(limits(3, 30, Some(30))).unwrap(), 30);
+    assert!(matches!(
+        extract(limits(2, 30, None)),
+        Err(Error::BadRequest(_))
+    ));
+    assert!(matches!(
+        extract(limits(3, 29, Some(100))),
+        Err(Error::PayloadTooLarge { limit: 29 })
+    ));
+    assert!(matches!(
+        extract(limits(3, 100, Some(29))),
+        Err(Error::QuotaExceeded)
+    ));
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn merge_into_existing() {
+    let root = root("merge");
+    let (from, into) = (root.join("unpacked"), root.join("into"));
+    fs::create_dir_all(from.join("sub/new_dir")).unwrap();
+    fs::write(from.join("sub/a.txt"), "new").unwrap();
+    fs::write(from.join("sub/new_dir/b.txt"), "b").unwrap();
+    fs::create_dir_all(into.join("sub")).unwrap();
+    fs::write(into.join("sub/a.txt"), "old!").unwrap();
+    fs::write(into.join("kept.txt"), "kept").unwrap();
+
+    let mut replaced = Vec::new();
+    merge(&from, &into, &mut |relative| {
+        replaced.push(relative.to_owned());
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!(replaced, [PathBuf::from("sub/a.txt")]);
+    assert_eq!(fs::read_to_string(into.join("sub/a.txt")).unwrap(), "new");
+    assert!(into.join("sub/new_dir/b.txt").is_file());
+    assert!(into.join("kept.txt").is_file());
+
+    // A file can't replace a directory: Nothing moves.
+    fs::create_dir_all(from.join("kept.txt")).unwrap();
+    fs::write(from.join("z.txt"), "z").unwrap();
+    let result = merge(&from, &into, &mut |_| Ok(()));
+    assert!(matches!(result, Err(Error::AlreadyExists(_))));
+    assert!(!into.join("z.txt").exists());
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[tokio::test]
+async fn upload_with_header_only() {
+    let root = root("upload");
+    let share = root.join("dirs/extract_upload_share");
+    fs::create_dir_all(&share).unwrap();
+    fs::create_dir_all(root.join("symlinks")).unwrap();
+    std::os::unix::fs::symlink(&share, root.join("symlinks/s")).unwrap();
+    let archive = fs::read(tar_with(&root, &["dir/a.txt"], 3)).unwrap();
+    let limits = ExtractLimits {
+        max_bytes: 5,
+        ..ExtractLimits::default()
+    };
+    let filter = extract_upload(root.join("symlinks"), root.join("unpacked"), limits);
+
+    let response = warp::test::request()
+        .method("PUT")
+        .path("/s/")
+        .header("x-extract", "tar")
+        .body(archive.clone())
+        .reply(&filter)
+        .await;
+    assert_eq!(response.status(), 201);
+    let summary: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(summary["files"], 1);
+    assert_eq!(fs::read(share.join("dir/a.txt")).unwrap(), b"xxx");
+    // Nothing left behind in staging.
+    assert_eq!(fs::read_dir(root.join("unpacked")).unwrap().count(), 0);
+
+    let too_large = fs::read(tar_with(&root, &["b.txt"], 6)).unwrap();
+    let response = warp::test::request()
+        .method("PUT")
+        .path("/s/")
+        .header("x-extract", "tar")
+        .body(too_large)
+        .reply(&filter)
+        .await;
+    assert_eq!(response.status(), 413);
+    assert!(!share.join("b.txt").exists());
+
+    // Without the header (or the query parameter), it's a plain PUT.
+    let matched = warp::test::request()
+        .method("PUT")
+        .path("/s/b.tar")
+        .body(archive)
+        .matches(&filter)
+        .await;
+    assert!(!matched);
+    fs::remove_dir_all(root).unwrap();
+}
@@ -0,0 +1,46 @@
+//! Single-file shares: [single_file] against a temporary directory, and [content_disposition].
+
+use std::fs;
+use std::os::unix::fs::symlink;
+use std::path::PathBuf;
+use wdav_crypto_rs::server::{content_disposition, single_file};
+
+/// A fresh directory of symlinks (`f` to a file, `d` to a directory with `d/g.txt`) under a
+/// per-test temporary directory.
+fn symlinks_dir(test: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("wdav_files_{test}_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("dirs/d")).unwrap();
+    fs::write(root.join("dirs/file name.txt"), "f").unwrap();
+    fs::write(root.join("dirs/d/g.txt"), "g").unwrap();
+    fs::create_dir_all(root.join("symlinks")).unwrap();
+    symlink(root.join("dirs/file name.txt"), root.join("symlinks/f")).unwrap();
+    symlink(root.join("dirs/d"), root.join("symlinks/d")).unwrap();
+    root.join("symlinks")
+}
+
+#[tokio::test]
+async fn single_file_only_for_files_at_the_top() {
+    let dir = symlinks_dir("top");
+    let filter = single_file(dir.clone());
+    let name = |path: &'static str| warp::test::request().path(path).filter(&filter);
+
+    assert_eq!(name("/f").await.unwrap(), Some("file name.txt".to_owned()));
+    assert_eq!(name("/d").await.unwrap(), None);
+    assert_eq!(name("/d/g.txt").await.unwrap(), None);
+    assert_eq!(name("/missing").await.unwrap(), None);
+    assert_eq!(name("/").await.unwrap(), None);
+    fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn content_disposition_encodes_non_ascii() {
+    assert_eq!(
+        content_disposition("a.txt"),
+        "attachment; filename=\"a.txt\"; filename*=UTF-8''a.txt"
+    );
+    assert_eq!(
+        content_disposition("é \"q\".md"),
+        "attachment; filename=\"_ _q_.md\"; filename*=UTF-8''%C3%A9%20%22q%22.md"
+    );
+}
@@ -0,0 +1,87 @@
+//! [share::copy_read_only] and [share::remove_links_into] against a temporary directory.
+
+use std::fs;
+use std::os::unix::fs::{symlink, PermissionsExt};
+use std::path::{Path, PathBuf};
+use wdav_crypto_rs::share;
+
+/// A fresh `from` directory (with `f.txt`, `sub/g.txt` and a symlink `link`) under a per-test
+/// temporary directory. Return it, and (not yet existing) `to`.
+fn dirs(test: &str) -> (PathBuf, PathBuf) {
+    let root = std::env::temp_dir().join(format!("wdav_freeze_{test}_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("from/sub")).unwrap();
+    fs::write(root.join("from/f.txt"), "f").unwrap();
+    fs::write(root.join("from/sub/g.txt"), "g").unwrap();
+    symlink(root.join("from/f.txt"), root.join("from/link")).unwrap();
+    (root.join("from"), root.join("to"))
+}
+
+fn mode(path: &Path) -> u32 {
+    fs::metadata(path).unwrap().permissions().mode() & 0o777
+}
+
+/// Make the copy's directories writable again, so that it can be removed.
+fn cleanup(to: &Path) {
+    for dir in [to.join("sub"), to.to_path_buf()] {
+        fs::set_permissions(dir, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    fs::remove_dir_all(to.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn copies_content_independently() {
+    let (from, to) = dirs("content");
+    share::copy_read_only(&from, &to).unwrap();
+    assert_eq!(fs::read_to_string(to.join("f.txt")).unwrap(), "f");
+    assert_eq!(fs::read_to_string(to.join("sub/g.txt")).unwrap(), "g");
+
+    fs::write(from.join("f.txt"), "changed").unwrap();
+    fs::write(from.join("new.txt"), "new").unwrap();
+    assert_eq!(fs::read_to_string(to.join("f.txt")).unwrap(), "f");
+    assert!(!to.join("new.txt").exists());
+    cleanup(&to);
+}
+
+#[test]
+fn read_only_without_symlinks() {
+    let (from, to) = dirs("modes");
+    share::copy_read_only(&from, &to).unwrap();
+    assert_eq!(mode(&to), 0o555);
+    assert_eq!(mode(&to.join("sub")), 0o555);
+    assert_eq!(mode(&to.join("f.txt")), 0o444);
+    assert_eq!(mode(&to.join("sub/g.txt")), 0o444);
+    assert!(fs::symlink_metadata(to.join("link")).is_err());
+    cleanup(&to);
+}
+
+#[test]
+fn removes_links_into_the_share_only() {
+    let (from, _) = dirs("links");
+    let root = from.parent().unwrap();
+    let other = root.join("from_other");
+    fs::create_dir_all(&other).unwrap();
+    let (write, read) = (root.join("write"), root.join("read"));
+    fs::create_dir_all(&write).unwrap();
+    fs::create_dir_all(&read).unwrap();
+    for (target, link) in [
+        (from.clone(), write.join("0123456789abcdef")),
+        (from.clone(), write.join("legacy")),
+        (from.join("sub"), write.join("nested")),
+        (other.clone(), write.join("other")),
+        (from.clone(), read.join("from")),
+    ] {
+        symlink(target, link).unwrap();
+    }
+    fs::write(write.join("not_a_link"), "").unwrap();
+
+    share::remove_links_into(&[&write], &from).unwrap();
+    let mut left: Vec<_> = fs::read_dir(&write)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    left.sort();
+    assert_eq!(left, ["not_a_link", "other"]);
+    assert!(read.join("from").exists());
+    fs::remove_dir_all(root).unwrap();
+}
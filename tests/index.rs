@@ -0,0 +1,136 @@
+//! [ShareIndex] full rescans and incremental refreshes, against [FakeFileSystem].
+
+mod common;
+
+use common::{dir, drop, hashed, layout, read, write};
+use std::time::Duration;
+use wdav_crypto_rs::entry::{Entry, SecondaryDir};
+use wdav_crypto_rs::fs::get_entries;
+use wdav_crypto_rs::index::ShareIndex;
+
+#[test]
+fn rescan() {
+    let index = ShareIndex::new();
+    let fs = layout().with_dir(dir("a")).with_dir(dir("b"));
+    index.rescan(&fs).unwrap();
+    assert_eq!(index.read().len(), 2);
+
+    index.rescan(&fs.without(dir("a"))).unwrap();
+    assert_eq!(index.read().keys().collect::<Vec<_>>(), ["b"]);
+}
+
+#[test]
+fn refresh_added_changed_and_removed() {
+    let index = ShareIndex::new();
+    let fs = layout().with_dir(dir("a"));
+    index.rescan(&fs).unwrap();
+
+    let fs = fs.with_dir(dir("b"));
+    index.refresh(&fs, "b").unwrap();
+    assert!(matches!(index.read()["b"], Entry::PrimaryOnly { .. }));
+
+    let fs = fs
+        .with_symlink(read("b"), dir("b"))
+        .with_symlink(write("b"), dir("b"));
+    index.refresh(&fs, "b").unwrap();
+    assert!(index.read()["b"].is_writable());
+
+    let fs = fs.without(dir("a"));
+    index.refresh(&fs, "a").unwrap();
+    assert!(!index.read().contains_key("a"));
+    assert_eq!(index.read().len(), 1);
+}
+
+#[test]
+fn entries_of_links() {
+    let index = ShareIndex::new();
+    let fs = layout().with_dir(dir("a"));
+    index.rescan(&fs).unwrap();
+    let link = hashed(SecondaryDir::Write, "b");
+    assert_eq!(index.entry_of_link(SecondaryDir::Write, &link), None);
+
+    // Even before the symlink exists: Its creation is what the watcher looks it up for.
+    let fs = fs.with_dir(dir("b"));
+    index.refresh(&fs, "b").unwrap();
+    assert_eq!(
+        index.entry_of_link(SecondaryDir::Write, &link).as_deref(),
+        Some("b")
+    );
+    assert_eq!(index.entry_of_link(SecondaryDir::Drop, &link), None);
+    let a = hashed(SecondaryDir::File, "a");
+    assert_eq!(
+        index.entry_of_link(SecondaryDir::File, &a).as_deref(),
+        Some("a")
+    );
+
+    index.refresh(&fs.without(dir("b")), "b").unwrap();
+    assert_eq!(index.entry_of_link(SecondaryDir::Write, &link), None);
+    index.rescan(&layout()).unwrap();
+    assert_eq!(index.entry_of_link(SecondaryDir::File, &a), None);
+}
+
+/// Refreshing every name one by one ends up the same as a full scan.
+#[test]
+fn refresh_matches_full_scan() {
+    let fs = layout()
+        .with_dir(dir("primary_only"))
+        .with_file(dir("non_dir"))
+        .with_dir(dir("read_write"))
+        .with_symlink(read("read_write"), dir("read_write"))
+        .with_symlink(write("read_write"), dir("read_write"))
+        .with_dir(dir("read_wrong"))
+        .with_symlink(read("read_wrong"), dir("primary_only"))
+        .with_file(write("read_wrong"))
+        .with_symlink(read("orphans"), dir("orphans"))
+        .with_symlink(write("orphans"), dir("orphans"))
+        .with_dir(dir("drop_box"))
+        .with_symlink(drop("drop_box"), dir("drop_box"))
+        .with_symlink(drop("orphans"), dir("orphans"));
+
+    let index = ShareIndex::new();
+    for name in [
+        "primary_only",
+        "non_dir",
+        "read_write",
+        "read_wrong",
+        "orphans",
+        "drop_box",
+    ] {
+        index.refresh(&fs, name).unwrap();
+    }
+    let scanned = get_entries(&fs).unwrap();
+
+    let refreshed = index.read();
+    assert_eq!(refreshed.len(), scanned.len());
+    for (name, entry) in scanned {
+        assert_eq!(
+            format!("{entry:?}"),
+            format!("{:?}", refreshed[&name]),
+            "{name}"
+        );
+    }
+}
+
+/// The fallback once inotify fails: Rescans keep coming, and undo changes that are gone by then.
+#[tokio::test]
+async fn rescan_periodically() {
+    let index = ShareIndex::new();
+    let fs = layout().with_dir(dir("a"));
+    let rescans = tokio::spawn({
+        let index = index.clone();
+        async move {
+            index
+                .rescan_periodically(fs, Duration::from_millis(10))
+                .await
+        }
+    });
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(index.read().keys().collect::<Vec<_>>(), ["a"]);
+
+    index.refresh(&layout().with_dir(dir("b")), "b").unwrap();
+    assert_eq!(index.read().len(), 2);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(index.read().keys().collect::<Vec<_>>(), ["a"]);
+    rescans.abort();
+}
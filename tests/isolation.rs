@@ -0,0 +1,64 @@
+//! [Isolation] of user content from /admin.
+
+use http::Method;
+use wdav_crypto_rs::isolation::{to_content_origin, Isolation, SANDBOX};
+
+#[test]
+fn content_origin_is_an_origin() {
+    let isolation = Isolation::new(Some("https://Files.example.com/"), false).unwrap();
+    assert!(isolation.is_content_host(Some("files.example.com")));
+    assert!(!isolation.is_content_host(Some("example.com")));
+    assert!(!isolation.is_content_host(None));
+
+    assert_eq!(
+        Isolation::new(Some(" "), false).unwrap(),
+        Isolation::default()
+    );
+    for origin in ["files.example.com", "/files", "https://example.com/files"] {
+        assert!(Isolation::new(Some(origin), false).is_err(), "{origin}");
+    }
+}
+
+#[test]
+fn harden_get_on_the_admin_origin() {
+    let isolation = Isolation::new(None, true).unwrap();
+    let response = isolation.harden(&Method::GET, SANDBOX, "<script></script>");
+    let headers = response.headers();
+    assert_eq!(headers["content-security-policy"], "sandbox");
+    assert_eq!(headers["x-content-type-options"], "nosniff");
+    assert_eq!(headers["content-disposition"], "attachment");
+
+    let response = isolation.harden(&Method::PUT, SANDBOX, "");
+    assert!(!response.headers().contains_key("content-security-policy"));
+
+    let isolation = Isolation::new(Some("https://files.example.com"), true).unwrap();
+    let response = isolation.harden(&Method::GET, SANDBOX, "<script></script>");
+    assert!(!response.headers().contains_key("content-security-policy"));
+}
+
+#[tokio::test]
+async fn redirect_get_to_the_content_origin() {
+    let isolation = Isolation::new(Some("https://files.example.com"), false).unwrap();
+    let filter = to_content_origin(isolation);
+
+    let response = warp::test::request()
+        .path("/read/d/x.html?a=1")
+        .header("host", "admin.example.com")
+        .reply(&filter)
+        .await;
+    assert_eq!(response.status(), 308);
+    assert_eq!(
+        response.headers()["location"],
+        "https://files.example.com/read/d/x.html?a=1"
+    );
+
+    for (method, host) in [("GET", "files.example.com"), ("PUT", "admin.example.com")] {
+        let matched = warp::test::request()
+            .method(method)
+            .path("/read/d/x.html")
+            .header("host", host)
+            .matches(&filter)
+            .await;
+        assert!(!matched, "{method} {host}");
+    }
+}
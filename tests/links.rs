@@ -0,0 +1,50 @@
+//! [link_name] of shares and single-file shares.
+
+mod common;
+
+use common::{dir, file_link, init_links, layout, read};
+use wdav_crypto_rs::entry::SecondaryDir;
+use wdav_crypto_rs::fs::get_entries;
+use wdav_crypto_rs::links::{link_name, LINK_NAME_LEN};
+
+#[test]
+fn hashed_except_read() {
+    init_links();
+    assert_eq!(link_name(SecondaryDir::Read, "docs"), "docs");
+
+    let hashed = [
+        SecondaryDir::Write,
+        SecondaryDir::Append,
+        SecondaryDir::Drop,
+        SecondaryDir::File,
+    ]
+    .map(|under| link_name(under, "docs"));
+    for name in &hashed {
+        assert_eq!(name.len(), LINK_NAME_LEN);
+        assert!(name.bytes().all(|b| b.is_ascii_hexdigit()), "{name}");
+        assert!(!name.contains("docs"), "{name}");
+    }
+    // A write link doesn't give away the drop link of the same share (nor the other way).
+    for (i, name) in hashed.iter().enumerate() {
+        assert!(!hashed[i + 1..].contains(name), "{name}");
+    }
+    // Re-generated after a restart: The same for the same key and salt.
+    assert_eq!(link_name(SecondaryDir::Write, "docs"), hashed[0]);
+    assert_ne!(link_name(SecondaryDir::Write, "docs2"), hashed[0]);
+}
+
+#[test]
+fn file_link_differs_from_file_name() {
+    let fs = layout()
+        .with_file(dir("report.pdf"))
+        .with_symlink(read("report.pdf"), dir("report.pdf"))
+        .with_symlink(file_link("report.pdf"), dir("report.pdf"));
+    let entries = get_entries(&fs).unwrap();
+    let link = entries["report.pdf"].link_name().unwrap();
+    assert_ne!(link, "report.pdf");
+    assert_eq!(link, link_name(SecondaryDir::File, "report.pdf"));
+    assert_eq!(
+        file_link("report.pdf"),
+        format!("{}/{link}", wdav_crypto_rs::SYMLINKS_FILE)
+    );
+}
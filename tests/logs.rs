@@ -0,0 +1,134 @@
+//! [access_log] and [audited] against temporary directories.
+
+use std::collections::HashMap;
+use std::fs;
+use std::os::unix::fs::symlink;
+use std::path::PathBuf;
+use warp::http::StatusCode;
+use warp::Filter;
+use wdav_crypto_rs::error::Error;
+use wdav_crypto_rs::logs::{access_log, audited, Logs};
+
+/// A fresh share directory, `symlinks/hash` pointing to it, and an empty `logs` directory, under a
+/// per-test temporary directory. Return that directory.
+fn dirs(test: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("wdav_logs_{test}_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    let share = root.join(format!("dirs/logs_{test}_share"));
+    fs::create_dir_all(&share).unwrap();
+    fs::write(share.join("a.txt"), "abc").unwrap();
+    fs::create_dir_all(root.join("symlinks")).unwrap();
+    fs::create_dir_all(root.join("logs")).unwrap();
+    symlink(&share, root.join("symlinks/hash")).unwrap();
+    root
+}
+
+#[tokio::test]
+async fn access_by_share() {
+    let root = dirs("access");
+    let logs = Logs::new(root.join("logs"));
+    let files = warp::path("write").and(warp::fs::dir(root.join("symlinks")));
+    let filter = access_log(
+        logs.clone(),
+        vec![("write", root.join("symlinks"))],
+        files.or(warp::any().and_then(|| async {
+            let missing = warp::reply::with_status("missing", StatusCode::NOT_FOUND);
+            Ok::<_, warp::Rejection>(missing)
+        })),
+    );
+
+    let response = warp::test::request()
+        .path("/write/hash/a.txt")
+        .header("x-forwarded-for", "192.0.2.1")
+        .remote_addr("127.0.0.1:1234".parse().unwrap())
+        .reply(&filter)
+        .await;
+    assert_eq!(response.body().as_ref(), b"abc");
+    warp::test::request()
+        .path("/write/other/a.txt")
+        .reply(&filter)
+        .await;
+    warp::test::request()
+        .method("PUT")
+        .path("/elsewhere/x")
+        .body("12345")
+        .reply(&filter)
+        .await;
+
+    let records = logs.recent_access(10).await.unwrap();
+    assert_eq!(records.len(), 3);
+    let (elsewhere, missing, read) = (&records[0], &records[1], &records[2]);
+    assert_eq!(read.method, "GET");
+    assert_eq!(read.share.as_deref(), Some("logs_access_share"));
+    assert_eq!(read.path.as_deref(), Some("a.txt"));
+    assert_eq!((read.status, read.bytes_sent), (200, Some(3)));
+    assert_eq!(read.client.as_deref(), Some("127.0.0.1"));
+    assert_eq!(read.forwarded_for.as_deref(), Some("192.0.2.1"));
+    // Never the hash.
+    assert_eq!(
+        (missing.share.as_deref(), missing.path.as_deref()),
+        (None, None)
+    );
+    assert_eq!(missing.status, 404);
+    assert_eq!(elsewhere.prefix, "elsewhere");
+    assert_eq!(elsewhere.path.as_deref(), Some("x"));
+    assert_eq!(elsewhere.bytes_received, Some(5));
+
+    let json = fs::read_to_string(logs.access_path()).unwrap();
+    assert_eq!(json.lines().count(), 3);
+    assert!(!json.contains("hash"), "{json}");
+    assert_eq!(logs.recent_access(1).await.unwrap()[0].method, "PUT");
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[tokio::test]
+async fn audit_of_actions() {
+    let root = dirs("audit");
+    let logs = Logs::new(root.join("logs"));
+    let action = warp::path("admin")
+        .and(warp::path("policy"))
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(warp::query::<HashMap<String, String>>())
+        .and_then(|name: String, _query: HashMap<String, String>| async move {
+            if name == "bad" {
+                Err(warp::reject::custom(Error::BadRequest("bad".to_owned())))
+            } else {
+                Ok("done")
+            }
+        });
+    let filter = audited(logs.clone(), action);
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/admin/policy/a%20b?deny=delete&max_put_bytes=")
+        .reply(&filter)
+        .await;
+    assert_eq!(response.status(), 200);
+    let response = warp::test::request()
+        .method("POST")
+        .path("/admin/policy/bad")
+        .reply(&filter)
+        .await;
+    assert_eq!(response.status(), 400);
+    // Neither is an action.
+    for (method, path) in [("POST", "/admin/other/x"), ("GET", "/admin/policy/x")] {
+        let response = warp::test::request()
+            .method(method)
+            .path(path)
+            .filter(&filter)
+            .await;
+        assert!(response.is_err(), "{method} {path}");
+    }
+
+    let records = logs.recent_audit(10).await.unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].share.as_deref(), Some("bad"));
+    assert_eq!(records[0].status, 400);
+    assert_eq!(records[1].action, "policy");
+    assert_eq!(records[1].share.as_deref(), Some("a b"));
+    assert_eq!(records[1].params["deny"], "delete");
+    assert_eq!(records[1].status, 200);
+    fs::remove_dir_all(root).unwrap();
+}
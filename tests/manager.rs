@@ -0,0 +1,223 @@
+//! [list] and the GET side of [file_manager] against a temporary directory.
+
+mod common;
+
+use common::{cleanup, color_props, share_dirs};
+use std::fs;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+use warp::hyper::body::Bytes;
+use wdav_crypto_rs::manager::{file_manager, list};
+use wdav_crypto_rs::props::PropStore;
+
+/// The sidecar store (only) of [share_dirs], like [post] uses it.
+fn props(symlinks: &Path) -> PropStore {
+    PropStore::new(symlinks, symlinks.with_file_name("props"), false)
+}
+
+/// [share_dirs] with a file, a sub-directory and a symlink.
+fn dirs(test: &str) -> PathBuf {
+    share_dirs("manager", test, |share| {
+        fs::create_dir_all(share.join("b_dir")).unwrap();
+        fs::write(share.join("a.txt"), "abc").unwrap();
+        fs::write(share.join("c.txt"), "").unwrap();
+        symlink(share.join("a.txt"), share.join("symlinked")).unwrap();
+    })
+}
+
+#[test]
+fn list_directories_first_without_symlinks() {
+    let symlinks = dirs("list");
+    let listed = list(&symlinks.join("s")).unwrap();
+    let names: Vec<_> = listed.iter().map(|l| l.name.as_str()).collect();
+    assert_eq!(names, ["b_dir", "a.txt", "c.txt"]);
+    assert!(listed[0].is_dir);
+    assert_eq!(listed[1].size, 3);
+    cleanup(&symlinks);
+}
+
+#[tokio::test]
+async fn get_lists_only_for_browsers() {
+    let symlinks = dirs("get");
+    let filter = file_manager(
+        "write",
+        symlinks.clone(),
+        symlinks.with_file_name("staging"),
+        props(&symlinks),
+    );
+
+    let response = warp::test::request()
+        .path("/s/")
+        .header("accept", "text/html,application/xhtml+xml")
+        .reply(&filter)
+        .await;
+    assert_eq!(response.status(), 200);
+    let html = String::from_utf8_lossy(response.body());
+    assert!(html.contains("a.txt") && html.contains("b_dir/"));
+    assert!(!html.contains("symlinked"));
+
+    let response = warp::test::request()
+        .path("/s")
+        .header("accept", "text/html")
+        .reply(&filter)
+        .await;
+    assert_eq!(response.status(), 303);
+    assert_eq!(response.headers()["location"], "/write/s/");
+
+    // WebDAV clients (and files) go on to the DAV handler.
+    for (path, accept) in [("/s/", "*/*"), ("/s/a.txt", "text/html")] {
+        let matched = warp::test::request()
+            .path(path)
+            .header("accept", accept)
+            .matches(&filter)
+            .await;
+        assert!(!matched, "{path}");
+    }
+    cleanup(&symlinks);
+}
+
+const BOUNDARY: &str = "wdav-boundary";
+
+/// A `multipart/form-data` body with `files` (names and contents) and text `fields`. Unless
+/// `complete`, it stops (like an aborted upload) after the last file's content.
+fn form(files: &[(&str, &str)], fields: &[(&str, &str)], complete: bool) -> String {
+    let mut body = String::new();
+    for (name, value) in fields {
+        body += &format!(
+            "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"{name}\"\r\n\r\n{value}\r\n"
+        );
+    }
+    for (file_name, content) in files {
+        body += &format!(
+            "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"file\"; \
+             filename=\"{file_name}\"\r\nContent-Type: text/plain\r\n\r\n{content}"
+        );
+        if complete {
+            body += "\r\n";
+        }
+    }
+    if complete {
+        body += &format!("--{BOUNDARY}--\r\n");
+    }
+    body
+}
+
+async fn post(symlinks: &Path, action: &str, body: String) -> warp::http::Response<Bytes> {
+    let filter = file_manager(
+        "write",
+        symlinks.to_owned(),
+        symlinks.with_file_name("staging"),
+        props(symlinks),
+    );
+    warp::test::request()
+        .method("POST")
+        .path(&format!("/s/?action={action}"))
+        .header(
+            "content-type",
+            format!("multipart/form-data; boundary={BOUNDARY}"),
+        )
+        .body(body)
+        .reply(&filter)
+        .await
+}
+
+#[tokio::test]
+async fn post_upload() {
+    let symlinks = dirs("upload");
+    let share = symlinks.join("s");
+
+    let body = form(&[("new.txt", "new"), ("a.txt", "replaced")], &[], true);
+    let response = post(&symlinks, "upload", body).await;
+    assert_eq!(response.status(), 303);
+    assert_eq!(fs::read_to_string(share.join("new.txt")).unwrap(), "new");
+    assert_eq!(fs::read_to_string(share.join("a.txt")).unwrap(), "replaced");
+
+    // An aborted upload over an existing file leaves that file as it was.
+    let body = form(&[("a.txt", "partial")], &[], false);
+    let response = post(&symlinks, "upload", body).await;
+    assert_eq!(response.status(), 400);
+    assert_eq!(fs::read_to_string(share.join("a.txt")).unwrap(), "replaced");
+    // Nothing left behind, either.
+    let staging = symlinks.with_file_name("staging");
+    assert_eq!(fs::read_dir(staging).unwrap().count(), 0);
+
+    let body = form(&[("b_dir", "x")], &[], true);
+    assert_eq!(post(&symlinks, "upload", body).await.status(), 409);
+    assert!(share.join("b_dir").is_dir());
+    cleanup(&symlinks);
+}
+
+#[tokio::test]
+async fn post_mkdir_rename_and_delete() {
+    let symlinks = dirs("actions");
+    let share = symlinks.join("s");
+
+    let response = post(&symlinks, "mkdir", form(&[], &[("name", "new_dir")], true)).await;
+    assert_eq!(response.status(), 303);
+    assert!(share.join("new_dir").is_dir());
+    let response = post(&symlinks, "mkdir", form(&[], &[("name", "../up")], true)).await;
+    assert_eq!(response.status(), 400);
+
+    let fields = [("from", "a.txt"), ("to", "renamed.txt")];
+    let response = post(&symlinks, "rename", form(&[], &fields, true)).await;
+    assert_eq!(response.status(), 303);
+    assert!(!share.join("a.txt").exists());
+    assert_eq!(
+        fs::read_to_string(share.join("renamed.txt")).unwrap(),
+        "abc"
+    );
+    // Neither over an existing name, nor of a (hidden) symlink.
+    let fields = [("from", "renamed.txt"), ("to", "c.txt")];
+    assert_eq!(
+        post(&symlinks, "rename", form(&[], &fields, true))
+            .await
+            .status(),
+        409
+    );
+    let fields = [("from", "symlinked"), ("to", "other")];
+    assert_eq!(
+        post(&symlinks, "rename", form(&[], &fields, true))
+            .await
+            .status(),
+        404
+    );
+
+    let response = post(&symlinks, "delete", form(&[], &[("name", "b_dir")], true)).await;
+    assert_eq!(response.status(), 303);
+    assert!(!share.join("b_dir").exists());
+    let response = post(&symlinks, "delete", form(&[], &[("name", "b_dir")], true)).await;
+    assert_eq!(response.status(), 404);
+    cleanup(&symlinks);
+}
+
+/// Dead properties follow renames, and go with deleted and replaced files.
+#[tokio::test]
+async fn post_keeps_dead_properties_in_step() {
+    let symlinks = dirs("props");
+    let store = props(&symlinks);
+    for path in ["s/a.txt", "s/b_dir", "s/c.txt"] {
+        let path = Path::new(path);
+        store.save(path, color_props("red")).await.unwrap();
+    }
+    let colored = |path: &'static str| {
+        let store = store.clone();
+        async move { !store.load(Path::new(path)).await.unwrap().is_empty() }
+    };
+
+    let fields = [("from", "a.txt"), ("to", "renamed.txt")];
+    post(&symlinks, "rename", form(&[], &fields, true)).await;
+    assert!(!colored("s/a.txt").await);
+    assert!(colored("s/renamed.txt").await);
+
+    post(&symlinks, "delete", form(&[], &[("name", "b_dir")], true)).await;
+    assert!(!colored("s/b_dir").await);
+    // Not even when it comes back.
+    post(&symlinks, "mkdir", form(&[], &[("name", "b_dir")], true)).await;
+    assert!(!colored("s/b_dir").await);
+
+    post(&symlinks, "upload", form(&[("c.txt", "new")], &[], true)).await;
+    assert_eq!(fs::read_to_string(symlinks.join("s/c.txt")).unwrap(), "new");
+    assert!(!colored("s/c.txt").await);
+    assert!(colored("s/renamed.txt").await);
+    cleanup(&symlinks);
+}
@@ -0,0 +1,82 @@
+//! [MethodPolicy] checks, and its stored form.
+
+use http::{Method, StatusCode};
+use wdav_crypto_rs::meta::{MethodPolicy, ShareMeta};
+
+fn status(policy: &MethodPolicy, method: &str, content_length: Option<u64>) -> Option<StatusCode> {
+    let method = Method::from_bytes(method.as_bytes()).unwrap();
+    policy
+        .check(&method, content_length)
+        .err()
+        .map(|e| e.status())
+}
+
+#[test]
+fn default_allows_everything() {
+    let policy = MethodPolicy::default();
+    for method in ["GET", "PUT", "MKCOL", "PROPPATCH", "DELETE"] {
+        assert_eq!(status(&policy, method, None), None, "{method}");
+    }
+}
+
+#[test]
+fn denied_methods() {
+    let policy = MethodPolicy::new(vec!["mkcol".to_owned(), "PropPatch".to_owned()], None).unwrap();
+    assert_eq!(
+        status(&policy, "MKCOL", None),
+        Some(StatusCode::METHOD_NOT_ALLOWED)
+    );
+    assert_eq!(
+        status(&policy, "PROPPATCH", None),
+        Some(StatusCode::METHOD_NOT_ALLOWED)
+    );
+    assert_eq!(status(&policy, "PUT", Some(1)), None);
+}
+
+#[test]
+fn max_put_bytes() {
+    let policy = MethodPolicy::new(vec![], Some(10 * 1024 * 1024)).unwrap();
+    assert_eq!(status(&policy, "PUT", Some(10 * 1024 * 1024)), None);
+    assert_eq!(
+        status(&policy, "PUT", Some(10 * 1024 * 1024 + 1)),
+        Some(StatusCode::PAYLOAD_TOO_LARGE)
+    );
+    assert_eq!(
+        status(&policy, "PUT", None),
+        Some(StatusCode::LENGTH_REQUIRED)
+    );
+    // Only PUT is limited.
+    assert_eq!(status(&policy, "PROPPATCH", None), None);
+}
+
+#[test]
+fn unknown_method_is_rejected_when_set() {
+    assert!(MethodPolicy::new(vec!["frobnicate".to_owned()], None).is_err());
+}
+
+#[test]
+fn stored_form_tolerates_missing_fields() {
+    let meta: ShareMeta = serde_json::from_str(r#"{"policy": {"max_put_bytes": 5}}"#).unwrap();
+    assert!(meta.policy.denied_methods.is_empty());
+    assert_eq!(meta.policy.max_put_bytes, Some(5));
+    assert_eq!(
+        serde_json::from_str::<ShareMeta>("{}").unwrap(),
+        ShareMeta::default()
+    );
+}
+
+#[test]
+fn retention_from_str() {
+    use wdav_crypto_rs::meta::Retention;
+    assert_eq!("off".parse::<Retention>().unwrap(), Retention::Off);
+    assert_eq!(
+        "last:3".parse::<Retention>().unwrap(),
+        Retention::KeepLast(3)
+    );
+    assert_eq!(
+        "days:30".parse::<Retention>().unwrap(),
+        Retention::KeepDays(30)
+    );
+    assert!("last:".parse::<Retention>().is_err());
+    assert!("forever".parse::<Retention>().is_err());
+}
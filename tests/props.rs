@@ -0,0 +1,159 @@
+//! [PropsFs] and [with_collection_props] against temporary directories, with extended attributes
+//! and with the sidecar store.
+
+mod common;
+
+use common::{cleanup, share_dirs};
+use dav_server::fakels::FakeLs;
+use dav_server::localfs::LocalFs;
+use dav_server::DavHandler;
+use std::fs;
+use std::path::{Path, PathBuf};
+use warp::Filter;
+use wdav_crypto_rs::props::{with_collection_props, PropStore, PropsFs, XATTR};
+
+/// [share_dirs] with `a.txt` and a `sub` directory in the share.
+fn dirs(test: &str) -> PathBuf {
+    share_dirs("props", test, |share| {
+        fs::create_dir(share.join("sub")).unwrap();
+        fs::write(share.join("a.txt"), "a").unwrap();
+    })
+}
+
+fn filter(
+    symlinks: &Path,
+    xattrs: bool,
+) -> impl Filter<Extract = (warp::reply::Response,), Error = warp::Rejection> + Clone {
+    let store = PropStore::new(symlinks, symlinks.with_file_name("props"), xattrs);
+    let filesystem = LocalFs::new(symlinks, false, false, false);
+    let handler = DavHandler::builder()
+        .filesystem(PropsFs::new(filesystem, store.clone()))
+        .locksystem(FakeLs::new())
+        .strip_prefix("/write")
+        .build_handler();
+    warp::path("write").and(with_collection_props(
+        "write",
+        store,
+        dav_server::warp::dav_handler(handler),
+    ))
+}
+
+async fn set_color(
+    filter: &(impl Filter<Extract = (warp::reply::Response,), Error = warp::Rejection> + 'static),
+    path: &str,
+    color: &str,
+) {
+    let response = warp::test::request()
+        .method("PROPPATCH")
+        .path(path)
+        .body(format!(
+            r#"<?xml version="1.0"?><d:propertyupdate xmlns:d="DAV:" xmlns:x="urn:test">
+            <d:set><d:prop><x:color>{color}</x:color></d:prop></d:set></d:propertyupdate>"#
+        ))
+        .reply(filter)
+        .await;
+    assert_eq!(response.status(), 207, "{path}");
+    let xml = String::from_utf8_lossy(response.body());
+    assert!(xml.contains("200 OK"), "{xml}");
+}
+
+/// The color that PROPFIND reports of `path`, if any.
+async fn color(
+    filter: &(impl Filter<Extract = (warp::reply::Response,), Error = warp::Rejection> + 'static),
+    path: &str,
+) -> Option<String> {
+    let response = warp::test::request()
+        .method("PROPFIND")
+        .path(path)
+        .header("depth", "0")
+        .body(
+            r#"<?xml version="1.0"?><d:propfind xmlns:d="DAV:" xmlns:x="urn:test">
+            <d:prop><x:color/></d:prop></d:propfind>"#,
+        )
+        .reply(filter)
+        .await;
+    assert_eq!(response.status(), 207, "{path}");
+    let xml = String::from_utf8_lossy(response.body()).to_string();
+    // Missing, it's in a 404 propstat instead.
+    if !xml.contains("200 OK") {
+        return None;
+    }
+    let end = xml.find("</x:color>")?;
+    let start = xml[..end].rfind('>')? + 1;
+    Some(xml[start..end].to_owned())
+}
+
+async fn request(
+    filter: &(impl Filter<Extract = (warp::reply::Response,), Error = warp::Rejection> + 'static),
+    method: &str,
+    path: &str,
+    destination: &str,
+) -> u16 {
+    let response = warp::test::request()
+        .method(method)
+        .path(path)
+        .header("destination", destination)
+        .reply(filter)
+        .await;
+    response.status().as_u16()
+}
+
+async fn survive_move_and_copy(test: &str, xattrs: bool) {
+    let symlinks = dirs(test);
+    let filter = filter(&symlinks, xattrs);
+
+    assert_eq!(color(&filter, "/write/s/a.txt").await, None);
+    set_color(&filter, "/write/s/a.txt", "red").await;
+    set_color(&filter, "/write/s/sub/", "blue").await;
+    assert_eq!(color(&filter, "/write/s/a.txt").await.unwrap(), "red");
+
+    let moved = request(&filter, "MOVE", "/write/s/a.txt", "/write/s/sub/b.txt").await;
+    assert_eq!(moved, 201);
+    assert_eq!(color(&filter, "/write/s/sub/b.txt").await.unwrap(), "red");
+
+    let copied = request(&filter, "COPY", "/write/s/sub/", "/write/s/copy/").await;
+    assert_eq!(copied, 201);
+    assert_eq!(color(&filter, "/write/s/copy/").await.unwrap(), "blue");
+    assert_eq!(color(&filter, "/write/s/copy/b.txt").await.unwrap(), "red");
+
+    // Each copy has its own.
+    set_color(&filter, "/write/s/copy/b.txt", "green").await;
+    assert_eq!(color(&filter, "/write/s/sub/b.txt").await.unwrap(), "red");
+
+    let moved = request(&filter, "MOVE", "/write/s/copy/", "/write/s/moved/").await;
+    assert_eq!(moved, 201);
+    assert_eq!(color(&filter, "/write/s/moved/").await.unwrap(), "blue");
+    assert_eq!(
+        color(&filter, "/write/s/moved/b.txt").await.unwrap(),
+        "green"
+    );
+
+    // A new file at the same path doesn't inherit them.
+    assert_eq!(request(&filter, "DELETE", "/write/s/moved/", "").await, 204);
+    fs::create_dir(symlinks.join("s/moved")).unwrap();
+    assert_eq!(color(&filter, "/write/s/moved/").await, None);
+
+    let sidecar = symlinks
+        .with_file_name("props")
+        .join(format!("props_{test}_share.json"));
+    if xattrs {
+        // (Unless the temporary directory doesn't have extended attributes.)
+        let in_xattr = xattr::get(symlinks.join("s/sub/b.txt"), XATTR);
+        assert!(!sidecar.exists() || in_xattr.is_err());
+    } else {
+        let json = fs::read_to_string(&sidecar).unwrap();
+        assert!(json.contains("\"sub/b.txt\""), "{json}");
+        assert!(!json.contains("moved"), "{json}");
+    }
+    cleanup(&symlinks);
+}
+
+#[tokio::test]
+async fn in_xattrs() {
+    survive_move_and_copy("xattrs", true).await;
+}
+
+#[tokio::test]
+async fn in_sidecar() {
+    survive_move_and_copy("sidecar", false).await;
+}
@@ -0,0 +1,118 @@
+//! [space] and [dav_handler] against temporary directories.
+
+mod common;
+
+use common::{cleanup, share_dirs};
+use dav_server::fakels::FakeLs;
+use dav_server::localfs::LocalFs;
+use dav_server::DavHandler;
+use std::fs;
+use std::path::Path;
+use warp::Filter;
+use wdav_crypto_rs::meta::ShareMeta;
+use wdav_crypto_rs::quota::{dav_handler, filesystem_available, space};
+use wdav_crypto_rs::server::{method_policy, recover_by_accept};
+
+/// Fill a share (see [share_dirs]) with 30 bytes in `a.txt` and `sub/b.txt`.
+fn thirty_bytes(share: &Path) {
+    fs::create_dir(share.join("sub")).unwrap();
+    fs::write(share.join("a.txt"), [b'a'; 10]).unwrap();
+    fs::write(share.join("sub/b.txt"), [b'b'; 20]).unwrap();
+}
+
+#[tokio::test]
+async fn space_of_quota_cached() {
+    let symlinks = share_dirs("quota", "space", thirty_bytes);
+    let share = symlinks.join("s");
+    let name = format!("quota_space_{}", std::process::id());
+
+    let measured = space(&name, share.clone(), Some(100)).await.unwrap();
+    assert_eq!((measured.used, measured.available), (30, 70));
+    // Measured again only later, but the quota applies right away.
+    fs::write(share.join("c.txt"), [b'c'; 5]).unwrap();
+    let measured = space(&name, share.clone(), Some(40)).await.unwrap();
+    assert_eq!((measured.used, measured.available), (30, 10));
+
+    let other = format!("quota_space_other_{}", std::process::id());
+    let measured = space(&other, share.clone(), None).await.unwrap();
+    assert_eq!(measured.used, 35);
+    assert!(measured.available <= filesystem_available(&share).unwrap() + 4096 * 1024);
+    cleanup(&symlinks);
+}
+
+#[tokio::test]
+async fn propfind_of_collections() {
+    let symlinks = share_dirs("quota", "propfind", thirty_bytes);
+    let filesystem = LocalFs::new(&symlinks, false, false, false);
+    let handler = DavHandler::builder()
+        .filesystem(filesystem.clone())
+        .locksystem(FakeLs::new())
+        .strip_prefix("/write")
+        .build_handler();
+    let filter = warp::path("write").and(dav_handler(handler, filesystem, symlinks.clone()));
+
+    let response = warp::test::request()
+        .method("PROPFIND")
+        .path("/write/s/")
+        .header("depth", "1")
+        .body(
+            r#"<?xml version="1.0"?><d:propfind xmlns:d="DAV:"><d:prop>
+            <d:quota-used-bytes/><d:quota-available-bytes/></d:prop></d:propfind>"#,
+        )
+        .reply(&filter)
+        .await;
+    assert_eq!(response.status(), 207);
+    let xml = String::from_utf8_lossy(response.body());
+    // Both the share and `sub` are collections of the share.
+    assert_eq!(xml.matches("<D:quota-used-bytes>30<").count(), 2, "{xml}");
+    assert_eq!(xml.matches("<D:quota-available-bytes>").count(), 3, "{xml}");
+
+    // Other methods are served as usual.
+    let response = warp::test::request()
+        .path("/write/s/sub/b.txt")
+        .reply(&filter)
+        .await;
+    assert_eq!(response.body().as_ref(), [b'b'; 20]);
+    cleanup(&symlinks);
+}
+
+#[tokio::test]
+async fn copy_measured() {
+    let symlinks = share_dirs("quota", "copy", thirty_bytes);
+    let meta_dir = symlinks.with_file_name("meta");
+    let meta = ShareMeta {
+        quota_bytes: Some(40),
+        ..ShareMeta::default()
+    };
+    meta.save_in(&meta_dir, "quota_copy_share").await.unwrap();
+    let filter = recover_by_accept(
+        warp::path("write")
+            .and(method_policy(symlinks.clone(), meta_dir))
+            .map(|| "passed"),
+    );
+    let status = |method: &'static str, from: &'static str, to: &'static str| {
+        let request = warp::test::request()
+            .method(method)
+            .path(from)
+            .header("destination", format!("http://localhost/write/s/{to}"));
+        async { request.reply(&filter).await.status() }
+    };
+
+    // 30 bytes used: A copy of the file fits, one of the folder doesn't.
+    assert_eq!(status("COPY", "/write/s/a.txt", "c.txt").await, 200);
+    assert_eq!(status("COPY", "/write/s/sub", "sub2").await, 507);
+    assert_eq!(status("COPY", "/write/s/", "all").await, 507);
+    // Moves within the share add nothing.
+    assert_eq!(status("MOVE", "/write/s/sub", "sub2").await, 200);
+    // What's overwritten makes room, unless it isn't.
+    assert_eq!(status("COPY", "/write/s/sub/b.txt", "a.txt").await, 200);
+    let request = warp::test::request()
+        .method("COPY")
+        .path("/write/s/sub/b.txt")
+        .header("destination", "http://localhost/write/s/a.txt")
+        .header("overwrite", "F");
+    assert_eq!(request.reply(&filter).await.status(), 507);
+    // Missing sources are left to the DAV handler.
+    assert_eq!(status("COPY", "/write/s/none", "none2").await, 200);
+    cleanup(&symlinks);
+}
@@ -0,0 +1,80 @@
+//! [get_entries_async] runs off the Tokio worker threads, with bounded concurrency.
+
+mod common;
+
+use common::{dir, layout};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use wdav_crypto_rs::fs::{get_entries_async, FakeFileSystem, FileSystem, MAX_PARALLEL_SCANS};
+
+/// [FakeFileSystem] with a slow [FileSystem::read_dir] that records how many calls overlap.
+#[derive(Clone)]
+struct SlowFileSystem {
+    inner: FakeFileSystem,
+    active: Arc<AtomicUsize>,
+    max_active: Arc<AtomicUsize>,
+}
+
+impl FileSystem for SlowFileSystem {
+    fn read_dir(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        let active = self.active.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_active.fetch_max(active, Ordering::SeqCst);
+        // Blocking on purpose: That's what a slow /tmp does.
+        thread::sleep(Duration::from_millis(20));
+        self.active.fetch_sub(1, Ordering::SeqCst);
+        self.inner.read_dir(dir)
+    }
+    fn is_dir(&self, path: &Path) -> bool {
+        self.inner.is_dir(path)
+    }
+    fn is_file(&self, path: &Path) -> bool {
+        self.inner.is_file(path)
+    }
+    fn is_symlink(&self, path: &Path) -> bool {
+        self.inner.is_symlink(path)
+    }
+    fn read_link_full(&self, path: &Path) -> io::Result<String> {
+        self.inner.read_link_full(path)
+    }
+    fn exists(&self, path: &Path) -> bool {
+        self.inner.exists(path)
+    }
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn scans_are_bounded_and_do_not_block_the_worker() {
+    let fs = SlowFileSystem {
+        inner: layout().with_dir(dir("a")),
+        active: Arc::default(),
+        max_active: Arc::default(),
+    };
+
+    let scans: Vec<_> = (0..4 * MAX_PARALLEL_SCANS)
+        .map(|_| tokio::spawn(get_entries_async(fs.clone())))
+        .collect();
+
+    // The (only) worker thread keeps running other tasks while the scans block.
+    let ticks = tokio::spawn(async {
+        let mut ticks = 0;
+        while ticks < 5 {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+            ticks += 1;
+        }
+        ticks
+    });
+    assert_eq!(ticks.await.unwrap(), 5);
+
+    for scan in scans {
+        let entries = scan.await.unwrap().unwrap();
+        assert!(entries["a"].name() == "a");
+    }
+    let max_active = fs.max_active.load(Ordering::SeqCst);
+    assert!(
+        (1..=MAX_PARALLEL_SCANS).contains(&max_active),
+        "{max_active}"
+    );
+}
@@ -0,0 +1,41 @@
+//! Helpers of [wdav_crypto_rs::site].
+
+use std::path::Path;
+use wdav_crypto_rs::meta::SiteConfig;
+use wdav_crypto_rs::site::{cache_control, content_type, is_site_path};
+
+#[test]
+fn site_paths_stay_within_the_share() {
+    assert!(is_site_path("index.html"));
+    assert!(is_site_path("errors/404.html"));
+    for path in [
+        "",
+        "/etc/passwd",
+        "../404.html",
+        "a/../../b",
+        "./index.html",
+    ] {
+        assert!(!is_site_path(path), "{path}");
+    }
+}
+
+#[test]
+fn content_types_and_caching() {
+    let site = SiteConfig {
+        max_age: 60,
+        ..SiteConfig::default()
+    };
+    let html = Path::new("docs/index.html");
+    assert_eq!(content_type(html), "text/html; charset=utf-8");
+    assert_eq!(cache_control(html, &site), "no-cache");
+
+    let script = Path::new("app.js");
+    assert!(content_type(script).contains("javascript"));
+    assert_eq!(cache_control(script, &site), "public, max-age=60");
+
+    assert_eq!(content_type(Path::new("logo.png")), "image/png");
+    assert_eq!(
+        content_type(Path::new("no_extension")),
+        "application/octet-stream"
+    );
+}
@@ -0,0 +1,128 @@
+//! [ShareTrash] against a temporary directory.
+
+mod common;
+
+use common::{cleanup, share_dirs};
+use core::time::Duration;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use wdav_crypto_rs::trash::ShareTrash;
+
+/// [share_dirs] with `sub/f.txt` in the share, and an empty `trash` directory. Return the `symlinks`
+/// and the `trash` directory.
+fn dirs(test: &str) -> (PathBuf, PathBuf) {
+    let symlinks = share_dirs("trash", test, |share| {
+        fs::create_dir(share.join("sub")).unwrap();
+        fs::write(share.join("sub/f.txt"), "f").unwrap();
+    });
+    let trash = symlinks.with_file_name("trash");
+    fs::create_dir(&trash).unwrap();
+    (symlinks, trash)
+}
+
+#[test]
+fn put_and_restore_directory() {
+    let (symlinks, root) = dirs("restore");
+    let share = symlinks.join("s");
+    let trash = ShareTrash::new(&root, "s");
+    let id = trash.put(&share, Path::new("sub")).unwrap();
+    assert!(!share.join("sub").exists());
+
+    let items = trash.list().unwrap();
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0].path, Path::new("sub"));
+    assert!(items[0].is_dir);
+
+    assert_eq!(trash.restore(&share, id).unwrap(), Path::new("sub"));
+    assert_eq!(fs::read_to_string(share.join("sub/f.txt")).unwrap(), "f");
+    assert!(trash.list().unwrap().is_empty());
+    cleanup(&symlinks);
+}
+
+#[test]
+fn restore_does_not_replace() {
+    let (symlinks, root) = dirs("conflict");
+    let share = symlinks.join("s");
+    let trash = ShareTrash::new(&root, "s");
+    let id = trash.put(&share, Path::new("sub/f.txt")).unwrap();
+    fs::write(share.join("sub/f.txt"), "new").unwrap();
+
+    let e = trash.restore(&share, id).unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::AlreadyExists);
+    assert_eq!(fs::read_to_string(share.join("sub/f.txt")).unwrap(), "new");
+    assert_eq!(trash.list().unwrap().len(), 1);
+    cleanup(&symlinks);
+}
+
+#[test]
+fn put_requires_existing_non_root() {
+    let (symlinks, root) = dirs("put");
+    let share = symlinks.join("s");
+    let trash = ShareTrash::new(&root, "s");
+    assert_eq!(
+        trash.put(&share, Path::new("missing")).unwrap_err().kind(),
+        io::ErrorKind::NotFound
+    );
+    assert!(trash.put(&share, Path::new("")).is_err());
+    assert!(trash.list().unwrap().is_empty());
+    cleanup(&symlinks);
+}
+
+#[test]
+fn purge() {
+    let (symlinks, root) = dirs("purge");
+    let share = symlinks.join("s");
+    fs::write(share.join("g.txt"), "g").unwrap();
+    let trash = ShareTrash::new(&root, "s");
+    let f = trash.put(&share, Path::new("sub/f.txt")).unwrap();
+    trash.put(&share, Path::new("g.txt")).unwrap();
+
+    assert_eq!(trash.purge_expired(Duration::from_secs(3600)).unwrap(), 0);
+    trash.purge(f).unwrap();
+    assert_eq!(trash.list().unwrap().len(), 1);
+    assert_eq!(trash.purge(f).unwrap_err().kind(), io::ErrorKind::NotFound);
+    assert_eq!(trash.purge_expired(Duration::ZERO).unwrap(), 1);
+    assert!(trash.list().unwrap().is_empty());
+    cleanup(&symlinks);
+}
+
+#[test]
+fn cap_purges_oldest_items() {
+    let (symlinks, root) = dirs("cap");
+    let share = symlinks.join("s");
+    // Each item takes 11 bytes: 10 of the file, 1 of its path.
+    let trash = ShareTrash::new(&root, "s").with_cap(Some(25));
+    for name in ["a", "b", "c"] {
+        fs::write(share.join(name), [b'x'; 10]).unwrap();
+        trash.put(&share, Path::new(name)).unwrap();
+    }
+    let paths: Vec<_> = trash.list().unwrap().into_iter().map(|i| i.path).collect();
+    assert_eq!(paths, [PathBuf::from("c"), PathBuf::from("b")]);
+
+    // Too large by itself.
+    fs::write(share.join("d"), [b'x'; 30]).unwrap();
+    trash.put(&share, Path::new("d")).unwrap();
+    assert!(trash.list().unwrap().is_empty());
+    cleanup(&symlinks);
+}
+
+/// A path longer (percent-encoded) than a file name can be.
+#[test]
+fn long_utf8_path() {
+    let (symlinks, root) = dirs("long");
+    let share = symlinks.join("s");
+    let trash = ShareTrash::new(&root, "s");
+    let name = "ä".repeat(100);
+    assert_eq!(name.len(), 200);
+    let file = Path::new("sub").join(&name);
+    fs::write(share.join(&file), "long").unwrap();
+
+    let id = trash.put(&share, &file).unwrap();
+    assert!(!share.join(&file).exists());
+    assert_eq!(trash.list().unwrap()[0].path, file);
+    assert_eq!(trash.restore(&share, id).unwrap(), file);
+    assert_eq!(fs::read_to_string(share.join(&file)).unwrap(), "long");
+    assert!(trash.list().unwrap().is_empty());
+    cleanup(&symlinks);
+}
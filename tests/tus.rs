@@ -0,0 +1,246 @@
+//! [tus] against temporary directories.
+
+mod common;
+
+use base64::Engine;
+use common::{cleanup, color_props, share_dirs};
+use std::fs;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use warp::Filter;
+use wdav_crypto_rs::props::PropStore;
+use wdav_crypto_rs::tus::{parse_metadata, purge_expired, tus};
+
+/// [share_dirs] with a `sub` directory in the share, and an empty `uploads` directory.
+fn dirs(test: &str) -> PathBuf {
+    let symlinks = share_dirs("tus", test, |share| {
+        fs::create_dir(share.join("sub")).unwrap()
+    });
+    fs::create_dir(symlinks.with_file_name("uploads")).unwrap();
+    symlinks
+}
+
+/// [tus] with the directories of [dirs], and versions in a `versions` directory beside them.
+fn filter(
+    symlinks: &Path,
+) -> impl Filter<Extract = (warp::reply::Response,), Error = warp::Rejection> + Clone {
+    warp::path("tus").and(tus(
+        "tus",
+        symlinks.to_owned(),
+        symlinks.with_file_name("uploads"),
+        symlinks.with_file_name("versions"),
+        props(symlinks),
+    ))
+}
+
+fn props(symlinks: &Path) -> PropStore {
+    PropStore::new(symlinks, symlinks.with_file_name("props"), false)
+}
+
+fn metadata(file_name: &str) -> String {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(file_name);
+    format!("filename {encoded},is_confidential")
+}
+
+fn request(method: &str, path: &str) -> warp::test::RequestBuilder {
+    warp::test::request()
+        .method(method)
+        .path(path)
+        .header("tus-resumable", "1.0.0")
+}
+
+#[test]
+fn metadata_pairs() {
+    let parsed = parse_metadata(&metadata("a/b.txt")).unwrap();
+    assert_eq!(parsed["filename"], "a/b.txt");
+    assert_eq!(parsed["is_confidential"], "");
+    assert!(parse_metadata("filename !!!").is_err());
+}
+
+#[tokio::test]
+async fn create_resume_and_finish() {
+    let symlinks = dirs("finish");
+    let filter = filter(&symlinks);
+
+    let response = request("POST", "/tus/s/")
+        .header("upload-length", "6")
+        .header("upload-metadata", metadata("sub/file.txt"))
+        .reply(&filter)
+        .await;
+    assert_eq!(response.status(), 201);
+    assert_eq!(response.headers()["tus-resumable"], "1.0.0");
+    let location = response.headers()["location"].to_str().unwrap().to_owned();
+    assert!(location.starts_with("/tus/s/"));
+
+    let patch = |offset: &str, body: &'static str| {
+        request("PATCH", &location)
+            .header("content-type", "application/offset+octet-stream")
+            .header("upload-offset", offset)
+            .body(body)
+    };
+    let response = patch("0", "abc").reply(&filter).await;
+    assert_eq!(response.status(), 204);
+    assert_eq!(response.headers()["upload-offset"], "3");
+
+    // Resume from where the server is, not from where the client thinks it is.
+    assert_eq!(patch("0", "abc").reply(&filter).await.status(), 409);
+    let response = request("HEAD", &location).reply(&filter).await;
+    assert_eq!(response.headers()["upload-offset"], "3");
+    assert_eq!(response.headers()["upload-length"], "6");
+
+    let response = patch("3", "def").reply(&filter).await;
+    assert_eq!(response.status(), 204);
+    assert_eq!(response.headers()["upload-offset"], "6");
+    let share = symlinks.join("s");
+    assert_eq!(
+        fs::read_to_string(share.join("sub/file.txt")).unwrap(),
+        "abcdef"
+    );
+
+    // Another write hash can't see it.
+    let other = location.replace("/s/", "/other/");
+    assert_eq!(request("HEAD", &other).reply(&filter).await.status(), 404);
+    cleanup(&symlinks);
+}
+
+#[tokio::test]
+async fn revoked_write_hash() {
+    let symlinks = dirs("revoked");
+    let filter = filter(&symlinks);
+    let response = request("POST", "/tus/s/")
+        .header("upload-length", "6")
+        .header("upload-metadata", metadata("sub/file.txt"))
+        .reply(&filter)
+        .await;
+    let location = response.headers()["location"].to_str().unwrap().to_owned();
+    let patch = |offset: &str| {
+        request("PATCH", &location)
+            .header("content-type", "application/offset+octet-stream")
+            .header("upload-offset", offset)
+            .body("abc")
+    };
+    assert_eq!(patch("0").reply(&filter).await.status(), 204);
+
+    let link = symlinks.join("s");
+    let share = fs::read_link(&link).unwrap();
+    fs::remove_file(&link).unwrap();
+    assert_eq!(patch("3").reply(&filter).await.status(), 404);
+    assert_eq!(
+        request("HEAD", &location).reply(&filter).await.status(),
+        404
+    );
+    // Nor can the hash lead to another share.
+    let other = symlinks.with_file_name("dirs").join("other");
+    fs::create_dir_all(other.join("sub")).unwrap();
+    symlink(&other, &link).unwrap();
+    assert_eq!(patch("3").reply(&filter).await.status(), 404);
+    assert!(!share.join("sub/file.txt").exists());
+    assert!(!other.join("sub/file.txt").exists());
+
+    fs::remove_file(&link).unwrap();
+    symlink(&share, &link).unwrap();
+    assert_eq!(patch("3").reply(&filter).await.status(), 204);
+    assert_eq!(
+        fs::read_to_string(share.join("sub/file.txt")).unwrap(),
+        "abcabc"
+    );
+    cleanup(&symlinks);
+}
+
+#[tokio::test]
+async fn reject_and_terminate() {
+    let symlinks = dirs("terminate");
+    let filter = filter(&symlinks);
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/tus/s/")
+        .header("upload-length", "1")
+        .header("upload-metadata", metadata("a.txt"))
+        .reply(&filter)
+        .await;
+    assert_eq!(response.status(), 412);
+    assert_eq!(response.headers()["tus-version"], "1.0.0");
+
+    for (name, status) in [("../a.txt", 400), ("missing/a.txt", 409), ("sub", 409)] {
+        let response = request("POST", "/tus/s/")
+            .header("upload-length", "1")
+            .header("upload-metadata", metadata(name))
+            .reply(&filter)
+            .await;
+        assert_eq!(response.status(), status, "{name}");
+    }
+
+    let response = request("POST", "/tus/s/")
+        .header("upload-length", "1")
+        .header("upload-metadata", metadata("a.txt"))
+        .reply(&filter)
+        .await;
+    let location = response.headers()["location"].to_str().unwrap().to_owned();
+    let response = request("DELETE", &location).reply(&filter).await;
+    assert_eq!(response.status(), 204);
+    assert_eq!(
+        request("HEAD", &location).reply(&filter).await.status(),
+        404
+    );
+    assert_eq!(
+        fs::read_dir(symlinks.with_file_name("uploads"))
+            .unwrap()
+            .count(),
+        0
+    );
+    cleanup(&symlinks);
+}
+
+#[tokio::test]
+async fn purge_expired_uploads() {
+    let symlinks = dirs("purge");
+    let filter = filter(&symlinks);
+    request("POST", "/tus/s/")
+        .header("upload-length", "1")
+        .header("upload-metadata", metadata("a.txt"))
+        .reply(&filter)
+        .await;
+    let uploads = symlinks.with_file_name("uploads");
+    assert_eq!(fs::read_dir(&uploads).unwrap().count(), 2);
+
+    assert_eq!(purge_expired(&uploads, SystemTime::now()).unwrap(), 0);
+    let later = SystemTime::now() + Duration::from_secs(2 * 24 * 60 * 60);
+    assert_eq!(purge_expired(&uploads, later).unwrap(), 1);
+    assert_eq!(fs::read_dir(&uploads).unwrap().count(), 0);
+    cleanup(&symlinks);
+}
+
+#[tokio::test]
+async fn empty_upload_finishes_right_away() {
+    let symlinks = dirs("empty");
+    let filter = filter(&symlinks);
+    let share = symlinks.join("s");
+    fs::write(share.join("sub/old.txt"), "old").unwrap();
+    let old = Path::new("s/sub/old.txt");
+    props(&symlinks)
+        .save(old, color_props("red"))
+        .await
+        .unwrap();
+
+    for name in ["sub/empty.txt", "sub/old.txt"] {
+        let response = request("POST", "/tus/s/")
+            .header("upload-length", "0")
+            .header("upload-metadata", metadata(name))
+            .reply(&filter)
+            .await;
+        assert_eq!(response.status(), 201, "{name}");
+        assert_eq!(fs::read(share.join(name)).unwrap(), b"", "{name}");
+
+        let location = response.headers()["location"].to_str().unwrap().to_owned();
+        let response = request("HEAD", &location).reply(&filter).await;
+        assert_eq!(response.headers()["upload-offset"], "0");
+    }
+    // The replaced file's dead properties went with it.
+    assert!(props(&symlinks).load(old).await.unwrap().is_empty());
+    // The replaced file's version.
+    let versions = symlinks.with_file_name("versions").join("tus_empty_share");
+    assert_eq!(fs::read_dir(versions).unwrap().count(), 1);
+    cleanup(&symlinks);
+}
@@ -0,0 +1,150 @@
+//! [ShareVersions] against a temporary directory.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use wdav_crypto_rs::meta::Retention;
+use wdav_crypto_rs::versions::ShareVersions;
+
+/// A fresh `share` directory and `versions` directory under a per-test temporary directory.
+fn dirs(test: &str) -> (PathBuf, PathBuf) {
+    let root = std::env::temp_dir().join(format!("wdav_versions_{test}_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("share/sub")).unwrap();
+    fs::create_dir_all(root.join("versions")).unwrap();
+    (root.join("share"), root.join("versions"))
+}
+
+fn cleanup(share: &Path) {
+    fs::remove_dir_all(share.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn keeps_last_versions() {
+    let (share, root) = dirs("keep_last");
+    let versions = ShareVersions::new(&root, "s");
+    for content in ["1", "2", "3"] {
+        fs::write(share.join("sub/f.txt"), content).unwrap();
+        versions
+            .snapshot(&share, Path::new("sub/f.txt"), Retention::KeepLast(2))
+            .unwrap();
+    }
+    let files = versions.list().unwrap();
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].path, Path::new("sub/f.txt"));
+    assert_eq!(files[0].versions.len(), 2);
+    // Versions live outside of the share, so WebDAV clients can't see them.
+    assert_eq!(fs::read_dir(share.join("sub")).unwrap().count(), 1);
+    cleanup(&share);
+}
+
+#[test]
+fn snapshot_of_directory_covers_its_files_and_off_keeps_nothing() {
+    let (share, root) = dirs("directory");
+    fs::write(share.join("sub/a"), "a").unwrap();
+    fs::write(share.join("sub/b"), "b").unwrap();
+    let versions = ShareVersions::new(&root, "s");
+
+    versions
+        .snapshot(&share, Path::new("sub"), Retention::Off)
+        .unwrap();
+    assert!(versions.list().unwrap().is_empty());
+
+    versions
+        .snapshot(&share, Path::new("sub"), Retention::KeepDays(1))
+        .unwrap();
+    let paths: Vec<_> = versions
+        .list()
+        .unwrap()
+        .into_iter()
+        .map(|f| f.path)
+        .collect();
+    assert_eq!(paths, [PathBuf::from("sub/a"), PathBuf::from("sub/b")]);
+    cleanup(&share);
+}
+
+#[test]
+fn restore_keeps_current_content_as_a_version() {
+    let (share, root) = dirs("restore");
+    let versions = ShareVersions::new(&root, "s");
+    let file = Path::new("sub/f.txt");
+    fs::write(share.join(file), "old").unwrap();
+    versions
+        .snapshot(&share, file, Retention::default())
+        .unwrap();
+    fs::write(share.join(file), "new").unwrap();
+
+    let old = versions.list().unwrap()[0].versions[0].id;
+    versions
+        .restore(&share, file, old, Retention::default())
+        .unwrap();
+    assert_eq!(fs::read_to_string(share.join(file)).unwrap(), "old");
+    assert_eq!(versions.list().unwrap()[0].versions.len(), 2);
+
+    // Deleted files can be restored, too.
+    fs::remove_dir_all(share.join("sub")).unwrap();
+    versions
+        .restore(&share, file, old, Retention::default())
+        .unwrap();
+    assert_eq!(fs::read_to_string(share.join(file)).unwrap(), "old");
+
+    let missing = versions.restore(&share, file, 1, Retention::default());
+    assert_eq!(missing.unwrap_err().kind(), std::io::ErrorKind::NotFound);
+    cleanup(&share);
+}
+
+/// A path longer (percent-encoded) than a file name can be.
+#[test]
+fn long_utf8_path() {
+    let (share, root) = dirs("long");
+    let versions = ShareVersions::new(&root, "s");
+    let name = "ä".repeat(100);
+    assert_eq!(name.len(), 200);
+    let file = Path::new("sub").join(&name);
+    fs::write(share.join(&file), "old").unwrap();
+    versions
+        .snapshot(&share, &file, Retention::default())
+        .unwrap();
+    fs::write(share.join(&file), "new").unwrap();
+
+    let files = versions.list().unwrap();
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].path, file);
+    versions
+        .restore(&share, &file, files[0].versions[0].id, Retention::default())
+        .unwrap();
+    assert_eq!(fs::read_to_string(share.join(&file)).unwrap(), "old");
+    cleanup(&share);
+}
+
+#[test]
+fn cap_removes_oldest_versions_of_any_files() {
+    let (share, root) = dirs("cap");
+    let versions = ShareVersions::new(&root, "s").with_cap(Some(25));
+    let keep = Retention::KeepLast(10);
+    for content in ["1", "2", "3"] {
+        fs::write(share.join("sub/f.txt"), content.repeat(10)).unwrap();
+        versions
+            .snapshot(&share, Path::new("sub/f.txt"), keep)
+            .unwrap();
+    }
+    let files = versions.list().unwrap();
+    assert_eq!(files[0].versions.len(), 2);
+
+    fs::write(share.join("sub/g.txt"), "g".repeat(10)).unwrap();
+    versions
+        .snapshot(&share, Path::new("sub/g.txt"), keep)
+        .unwrap();
+    let files = versions.list().unwrap();
+    let counts: Vec<_> = files.iter().map(|f| f.versions.len()).collect();
+    assert_eq!(counts, [1, 1]);
+    let newest = files[0].versions[0].id;
+    fs::write(share.join("sub/f.txt"), "x").unwrap();
+    versions
+        .restore(&share, Path::new("sub/f.txt"), newest, keep)
+        .unwrap();
+    assert_eq!(
+        fs::read_to_string(share.join("sub/f.txt")).unwrap(),
+        "3".repeat(10)
+    );
+    cleanup(&share);
+}